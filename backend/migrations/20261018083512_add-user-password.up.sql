@@ -0,0 +1,2 @@
+-- modify "users" table
+ALTER TABLE "public"."users" ADD COLUMN "password_hash" character varying NULL;
@@ -0,0 +1,2 @@
+-- reverse: create "invite_codes" table
+DROP TABLE "public"."invite_codes";
@@ -0,0 +1,9 @@
+-- create "invite_codes" table
+CREATE TABLE "public"."invite_codes" (
+  "code_hash" character varying NOT NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  "used_by" uuid NULL,
+  "used_at" timestamptz NULL,
+  PRIMARY KEY ("code_hash"),
+  CONSTRAINT "invite_codes_used_by_fkey" FOREIGN KEY ("used_by") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE SET NULL
+);
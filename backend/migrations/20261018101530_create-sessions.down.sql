@@ -0,0 +1,2 @@
+-- reverse: create "sessions" table
+DROP TABLE "public"."sessions";
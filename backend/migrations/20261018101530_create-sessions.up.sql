@@ -0,0 +1,14 @@
+-- create "sessions" table
+CREATE TABLE "public"."sessions" (
+  "id" uuid NOT NULL,
+  "user_id" uuid NOT NULL,
+  "refresh_token_hash" character varying NOT NULL,
+  "user_agent" character varying NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  "last_refreshed_at" timestamptz NOT NULL DEFAULT now(),
+  "expires_at" timestamptz NOT NULL,
+  "revoked_at" timestamptz NULL,
+  PRIMARY KEY ("id"),
+  CONSTRAINT "sessions_refresh_token_hash_key" UNIQUE ("refresh_token_hash"),
+  CONSTRAINT "sessions_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
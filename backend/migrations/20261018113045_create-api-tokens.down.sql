@@ -0,0 +1,2 @@
+-- reverse: create "api_tokens" table
+DROP TABLE "public"."api_tokens";
@@ -0,0 +1,4 @@
+-- reverse: create "oidc_auth_requests" table
+DROP TABLE "public"."oidc_auth_requests";
+-- reverse: modify "users" table
+ALTER TABLE "public"."users" DROP CONSTRAINT "users_oidc_issuer_oidc_subject_key", DROP COLUMN "oidc_subject", DROP COLUMN "oidc_issuer";
@@ -0,0 +1,11 @@
+-- modify "users" table
+ALTER TABLE "public"."users" ADD COLUMN "oidc_issuer" character varying NULL, ADD COLUMN "oidc_subject" character varying NULL, ADD
+ CONSTRAINT "users_oidc_issuer_oidc_subject_key" UNIQUE ("oidc_issuer", "oidc_subject");
+-- create "oidc_auth_requests" table
+CREATE TABLE "public"."oidc_auth_requests" (
+  "state_hash" character varying NOT NULL,
+  "code_verifier" character varying NOT NULL,
+  "nonce" character varying NOT NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY ("state_hash")
+);
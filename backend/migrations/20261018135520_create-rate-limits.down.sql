@@ -0,0 +1,4 @@
+-- reverse: create "rate_limit_counters" table
+DROP TABLE "public"."rate_limit_counters";
+-- reverse: create "login_lockouts" table
+DROP TABLE "public"."login_lockouts";
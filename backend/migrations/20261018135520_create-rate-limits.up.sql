@@ -0,0 +1,13 @@
+-- create "login_lockouts" table
+CREATE TABLE "public"."login_lockouts" (
+  "username" character varying NOT NULL,
+  "locked_until" timestamptz NOT NULL,
+  PRIMARY KEY ("username")
+);
+-- create "rate_limit_counters" table
+CREATE TABLE "public"."rate_limit_counters" (
+  "key" character varying NOT NULL,
+  "window_start" timestamptz NOT NULL,
+  "count" integer NOT NULL,
+  PRIMARY KEY ("key")
+);
@@ -0,0 +1,12 @@
+-- reverse: create index "task_workspace_id_idx" to table: "task"
+DROP INDEX "public"."task_workspace_id_idx";
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" DROP CONSTRAINT "task_workspace_id_fkey", DROP COLUMN "workspace_id";
+-- reverse: create index "workspace_members_user_id_idx" to table: "workspace_members"
+DROP INDEX "public"."workspace_members_user_id_idx";
+-- reverse: create "workspace_members" table
+DROP TABLE "public"."workspace_members";
+-- reverse: create "workspaces" table
+DROP TABLE "public"."workspaces";
+-- reverse: create enum type "workspace_role"
+DROP TYPE "public"."workspace_role";
@@ -0,0 +1,37 @@
+-- create enum type "workspace_role"
+CREATE TYPE "public"."workspace_role" AS ENUM ('owner', 'editor', 'viewer');
+-- create "workspaces" table
+CREATE TABLE "public"."workspaces" (
+  "id" uuid NOT NULL,
+  "name" character varying NOT NULL,
+  "personal_user_id" uuid NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY ("id"),
+  CONSTRAINT "workspaces_personal_user_id_key" UNIQUE ("personal_user_id"),
+  CONSTRAINT "workspaces_personal_user_id_fkey" FOREIGN KEY ("personal_user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create "workspace_members" table
+CREATE TABLE "public"."workspace_members" (
+  "workspace_id" uuid NOT NULL,
+  "user_id" uuid NOT NULL,
+  "role" "public"."workspace_role" NOT NULL,
+  PRIMARY KEY ("workspace_id", "user_id"),
+  CONSTRAINT "workspace_members_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE,
+  CONSTRAINT "workspace_members_workspace_id_fkey" FOREIGN KEY ("workspace_id") REFERENCES "public"."workspaces" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create index "workspace_members_user_id_idx" to table: "workspace_members"
+CREATE INDEX "workspace_members_user_id_idx" ON "public"."workspace_members" ("user_id");
+-- backfill a personal workspace for every existing user
+INSERT INTO "public"."workspaces" ("id", "name", "personal_user_id")
+  SELECT gen_random_uuid(), "username", "id" FROM "public"."users";
+INSERT INTO "public"."workspace_members" ("workspace_id", "user_id", "role")
+  SELECT "id", "personal_user_id", 'owner' FROM "public"."workspaces";
+-- modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "workspace_id" uuid NULL, ADD
+ CONSTRAINT "task_workspace_id_fkey" FOREIGN KEY ("workspace_id") REFERENCES "public"."workspaces" ("id") ON UPDATE NO ACTION ON DELETE CASCADE;
+-- move existing tasks into the personal workspace of their owners
+UPDATE "public"."task" SET "workspace_id" = "workspaces"."id"
+  FROM "public"."workspaces" WHERE "workspaces"."personal_user_id" = "task"."user_id";
+ALTER TABLE "public"."task" ALTER COLUMN "workspace_id" SET NOT NULL;
+-- create index "task_workspace_id_idx" to table: "task"
+CREATE INDEX "task_workspace_id_idx" ON "public"."task" ("workspace_id");
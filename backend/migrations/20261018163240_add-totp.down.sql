@@ -0,0 +1,6 @@
+-- reverse: create "login_challenges" table
+DROP TABLE "public"."login_challenges";
+-- reverse: create "totp_recovery_codes" table
+DROP TABLE "public"."totp_recovery_codes";
+-- reverse: create "user_totp" table
+DROP TABLE "public"."user_totp";
@@ -0,0 +1,27 @@
+-- create "user_totp" table
+CREATE TABLE "public"."user_totp" (
+  "user_id" uuid NOT NULL,
+  "secret_ciphertext" bytea NOT NULL,
+  "confirmed_at" timestamptz NULL,
+  "last_used_step" bigint NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY ("user_id"),
+  CONSTRAINT "user_totp_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create "totp_recovery_codes" table
+CREATE TABLE "public"."totp_recovery_codes" (
+  "user_id" uuid NOT NULL,
+  "code_hash" character varying NOT NULL,
+  "used_at" timestamptz NULL,
+  PRIMARY KEY ("user_id", "code_hash"),
+  CONSTRAINT "totp_recovery_codes_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create "login_challenges" table
+CREATE TABLE "public"."login_challenges" (
+  "token_hash" character varying NOT NULL,
+  "user_id" uuid NOT NULL,
+  "user_agent" character varying NULL,
+  "expires_at" timestamptz NOT NULL,
+  PRIMARY KEY ("token_hash"),
+  CONSTRAINT "login_challenges_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- reverse: create index "email_tokens_user_id_idx" to table: "email_tokens"
+DROP INDEX "public"."email_tokens_user_id_idx";
+-- reverse: create "email_tokens" table
+DROP TABLE "public"."email_tokens";
+-- reverse: create enum type "email_token_purpose"
+DROP TYPE "public"."email_token_purpose";
+-- reverse: modify "users" table
+ALTER TABLE "public"."users" DROP CONSTRAINT "users_email_key", DROP COLUMN "email";
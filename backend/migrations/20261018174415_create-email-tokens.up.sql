@@ -0,0 +1,17 @@
+-- modify "users" table
+ALTER TABLE "public"."users" ADD COLUMN "email" character varying NULL, ADD
+ CONSTRAINT "users_email_key" UNIQUE ("email");
+-- create enum type "email_token_purpose"
+CREATE TYPE "public"."email_token_purpose" AS ENUM ('magic_link', 'password_reset');
+-- create "email_tokens" table
+CREATE TABLE "public"."email_tokens" (
+  "token_hash" character varying NOT NULL,
+  "user_id" uuid NOT NULL,
+  "purpose" "public"."email_token_purpose" NOT NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  "expires_at" timestamptz NOT NULL,
+  PRIMARY KEY ("token_hash"),
+  CONSTRAINT "email_tokens_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create index "email_tokens_user_id_idx" to table: "email_tokens"
+CREATE INDEX "email_tokens_user_id_idx" ON "public"."email_tokens" ("user_id");
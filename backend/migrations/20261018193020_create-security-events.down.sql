@@ -0,0 +1,6 @@
+-- reverse: create index "security_events_user_id_created_at_idx" to table: "security_events"
+DROP INDEX "public"."security_events_user_id_created_at_idx";
+-- reverse: create "security_events" table
+DROP TABLE "public"."security_events";
+-- reverse: create enum type "security_event_kind"
+DROP TYPE "public"."security_event_kind";
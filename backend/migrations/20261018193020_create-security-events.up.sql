@@ -0,0 +1,16 @@
+-- create enum type "security_event_kind"
+CREATE TYPE "public"."security_event_kind" AS ENUM ('login_succeeded', 'login_failed', 'session_refreshed', 'session_revoked', 'api_token_created', 'api_token_deleted', 'password_changed', 'two_factor_enabled', 'two_factor_disabled', 'recovery_codes_regenerated');
+-- create "security_events" table
+CREATE TABLE "public"."security_events" (
+  "id" uuid NOT NULL,
+  "user_id" uuid NOT NULL,
+  "kind" "public"."security_event_kind" NOT NULL,
+  "detail" character varying NULL,
+  "ip_address" character varying NULL,
+  "user_agent" character varying NULL,
+  "created_at" timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY ("id"),
+  CONSTRAINT "security_events_user_id_fkey" FOREIGN KEY ("user_id") REFERENCES "public"."users" ("id") ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- create index "security_events_user_id_created_at_idx" to table: "security_events"
+CREATE INDEX "security_events_user_id_created_at_idx" ON "public"."security_events" ("user_id", "created_at", "id");
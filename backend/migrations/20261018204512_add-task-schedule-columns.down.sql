@@ -0,0 +1,13 @@
+-- reverse: create index "task_workspace_id_scheduled_idx" to table: "task"
+DROP INDEX "public"."task_workspace_id_scheduled_idx";
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "scheduled_on" json NULL;
+-- reverse: copy the epochs of "scheduled_on" to the new columns
+UPDATE "public"."task" SET "scheduled_on" = json_build_object('Date', to_char("scheduled_start", 'YYYY-MM-DD')) WHERE "scheduled_kind" = 'date';
+UPDATE "public"."task" SET "scheduled_on" = json_build_object('Week', json_build_object('start_date', to_char("scheduled_start", 'YYYY-MM-DD'))) WHERE "scheduled_kind" = 'week';
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" DROP COLUMN "scheduled_end", DROP COLUMN "scheduled_kind", DROP COLUMN "scheduled_start";
+-- reverse: drop index "task_workspace_id_idx" from table: "task"
+CREATE INDEX "task_workspace_id_idx" ON "public"."task" ("workspace_id");
+-- reverse: create enum type "epoch_kind"
+DROP TYPE "public"."epoch_kind";
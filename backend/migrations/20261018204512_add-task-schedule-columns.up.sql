@@ -0,0 +1,14 @@
+-- create enum type "epoch_kind"
+CREATE TYPE "public"."epoch_kind" AS ENUM ('date', 'week');
+-- drop index "task_workspace_id_idx" from table: "task"
+DROP INDEX "public"."task_workspace_id_idx";
+-- modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "scheduled_kind" "public"."epoch_kind" NULL, ADD COLUMN "scheduled_start" date NULL, ADD COLUMN "scheduled_end" date NULL;
+-- copy the epochs of "scheduled_on" to the new columns
+UPDATE "public"."task" SET "scheduled_kind" = 'date', "scheduled_start" = ("scheduled_on" ->> 'Date')::date, "scheduled_end" = ("scheduled_on" ->> 'Date')::date + 1 WHERE "scheduled_on" ->> 'Date' IS NOT NULL;
+UPDATE "public"."task" SET "scheduled_kind" = 'week', "scheduled_start" = ("scheduled_on" -> 'Week' ->> 'start_date')::date, "scheduled_end" = ("scheduled_on" -> 'Week' ->> 'start_date')::date + 7 WHERE "scheduled_on" -> 'Week' IS NOT NULL;
+UPDATE "public"."task" SET "schedule_index_date" = "scheduled_start";
+-- modify "task" table
+ALTER TABLE "public"."task" DROP COLUMN "scheduled_on";
+-- create index "task_workspace_id_scheduled_idx" to table: "task"
+CREATE INDEX "task_workspace_id_scheduled_idx" ON "public"."task" ("workspace_id", "scheduled_start", "scheduled_end");
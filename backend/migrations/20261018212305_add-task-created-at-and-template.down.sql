@@ -0,0 +1,4 @@
+-- reverse: create index "task_recurring_template_id_idx" to table: "task"
+DROP INDEX "public"."task_recurring_template_id_idx";
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" DROP CONSTRAINT "task_recurring_template_id_fkey", DROP COLUMN "created_at", DROP COLUMN "recurring_template_id";
@@ -0,0 +1,5 @@
+-- modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "recurring_template_id" uuid NULL, ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT now(), ADD
+ CONSTRAINT "task_recurring_template_id_fkey" FOREIGN KEY ("recurring_template_id") REFERENCES "public"."task" ("id") ON UPDATE NO ACTION ON DELETE SET NULL;
+-- create index "task_recurring_template_id_idx" to table: "task"
+CREATE INDEX "task_recurring_template_id_idx" ON "public"."task" ("recurring_template_id");
@@ -0,0 +1,2 @@
+-- reverse: create index "task_title_search_idx" to table: "task"
+DROP INDEX "public"."task_title_search_idx";
@@ -0,0 +1,2 @@
+-- create index "task_title_search_idx" to table: "task"
+CREATE INDEX "task_title_search_idx" ON "public"."task" USING GIN (to_tsvector('english'::regconfig, (title)::text));
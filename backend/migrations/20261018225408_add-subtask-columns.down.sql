@@ -0,0 +1,4 @@
+-- reverse: create index "task_parent_id_idx" to table: "task"
+DROP INDEX "public"."task_parent_id_idx";
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" DROP COLUMN "complete_with_subtasks";
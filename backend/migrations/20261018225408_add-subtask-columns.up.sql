@@ -0,0 +1,4 @@
+-- modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "complete_with_subtasks" boolean NOT NULL DEFAULT false;
+-- create index "task_parent_id_idx" to table: "task"
+CREATE INDEX "task_parent_id_idx" ON "public"."task" ("parent_id");
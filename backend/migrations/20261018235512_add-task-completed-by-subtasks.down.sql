@@ -0,0 +1,2 @@
+-- reverse: modify "task" table
+ALTER TABLE "public"."task" DROP COLUMN "completed_by_subtasks";
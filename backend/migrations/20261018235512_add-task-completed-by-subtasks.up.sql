@@ -0,0 +1,2 @@
+-- modify "task" table
+ALTER TABLE "public"."task" ADD COLUMN "completed_by_subtasks" boolean NOT NULL DEFAULT false;
@@ -0,0 +1,4 @@
+-- lockouts only last minutes, so the ones of other client IPs are dropped
+DELETE FROM "public"."login_lockouts";
+-- reverse: modify "login_lockouts" table
+ALTER TABLE "public"."login_lockouts" DROP CONSTRAINT "login_lockouts_pkey", DROP COLUMN "client_ip", ADD PRIMARY KEY ("username");
@@ -0,0 +1,4 @@
+-- lockouts only last minutes, so the ones without a client IP are dropped
+DELETE FROM "public"."login_lockouts";
+-- modify "login_lockouts" table
+ALTER TABLE "public"."login_lockouts" DROP CONSTRAINT "login_lockouts_pkey", ADD COLUMN "client_ip" character varying NOT NULL, ADD PRIMARY KEY ("username", "client_ip");
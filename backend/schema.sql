@@ -1,19 +1,168 @@
 CREATE TABLE users (
   id uuid PRIMARY KEY,
-  username varchar(50) UNIQUE NOT NULL
+  username varchar(50) UNIQUE NOT NULL,
+  password_hash varchar,
+  oidc_issuer varchar,
+  oidc_subject varchar,
+  UNIQUE (oidc_issuer, oidc_subject),
+  email varchar UNIQUE
 );
 
+CREATE TABLE invite_codes (
+  code_hash varchar PRIMARY KEY,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  used_by uuid,
+  FOREIGN KEY (used_by) REFERENCES users(id) ON DELETE SET NULL,
+  used_at timestamptz
+);
+
+CREATE TABLE sessions (
+  id uuid PRIMARY KEY,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  refresh_token_hash varchar UNIQUE NOT NULL,
+  user_agent varchar,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  last_refreshed_at timestamptz NOT NULL DEFAULT now(),
+  expires_at timestamptz NOT NULL,
+  revoked_at timestamptz
+);
+
+CREATE TABLE oidc_auth_requests (
+  state_hash varchar PRIMARY KEY,
+  code_verifier varchar NOT NULL,
+  nonce varchar NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE api_tokens (
+  id uuid PRIMARY KEY,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  name varchar NOT NULL,
+  token_hash varchar UNIQUE NOT NULL,
+  scopes varchar[] NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  last_used_at timestamptz
+);
+
+CREATE TABLE user_totp (
+  user_id uuid PRIMARY KEY,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  secret_ciphertext bytea NOT NULL,
+  confirmed_at timestamptz,
+  last_used_step bigint,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE totp_recovery_codes (
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  code_hash varchar NOT NULL,
+  used_at timestamptz,
+  PRIMARY KEY (user_id, code_hash)
+);
+
+CREATE TABLE login_challenges (
+  token_hash varchar PRIMARY KEY,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  user_agent varchar,
+  expires_at timestamptz NOT NULL
+);
+
+CREATE TYPE email_token_purpose AS ENUM ('magic_link', 'password_reset');
+
+CREATE TABLE email_tokens (
+  token_hash varchar PRIMARY KEY,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  purpose email_token_purpose NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  expires_at timestamptz NOT NULL
+);
+
+CREATE INDEX email_tokens_user_id_idx ON email_tokens (user_id);
+
+CREATE TYPE security_event_kind AS ENUM ('login_succeeded', 'login_failed', 'session_refreshed', 'session_revoked', 'api_token_created', 'api_token_deleted', 'password_changed', 'two_factor_enabled', 'two_factor_disabled', 'recovery_codes_regenerated');
+
+CREATE TABLE security_events (
+  id uuid PRIMARY KEY,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  kind security_event_kind NOT NULL,
+  detail varchar,
+  ip_address varchar,
+  user_agent varchar,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX security_events_user_id_created_at_idx ON security_events (user_id, created_at, id);
+
+CREATE TABLE rate_limit_counters (
+  key varchar PRIMARY KEY,
+  window_start timestamptz NOT NULL,
+  count integer NOT NULL
+);
+
+CREATE TABLE login_lockouts (
+  username varchar NOT NULL,
+  client_ip varchar NOT NULL,
+  locked_until timestamptz NOT NULL,
+  PRIMARY KEY (username, client_ip)
+);
+
+CREATE TABLE workspaces (
+  id uuid PRIMARY KEY,
+  name varchar NOT NULL,
+  personal_user_id uuid UNIQUE,
+  FOREIGN KEY (personal_user_id) REFERENCES users(id) ON DELETE CASCADE,
+  created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TYPE workspace_role AS ENUM ('owner', 'editor', 'viewer');
+
+CREATE TABLE workspace_members (
+  workspace_id uuid NOT NULL,
+  FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+  user_id uuid NOT NULL,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  role workspace_role NOT NULL,
+  PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+CREATE TYPE epoch_kind AS ENUM ('date', 'week');
+
 CREATE TABLE task (
   id uuid PRIMARY KEY,
   user_id uuid NOT NULL,
   FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+  workspace_id uuid NOT NULL,
+  FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
   recurring_spec json,
   next_recurring_check_date date,
-  scheduled_on json,
+  recurring_template_id uuid,
+  FOREIGN KEY (recurring_template_id) REFERENCES task(id) ON DELETE SET NULL,
+  scheduled_kind epoch_kind,
+  scheduled_start date,
+  scheduled_end date,
   schedule_index_date date,
   complete_date date,
   parent_id uuid,
   FOREIGN KEY (parent_id) REFERENCES task(id) ON DELETE SET NULL,
+  complete_with_subtasks boolean NOT NULL DEFAULT false,
+  completed_by_subtasks boolean NOT NULL DEFAULT false,
   title varchar NOT NULL,
-  cost integer
+  cost integer,
+  created_at timestamptz NOT NULL DEFAULT now()
 );
+
+CREATE INDEX task_workspace_id_scheduled_idx ON task (workspace_id, scheduled_start, scheduled_end);
+
+CREATE INDEX task_recurring_template_id_idx ON task (recurring_template_id);
+
+CREATE INDEX task_parent_id_idx ON task (parent_id);
+
+CREATE INDEX task_title_search_idx ON task USING GIN (to_tsvector('english', title));
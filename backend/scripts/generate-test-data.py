@@ -41,6 +41,7 @@ def sql_table(table_name=None):
 class User:
     id: uuid.UUID
     username: str
+    password_hash: str | None = None
 
 
 @sql_table("task")
@@ -48,12 +49,19 @@ class User:
 class Task:
     id: uuid.UUID
     user_id: uuid.UUID
-    scheduled_on: Json | None
+    scheduled_kind: str | None
+    scheduled_start: datetime.date | None
+    scheduled_end: datetime.date | None
+    schedule_index_date: datetime.date | None
     title: str
     cost: int | None
 
 
 METEOR_UUID = uuid.UUID("00000000-0000-4000-8001-000000000000")
+# Argon2id hash of the password "meteor".
+METEOR_PASSWORD_HASH = (
+    "$argon2id$v=19$m=19456,t=2,p=1$+98Xq1lQj2pU+ZRotsNOUw$z5bhFMREzt7vNPD8fx0d4AM8m5/ZR1hysoYf4FTb1PI"
+)
 
 
 def task_uuid(n: int):
@@ -61,24 +69,30 @@ def task_uuid(n: int):
 
 
 def generate_test_data():
-    meteor = User(METEOR_UUID, "meteor")
+    meteor = User(METEOR_UUID, "meteor", METEOR_PASSWORD_HASH)
     users = [meteor, User(uuid.uuid4(), "test")]
     tasks = [
         Task(
             task_uuid(1),
             meteor.id,
-            Json({"Date": "2024-08-03"}),
+            "date",
+            datetime.date(2024, 8, 3),
+            datetime.date(2024, 8, 4),
+            datetime.date(2024, 8, 3),
             "Task #1",
             1,
         ),
         Task(
             task_uuid(2),
             meteor.id,
-            Json({"Week": {"start_date": "2024-08-05"}}),
+            "week",
+            datetime.date(2024, 8, 5),
+            datetime.date(2024, 8, 12),
+            datetime.date(2024, 8, 5),
             "Task #2",
             3,
         ),
-        Task(task_uuid(3), meteor.id, None, "Task #3", None),
+        Task(task_uuid(3), meteor.id, None, None, None, None, "Task #3", None),
     ]
 
     return [*users, *tasks]
@@ -3,6 +3,7 @@ import requests
 
 response = requests.post('http://localhost:8000/auth/login', json={
     'username': 'meteor',
+    'password': 'meteor',
 })
 response_json = response.json()
 token = response_json['token']
@@ -0,0 +1,191 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set, TransactionTrait,
+};
+use uuid::Uuid;
+
+use super::{AppError, AppResult};
+use crate::{
+    auth::{generate_api_token, record_security_event, ClientInfo, SecurityEventKind},
+    db::DatabaseTransactionExt,
+    entities,
+};
+
+/// A permission that can be granted to a personal API token.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    strum::Display,
+    strum::EnumString,
+    async_graphql::Enum,
+)]
+pub(crate) enum Scope {
+    #[strum(serialize = "tasks:read")]
+    TasksRead,
+    #[strum(serialize = "tasks:write")]
+    TasksWrite,
+}
+
+impl Scope {
+    /// Parses the scopes stored in the database, skipping the ones that no longer exist.
+    pub(crate) fn parse_all(scopes: &[String]) -> Vec<Scope> {
+        scopes
+            .iter()
+            .filter_map(|scope| Scope::from_str(scope).ok())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ApiToken {
+    pub(crate) id: Uuid,
+    pub(crate) name: String,
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Creates a personal API token for the user. Returns the token along with its secret, which is
+/// only stored hashed and thus cannot be retrieved later.
+pub(crate) async fn create_api_token(
+    user_id: Uuid,
+    name: String,
+    mut scopes: Vec<Scope>,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<(ApiToken, String)> {
+    if name.trim().is_empty() {
+        return Err(AppError::invalid_field(
+            "name",
+            "API token name must not be empty",
+        ));
+    }
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(AppError::invalid_field(
+            "scopes",
+            "API token must have at least one scope",
+        ));
+    }
+
+    let (secret, token_hash) = generate_api_token();
+    let client = client.clone();
+    let tx = db_conn.begin().await?;
+    let api_token = tx
+        .with(|tx| async move {
+            let api_token = entities::api_tokens::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                user_id: Set(user_id),
+                name: Set(name),
+                token_hash: Set(token_hash),
+                scopes: Set(scopes.iter().map(Scope::to_string).collect()),
+                ..Default::default()
+            }
+            .insert(&*tx)
+            .await?;
+            record_security_event(
+                user_id,
+                SecurityEventKind::ApiTokenCreated,
+                Some(api_token.name.clone()),
+                &client,
+                &*tx,
+            )
+            .await?;
+            Ok::<_, AppError>(api_token)
+        })
+        .await?;
+
+    Ok((api_token.into(), secret))
+}
+
+pub(crate) async fn list_api_tokens(
+    user_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<ApiToken>> {
+    Ok(entities::api_tokens::Entity::find()
+        .filter(entities::api_tokens::Column::UserId.eq(user_id))
+        .order_by_asc(entities::api_tokens::Column::CreatedAt)
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(ApiToken::from)
+        .collect())
+}
+
+pub(crate) async fn delete_api_token(
+    user_id: Uuid,
+    id: Uuid,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<()> {
+    let client = client.clone();
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let api_token = entities::api_tokens::Entity::find_by_id(id)
+            .filter(entities::api_tokens::Column::UserId.eq(user_id))
+            .lock_exclusive()
+            .one(&*tx)
+            .await?
+            .ok_or_else(|| AppError::api_token_not_found(id))?;
+        entities::api_tokens::Entity::delete_by_id(id)
+            .exec(&*tx)
+            .await?;
+        record_security_event(
+            user_id,
+            SecurityEventKind::ApiTokenDeleted,
+            Some(api_token.name),
+            &client,
+            &*tx,
+        )
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+impl From<entities::api_tokens::Model> for ApiToken {
+    fn from(value: entities::api_tokens::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            scopes: Scope::parse_all(&value.scopes),
+            created_at: value.created_at.into(),
+            last_used_at: value.last_used_at.map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn scopes_round_trip_through_strings() {
+        let stored = [Scope::TasksRead, Scope::TasksWrite].map(|scope| scope.to_string());
+
+        expect_that!(stored, elements_are![eq("tasks:read"), eq("tasks:write")]);
+        expect_that!(
+            Scope::parse_all(&stored),
+            elements_are![eq(&Scope::TasksRead), eq(&Scope::TasksWrite)]
+        );
+    }
+
+    #[googletest::test]
+    fn unknown_scopes_are_skipped() {
+        expect_that!(
+            Scope::parse_all(&["tasks:read".to_owned(), "admin".to_owned()]),
+            elements_are![eq(&Scope::TasksRead)]
+        );
+    }
+}
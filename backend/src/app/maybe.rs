@@ -15,7 +15,6 @@ impl<T> Maybe<T> {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn is_defined(&self) -> bool {
         matches!(self, Maybe::Some(_))
     }
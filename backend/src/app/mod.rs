@@ -1,11 +1,21 @@
 use std::sync::Arc;
 
+use async_graphql::ErrorExtensions;
 use sea_orm::{DbErr, TransactionError};
 use uuid::Uuid;
 
+use crate::error::{ApiError, ErrorCode};
+
+pub(crate) mod api_token;
 pub(crate) mod maybe;
+pub(crate) mod security_event;
+pub(crate) mod session;
 pub(crate) mod task;
+pub(crate) mod task_event;
+pub(crate) mod task_search;
 pub(crate) mod time;
+pub(crate) mod two_factor;
+pub(crate) mod workspace;
 
 pub type AppResult<T> = Result<T, AppError>;
 
@@ -13,8 +23,17 @@ pub type AppResult<T> = Result<T, AppError>;
 pub enum AppError {
     #[error("{typ} with id = {id} is not found")]
     ResourceNotFound { typ: ResourceType, id: Uuid },
+    #[error("permission denied: {reason}")]
+    PermissionDenied { reason: String },
+    /// `field` is the path of the input that is not valid, if it is a single one.
     #[error("invalid input: {reason}")]
-    InvalidInput { reason: String },
+    InvalidInput {
+        reason: String,
+        field: Option<&'static str>,
+    },
+    /// Changes were missed by a subscription, so what the client has may be out of date.
+    #[error("task changes may have been missed, reload the tasks and subscribe again")]
+    EventsMissed,
     #[error("internal error: {0}")]
     Internal(#[from] anyhow::Error),
 }
@@ -22,6 +41,10 @@ pub enum AppError {
 #[derive(Debug, strum::Display)]
 pub enum ResourceType {
     Task,
+    Session,
+    ApiToken,
+    Workspace,
+    WorkspaceMember,
 }
 
 impl AppError {
@@ -32,52 +55,83 @@ impl AppError {
         }
     }
 
+    fn session_not_found(id: Uuid) -> Self {
+        AppError::ResourceNotFound {
+            typ: ResourceType::Session,
+            id,
+        }
+    }
+
+    fn api_token_not_found(id: Uuid) -> Self {
+        AppError::ResourceNotFound {
+            typ: ResourceType::ApiToken,
+            id,
+        }
+    }
+
+    fn workspace_not_found(id: Uuid) -> Self {
+        AppError::ResourceNotFound {
+            typ: ResourceType::Workspace,
+            id,
+        }
+    }
+
+    fn workspace_member_not_found(id: Uuid) -> Self {
+        AppError::ResourceNotFound {
+            typ: ResourceType::WorkspaceMember,
+            id,
+        }
+    }
+
+    fn permission_denied(reason: impl Into<String>) -> Self {
+        AppError::PermissionDenied {
+            reason: reason.into(),
+        }
+    }
+
     fn invalid_input(reason: impl Into<String>) -> Self {
         AppError::InvalidInput {
             reason: reason.into(),
+            field: None,
+        }
+    }
+
+    /// An invalid input of the field at the path, e.g. `recurringSpec.startDate`.
+    fn invalid_field(field: &'static str, reason: impl Into<String>) -> Self {
+        AppError::InvalidInput {
+            reason: reason.into(),
+            field: Some(field),
         }
     }
 }
 
-// #[derive(Debug)]
-// pub(crate) enum BadRequestReason {
-//     InvalidTaskScheduleSpec,
-// RequiredFieldIsNull { field: String },
-// }
-
-// impl Display for BadRequestReason {
-//     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-//         match self {
-// Self::InvalidDateRange => write!(
-//     f,
-//     "The date range is not valid. \
-//      Start date or end date must be given if the other is, \
-//      and the end date must be later than the start date."
-// ),
-//             Self::InvalidTaskScheduleSpec => {
-//                 write!(f, "The task schedule date spec JSON is not valid.")
-//             }
-//             Self::RequiredFieldIsNull { field } => {
-//                 write!(f, "Field {field} is a required field, but set to null.")
-//             }
-//         }
-//     }
-// }
-
-// impl ErrorExtensions for AppError {
-//     fn extend(&self) -> async_graphql::Error {
-//         async_graphql::Error::new(self.to_string()).extend_with(|_, e| {
-//             use AppError::*;
-//             match self {
-//                 ResourceNotFound(..) => e.set("code", "NOT_FOUND"),
-//                 BadRequest(..) => e.set("code", "BAD_REQUEST"),
-//                 Internal(..) => {
-//                     e.set("code", "INTERNAL_SERVER_ERROR");
-//                 }
-//             }
-//         })
-//     }
-// }
+impl From<&AppError> for ApiError {
+    fn from(value: &AppError) -> Self {
+        match value {
+            AppError::ResourceNotFound { typ, .. } => {
+                ApiError::new(ErrorCode::NotFound, value.to_string()).with_resource(typ)
+            }
+            AppError::PermissionDenied { .. } => {
+                ApiError::new(ErrorCode::PermissionDenied, value.to_string())
+            }
+            AppError::InvalidInput { field, .. } => {
+                let error = ApiError::new(ErrorCode::InvalidInput, value.to_string());
+                match field {
+                    Some(field) => error.with_field(field),
+                    None => error,
+                }
+            }
+            AppError::EventsMissed => ApiError::new(ErrorCode::EventsMissed, value.to_string()),
+            AppError::Internal(err) => ApiError::internal(err),
+        }
+    }
+}
+
+impl ErrorExtensions for AppError {
+    fn extend(&self) -> async_graphql::Error {
+        ApiError::from(self).extend()
+    }
+}
 
 impl From<DbErr> for AppError {
     fn from(value: sea_orm::DbErr) -> Self {
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::AppResult;
+use crate::entities::{self, sea_orm_active_enums::SecurityEventKind};
+
+/// What happened in an event of the security audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, async_graphql::Enum)]
+#[graphql(name = "SecurityEventKind")]
+pub(crate) enum EventKind {
+    /// A session was started; the detail is how the user logged in.
+    LoginSucceeded,
+    /// A wrong password or code was given; the detail is which one.
+    LoginFailed,
+    SessionRefreshed,
+    /// A session was ended by logging out or by revoking it from another session.
+    SessionRevoked,
+    /// A personal API token was created; the detail is its name.
+    ApiTokenCreated,
+    /// A personal API token was deleted; the detail is its name.
+    ApiTokenDeleted,
+    PasswordChanged,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    RecoveryCodesRegenerated,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SecurityEvent {
+    pub(crate) id: Uuid,
+    pub(crate) kind: EventKind,
+    pub(crate) detail: Option<String>,
+    pub(crate) ip_address: Option<String>,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+/// The position of an event in the log, which is ordered newest first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SecurityEventCursor {
+    created_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl SecurityEvent {
+    pub(crate) fn cursor(&self) -> SecurityEventCursor {
+        SecurityEventCursor {
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+/// Lists up to `limit` events of the user, newest first, starting after the cursor. Also returns
+/// whether there are more events after the returned ones.
+pub(crate) async fn list_security_events(
+    user_id: Uuid,
+    after: Option<SecurityEventCursor>,
+    limit: u64,
+    db_conn: &DatabaseConnection,
+) -> AppResult<(Vec<SecurityEvent>, bool)> {
+    let mut query = entities::security_events::Entity::find()
+        .filter(entities::security_events::Column::UserId.eq(user_id));
+    if let Some(after) = after {
+        query = query.filter(
+            Condition::any()
+                .add(entities::security_events::Column::CreatedAt.lt(after.created_at))
+                .add(
+                    entities::security_events::Column::CreatedAt
+                        .eq(after.created_at)
+                        .and(entities::security_events::Column::Id.lt(after.id)),
+                ),
+        );
+    }
+    // One more than asked for tells whether there is a next page.
+    let mut events = query
+        .order_by_desc(entities::security_events::Column::CreatedAt)
+        .order_by_desc(entities::security_events::Column::Id)
+        .limit(limit + 1)
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(SecurityEvent::from)
+        .collect::<Vec<_>>();
+    let has_next_page = events.len() as u64 > limit;
+    events.truncate(limit as usize);
+
+    Ok((events, has_next_page))
+}
+
+impl From<entities::security_events::Model> for SecurityEvent {
+    fn from(value: entities::security_events::Model) -> Self {
+        Self {
+            id: value.id,
+            kind: value.kind.into(),
+            detail: value.detail,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            created_at: value.created_at.into(),
+        }
+    }
+}
+
+impl From<SecurityEventKind> for EventKind {
+    fn from(value: SecurityEventKind) -> Self {
+        match value {
+            SecurityEventKind::LoginSucceeded => EventKind::LoginSucceeded,
+            SecurityEventKind::LoginFailed => EventKind::LoginFailed,
+            SecurityEventKind::SessionRefreshed => EventKind::SessionRefreshed,
+            SecurityEventKind::SessionRevoked => EventKind::SessionRevoked,
+            SecurityEventKind::ApiTokenCreated => EventKind::ApiTokenCreated,
+            SecurityEventKind::ApiTokenDeleted => EventKind::ApiTokenDeleted,
+            SecurityEventKind::PasswordChanged => EventKind::PasswordChanged,
+            SecurityEventKind::TwoFactorEnabled => EventKind::TwoFactorEnabled,
+            SecurityEventKind::TwoFactorDisabled => EventKind::TwoFactorDisabled,
+            SecurityEventKind::RecoveryCodesRegenerated => EventKind::RecoveryCodesRegenerated,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+};
+use uuid::Uuid;
+
+use super::{AppError, AppResult};
+use crate::{
+    auth::{active_session_condition, record_security_event, ClientInfo, SecurityEventKind},
+    entities,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) struct Session {
+    pub(crate) id: Uuid,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) last_refreshed_at: DateTime<Utc>,
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+/// Lists the sessions of the user that are neither revoked nor expired, most recently used first.
+pub(crate) async fn list_sessions(
+    user_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<Session>> {
+    Ok(entities::sessions::Entity::find()
+        .filter(entities::sessions::Column::UserId.eq(user_id))
+        .filter(active_session_condition())
+        .order_by_desc(entities::sessions::Column::LastRefreshedAt)
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(Session::from)
+        .collect())
+}
+
+pub(crate) async fn revoke_session(
+    user_id: Uuid,
+    session_id: Uuid,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<()> {
+    let result = entities::sessions::Entity::update_many()
+        .col_expr(
+            entities::sessions::Column::RevokedAt,
+            Expr::current_timestamp().into(),
+        )
+        .filter(entities::sessions::Column::Id.eq(session_id))
+        .filter(entities::sessions::Column::UserId.eq(user_id))
+        .filter(active_session_condition())
+        .exec(db_conn)
+        .await?;
+
+    if result.rows_affected != 1 {
+        return Err(AppError::session_not_found(session_id));
+    }
+    record_security_event(
+        user_id,
+        SecurityEventKind::SessionRevoked,
+        None,
+        client,
+        db_conn,
+    )
+    .await?;
+
+    Ok(())
+}
+
+impl From<entities::sessions::Model> for Session {
+    fn from(value: entities::sessions::Model) -> Self {
+        Self {
+            id: value.id,
+            user_agent: value.user_agent,
+            created_at: value.created_at.into(),
+            last_refreshed_at: value.last_refreshed_at.into(),
+            expires_at: value.expires_at.into(),
+        }
+    }
+}
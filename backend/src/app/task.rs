@@ -1,36 +1,51 @@
-use anyhow::Context;
+use std::{collections::HashMap, future::Future};
+
 use async_graphql::MaybeUndefined;
-use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc, Weekday};
 use futures::FutureExt;
 use sea_orm::{
+    sea_query::{NullOrdering, Query, SimpleExpr},
     ActiveModelTrait,
     ActiveValue::{self, NotSet},
-    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
-    Set, TransactionTrait,
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, Set,
+    TransactionTrait,
 };
+use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::{
     app::time::EpochLike,
-    db::DatabaseTransactionExt,
-    entities::{self, task::Model as TaskModel},
+    db::{DatabaseTransactionExt, TransactionWrapper},
+    entities::{self, sea_orm_active_enums::EpochKind as DbEpochKind, task::Model as TaskModel},
 };
 
 use super::{
     maybe::Maybe,
-    time::{today, Epoch, EpochKind, RecurringPattern, RecurringSpec},
+    task_event::{notify_task_changed, TaskChangeKind},
+    time::{today, DateRange, Epoch, EpochKind, RecurringPattern, RecurringSpec},
+    workspace::{member_role, personal_workspace_id, require_role, Role},
     AppError, AppResult,
 };
 
 #[derive(Clone, Debug)]
 pub(crate) struct Task {
     pub(crate) id: Uuid,
+    pub(crate) workspace_id: Uuid,
     pub(crate) scheduled_on: Option<Epoch>,
     pub(crate) complete_date: Option<NaiveDate>,
     pub(crate) recurring_data: Option<RecurringData>,
+    /// The recurring task this task was generated from, if it is an occurrence of one.
+    pub(crate) recurring_template_id: Option<Uuid>,
+    /// The task this task is a subtask of.
+    pub(crate) parent_id: Option<Uuid>,
+    /// Whether the task is completed once all its subtasks are.
+    pub(crate) complete_with_subtasks: bool,
     pub(crate) title: String,
+    /// The cost of the task, which is the sum of the costs of its subtasks if it has any.
     pub(crate) cost: Option<i32>,
+    pub(crate) created_at: DateTime<Utc>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -44,14 +59,17 @@ impl Task {
         self.complete_date.is_some()
     }
 
+    pub(crate) fn recurrence(&self) -> Recurrence {
+        if self.recurring_data.is_some() {
+            Recurrence::Recurring
+        } else if self.recurring_template_id.is_some() {
+            Recurrence::Generated
+        } else {
+            Recurrence::OneOff
+        }
+    }
+
     fn into_active_model(self) -> AppResult<entities::task::ActiveModel> {
-        let scheduled_on = self
-            .scheduled_on
-            .map(serde_json::to_value)
-            .transpose()
-            .map_err(|err| {
-                AppError::invalid_input(format!("failed to convert `scheduled_on` to JSON: {err}"))
-            })?;
         let (next_recurring_check_date, recurring_spec) = match &self.recurring_data {
             Some(RecurringData {
                 next_check_date,
@@ -68,16 +86,22 @@ impl Task {
             None => (None, None),
         };
 
-        Ok(entities::task::ActiveModel {
+        let mut model = entities::task::ActiveModel {
             id: Set(self.id),
-            scheduled_on: Set(scheduled_on),
+            workspace_id: Set(self.workspace_id),
             next_recurring_check_date: Set(next_recurring_check_date),
             recurring_spec: Set(recurring_spec),
+            recurring_template_id: Set(self.recurring_template_id),
+            parent_id: Set(self.parent_id),
+            complete_with_subtasks: Set(self.complete_with_subtasks),
             title: Set(self.title),
             cost: Set(self.cost),
             complete_date: Set(self.complete_date),
+            created_at: Set(self.created_at.into()),
             ..Default::default()
-        })
+        };
+        set_schedule(&mut model, self.scheduled_on);
+        Ok(model)
     }
 
     async fn schedule_recurring_until(
@@ -94,20 +118,34 @@ impl Task {
         let mut next_schedule_epoch = recurring_data
             .spec
             .next_starting_from(recurring_data.next_check_date);
-        while next_schedule_epoch.start_date() < until {
+        while next_schedule_epoch.start_date() < until
+            && !recurring_data.spec.has_ended_before(next_schedule_epoch)
+        {
             let mut child_task_model = Task {
                 id: Uuid::new_v4(),
+                workspace_id: self.workspace_id,
                 scheduled_on: Some(next_schedule_epoch),
                 complete_date: None,
                 recurring_data: None,
+                recurring_template_id: Some(self.id),
+                parent_id: None,
+                complete_with_subtasks: false,
                 title: self.title.clone(),
                 cost: self.cost,
+                created_at: Utc::now(),
             }
             .into_active_model()?;
             child_task_model.user_id = Set(user_id);
 
             let child_task = child_task_model.insert(db_conn).await?;
-            info!(epoch = ?child_task.scheduled_on, id = ?child_task.id, "Scheduled recurring task");
+            info!(epoch = ?next_schedule_epoch, id = ?child_task.id, "Scheduled recurring task");
+            notify_task_changed(
+                TaskChangeKind::Created,
+                child_task.id,
+                child_task.workspace_id,
+                db_conn,
+            )
+            .await?;
 
             next_schedule_epoch = recurring_data
                 .spec
@@ -134,8 +172,23 @@ impl Task {
     }
 }
 
+/// Sets the columns of when the task is scheduled. The view filters compare the start and the
+/// exclusive end of the epoch, and the kind tells which epoch the dates are.
+fn set_schedule(task: &mut entities::task::ActiveModel, scheduled_on: Option<Epoch>) {
+    task.scheduled_kind = Set(scheduled_on.map(|epoch| epoch.kind().into()));
+    task.scheduled_start = Set(scheduled_on.map(|epoch| epoch.start_date()));
+    task.scheduled_end = Set(scheduled_on.map(|epoch| epoch.end_date()));
+    task.schedule_index_date = Set(scheduled_on.map(|epoch| epoch.index_date()));
+}
+
 #[derive(Debug)]
 pub(crate) struct CreateTaskInput {
+    /// The workspace to create the task in. Defaults to the workspace of the parent, or to the
+    /// personal workspace of the user if the task is not a subtask.
+    pub(crate) workspace_id: Option<Uuid>,
+    /// The task to create the task as a subtask of.
+    pub(crate) parent_id: Option<Uuid>,
+    pub(crate) complete_with_subtasks: bool,
     pub(crate) scheduled_on: Option<Epoch>,
     pub(crate) recurring_spec: Option<RecurringSpec>,
     pub(crate) title: String,
@@ -149,50 +202,202 @@ pub(crate) async fn create_task(
 ) -> AppResult<Task> {
     info!(?input, "Create new task");
     let task_id = Uuid::new_v4();
+    validate_title(&input.title)?;
     if let Some(recurring_spec) = &input.recurring_spec {
         validate_recurring_spec(recurring_spec)?;
+        if input.parent_id.is_some() {
+            return Err(AppError::invalid_field(
+                "recurringSpec",
+                "subtasks cannot be recurring",
+            ));
+        }
     }
-    let mut task = Task {
-        id: task_id,
-        scheduled_on: input.scheduled_on,
-        complete_date: None,
-        recurring_data: input.recurring_spec.map(|spec| RecurringData {
-            next_check_date: today(),
-            spec,
-        }),
-        title: input.title,
-        cost: input.cost,
-    };
 
     let tx = db_conn.begin().await?;
 
     let task = tx
         .with(|tx| async move {
+            let parent = match input.parent_id {
+                Some(parent_id) => Some(find_editable_task(user_id, parent_id, &*tx).await?),
+                None => None,
+            };
+            let workspace_id = match (input.workspace_id, &parent) {
+                (Some(workspace_id), _) => {
+                    require_role(user_id, workspace_id, Role::Editor, &*tx).await?;
+                    workspace_id
+                }
+                (None, Some(parent)) => parent.workspace_id,
+                (None, None) => personal_workspace_id(user_id, &*tx).await?,
+            };
+            if let Some(parent) = &parent {
+                validate_parent(task_id, workspace_id, parent, &*tx).await?;
+            }
+            let mut task = Task {
+                id: task_id,
+                workspace_id,
+                scheduled_on: input.scheduled_on,
+                complete_date: None,
+                recurring_data: input.recurring_spec.map(|spec| RecurringData {
+                    next_check_date: today(),
+                    spec,
+                }),
+                recurring_template_id: None,
+                parent_id: input.parent_id,
+                complete_with_subtasks: input.complete_with_subtasks,
+                title: input.title,
+                cost: input.cost,
+                created_at: Utc::now(),
+            };
+            let mut model = task.clone().into_active_model()?;
+            model.user_id = Set(user_id);
+            model.insert(&*tx).await?;
+            notify_task_changed(TaskChangeKind::Created, task.id, task.workspace_id, &*tx).await?;
+            if let Some(parent_id) = task.parent_id {
+                update_from_subtasks(parent_id, &*tx).await?;
+            }
+
+            // The occurrences are scheduled after the task is inserted, since they refer to it.
             if task.recurring_data.is_some() {
                 task.schedule_recurring_until(user_id, today() + TimeDelta::days(14), &*tx)
                     .await?;
+                task.save_next_check_date(&*tx).await?;
             }
-            let mut task = task.into_active_model()?;
-            task.user_id = Set(user_id);
-            let task = task.insert(&*tx).await?;
 
             Ok::<_, AppError>(task)
         })
         .await?;
 
-    Ok(task
-        .try_into()
-        .context("Bug: the task just inserted in `create_task` cannot be convert to a Task")?)
+    Ok(task)
+}
+
+fn validate_title(title: &str) -> AppResult<()> {
+    if title.trim().is_empty() {
+        return Err(AppError::invalid_field("title", "title is required"));
+    }
+    Ok(())
+}
+
+/// Checks that the task of the workspace can be a subtask of the parent: the parent must be in the
+/// same workspace, not be recurring, and not be the task or one of its subtasks.
+async fn validate_parent(
+    task_id: Uuid,
+    workspace_id: Uuid,
+    parent: &TaskModel,
+    db: &impl ConnectionTrait,
+) -> AppResult<()> {
+    if parent.workspace_id != workspace_id {
+        return Err(AppError::invalid_field(
+            "parentId",
+            "the parent must be in the workspace of the task",
+        ));
+    }
+    if parent.recurring_spec.is_some() {
+        return Err(AppError::invalid_field(
+            "parentId",
+            "recurring tasks cannot have subtasks",
+        ));
+    }
+
+    let mut ancestor_id = Some(parent.id);
+    while let Some(id) = ancestor_id {
+        if id == task_id {
+            return Err(AppError::invalid_field(
+                "parentId",
+                "a task cannot be a subtask of itself or of its subtasks",
+            ));
+        }
+        ancestor_id = entities::task::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .and_then(|ancestor| ancestor.parent_id);
+    }
+    Ok(())
+}
+
+/// Updates a task from its subtasks after they changed: its cost is the sum of theirs, or none once
+/// it has no subtasks left. If it is completed with them, it is completed when they all are, and
+/// reopened when one of them is not any more, unless it was completed by the user. The parent of
+/// the task is then updated in turn if the task changed.
+async fn update_from_subtasks(task_id: Uuid, db: &impl ConnectionTrait) -> AppResult<()> {
+    let mut next_id = Some(task_id);
+    while let Some(task_id) = next_id {
+        let Some(task) = entities::task::Entity::find_by_id(task_id)
+            .lock_exclusive()
+            .one(db)
+            .await?
+        else {
+            break;
+        };
+        let subtasks = entities::task::Entity::find()
+            .filter(entities::task::Column::ParentId.eq(task_id))
+            .all(db)
+            .await?;
+
+        let cost = subtasks
+            .iter()
+            .filter_map(|subtask| subtask.cost.map(i64::from))
+            .reduce(|total, cost| total + cost)
+            .map(i32::try_from)
+            .transpose()
+            .map_err(|_| {
+                AppError::invalid_field("cost", "the sum of the costs of the subtasks is too large")
+            })?;
+        let subtasks_completed = !subtasks.is_empty()
+            && subtasks
+                .iter()
+                .all(|subtask| subtask.complete_date.is_some());
+        let (complete_date, completed_by_subtasks) = match task.complete_date {
+            None if task.complete_with_subtasks && subtasks_completed => (
+                subtasks
+                    .iter()
+                    .filter_map(|subtask| subtask.complete_date)
+                    .max(),
+                true,
+            ),
+            Some(_) if task.completed_by_subtasks && !subtasks_completed => (None, false),
+            complete_date => (complete_date, task.completed_by_subtasks),
+        };
+        if cost == task.cost && complete_date == task.complete_date {
+            break;
+        }
+
+        next_id = task.parent_id;
+        let mut task = task.into_active_model();
+        task.cost = Set(cost);
+        task.complete_date = Set(complete_date);
+        task.completed_by_subtasks = Set(completed_by_subtasks);
+        let task = task.update(db).await?;
+        notify_task_changed(TaskChangeKind::Updated, task.id, task.workspace_id, db).await?;
+    }
+    Ok(())
 }
 
 fn validate_recurring_spec(spec: &RecurringSpec) -> AppResult<()> {
+    let RecurringPattern::EveryEpoch { every, .. } = spec.pattern;
+    if every < 1 {
+        return Err(AppError::invalid_field(
+            "recurringSpec.pattern.every",
+            "the task must repeat every 1 or more epochs",
+        ));
+    }
+    if spec
+        .end_date
+        .is_some_and(|end_date| end_date <= spec.start_date)
+    {
+        return Err(AppError::invalid_field(
+            "recurringSpec.endDate",
+            "the end date must be after the start date",
+        ));
+    }
+
     if let RecurringPattern::EveryEpoch {
         kind: EpochKind::Week,
         ..
     } = spec.pattern
     {
         if spec.start_date.weekday() != Weekday::Mon {
-            return Err(AppError::invalid_input(
+            return Err(AppError::invalid_field(
+                "recurringSpec.startDate",
                 "for recurring spec that repeats every week, the start date must be on Monday",
             ));
         }
@@ -201,11 +406,48 @@ fn validate_recurring_spec(spec: &RecurringSpec) -> AppResult<()> {
     Ok(())
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub(crate) struct TaskFilter {
+    /// Only lists the tasks of this workspace, instead of all the workspaces of the user.
+    pub(crate) workspace_id: Option<Uuid>,
     pub(crate) view_filter: Option<ViewFilter>,
+    /// Only lists the tasks that are completed, or only those that are not.
+    pub(crate) completed: Option<bool>,
+    /// Only lists the tasks completed in the range.
+    pub(crate) complete_date: Option<DateRange>,
+    pub(crate) cost: Option<CostRange>,
+    pub(crate) recurrence: Option<Recurrence>,
+    /// Only lists the tasks that are not scheduled.
+    pub(crate) unscheduled_only: bool,
 }
 
+/// The costs from `min` to `max`, both included. Tasks without a cost are not in any range.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CostRange {
+    pub(crate) min: Option<i32>,
+    pub(crate) max: Option<i32>,
+}
+
+impl CostRange {
+    fn contains(&self, cost: Option<i32>) -> bool {
+        cost.is_some_and(|cost| {
+            self.min.is_none_or(|min| min <= cost) && self.max.is_none_or(|max| cost <= max)
+        })
+    }
+}
+
+/// Whether a task is part of a recurring series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, async_graphql::Enum)]
+pub(crate) enum Recurrence {
+    /// A recurring task, from which the occurrences are generated.
+    Recurring,
+    /// A task that is neither recurring nor generated from a recurring task.
+    OneOff,
+    /// An occurrence generated from a recurring task.
+    Generated,
+}
+
+#[derive(Clone)]
 pub(crate) struct ViewFilter {
     pub(crate) view_type: ViewType,
     pub(crate) epoch: Option<Epoch>,
@@ -217,105 +459,883 @@ pub(crate) enum ViewType {
     Planned,
 }
 
+impl TaskFilter {
+    /// Whether the task matches the filter. Whether the user can see the task is not checked.
+    pub(crate) fn matches(&self, task: &Task) -> bool {
+        self.workspace_id
+            .is_none_or(|workspace_id| task.workspace_id == workspace_id)
+            && self
+                .view_filter
+                .as_ref()
+                .is_none_or(|view_filter| view_filter.matches(task))
+            && self
+                .completed
+                .is_none_or(|completed| task.is_completed() == completed)
+            && self.complete_date.is_none_or(|range| {
+                task.complete_date
+                    .is_some_and(|date| range.contains(date.date_range()))
+            })
+            && self.cost.is_none_or(|range| range.contains(task.cost))
+            && self
+                .recurrence
+                .is_none_or(|recurrence| task.recurrence() == recurrence)
+            && (!self.unscheduled_only || task.scheduled_on.is_none())
+    }
+
+    /// The filter as a condition on the tasks, the same as [`TaskFilter::matches`] except for the
+    /// workspace, which [`visible_tasks`] checks.
+    fn condition(&self) -> Condition {
+        use entities::task::Column;
+
+        let mut condition = Condition::all();
+        if let Some(view_condition) = self.view_filter.as_ref().and_then(ViewFilter::condition) {
+            condition = condition.add(view_condition);
+        }
+        if let Some(completed) = self.completed {
+            condition = condition.add(if completed {
+                Column::CompleteDate.is_not_null()
+            } else {
+                Column::CompleteDate.is_null()
+            });
+        }
+        if let Some(range) = self.complete_date {
+            condition = condition
+                .add(Column::CompleteDate.gte(range.start()))
+                .add(Column::CompleteDate.lt(range.end()));
+        }
+        if let Some(range) = self.cost {
+            condition = condition.add(Column::Cost.is_not_null());
+            if let Some(min) = range.min {
+                condition = condition.add(Column::Cost.gte(min));
+            }
+            if let Some(max) = range.max {
+                condition = condition.add(Column::Cost.lte(max));
+            }
+        }
+        if let Some(recurrence) = self.recurrence {
+            condition = condition.add(match recurrence {
+                Recurrence::Recurring => Column::RecurringSpec.is_not_null(),
+                Recurrence::OneOff => Column::RecurringSpec
+                    .is_null()
+                    .and(Column::RecurringTemplateId.is_null()),
+                Recurrence::Generated => Column::RecurringSpec
+                    .is_null()
+                    .and(Column::RecurringTemplateId.is_not_null()),
+            });
+        }
+        if self.unscheduled_only {
+            condition = condition.add(Column::ScheduledStart.is_null());
+        }
+        condition
+    }
+}
+
+impl ViewFilter {
+    fn matches(&self, task: &Task) -> bool {
+        fn generalized_contains(e1: Option<Epoch>, e2: Option<Epoch>) -> bool {
+            match (e1, e2) {
+                (Some(e1), Some(e2)) => e1.contains(e2),
+                (None, _) => true,
+                (_, None) => false,
+            }
+        }
+
+        match self.view_type {
+            ViewType::Scheduled => generalized_contains(self.epoch, task.scheduled_on),
+            ViewType::Planned => generalized_contains(task.scheduled_on, self.epoch),
+        }
+    }
+
+    /// The filter as a condition on the dates of the tasks, the same as [`ViewFilter::matches`].
+    /// The dates of tasks that are not scheduled are null, so they fail the comparisons.
+    fn condition(&self) -> Option<SimpleExpr> {
+        let (start, end) = (
+            entities::task::Column::ScheduledStart,
+            entities::task::Column::ScheduledEnd,
+        );
+        match (self.view_type, self.epoch) {
+            (ViewType::Scheduled, None) => None,
+            (ViewType::Scheduled, Some(epoch)) => {
+                Some(start.gte(epoch.start_date()).and(end.lte(epoch.end_date())))
+            }
+            (ViewType::Planned, None) => Some(start.is_null()),
+            (ViewType::Planned, Some(epoch)) => Some(
+                start
+                    .is_null()
+                    .or(start.lte(epoch.start_date()).and(end.gte(epoch.end_date()))),
+            ),
+        }
+    }
+}
+
+/// What the tasks are listed in the order of. Tasks with the same value, e.g. those without a
+/// cost, are ordered by id, and tasks without a value come last.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, async_graphql::Enum)]
+pub(crate) enum TaskSortKey {
+    /// The id, which keeps the order stable while tasks are added and changed.
+    #[default]
+    Id,
+    /// The start of the epoch the task is scheduled on.
+    ScheduledDate,
+    Cost,
+    Title,
+    CreatedAt,
+}
+
+impl TaskSortKey {
+    fn column(self) -> entities::task::Column {
+        match self {
+            TaskSortKey::Id => entities::task::Column::Id,
+            TaskSortKey::ScheduledDate => entities::task::Column::ScheduleIndexDate,
+            TaskSortKey::Cost => entities::task::Column::Cost,
+            TaskSortKey::Title => entities::task::Column::Title,
+            TaskSortKey::CreatedAt => entities::task::Column::CreatedAt,
+        }
+    }
+
+    fn value(self, task: &Task) -> Option<SortValue> {
+        match self {
+            TaskSortKey::Id => None,
+            TaskSortKey::ScheduledDate => Some(SortValue::ScheduledDate(
+                task.scheduled_on.map(|epoch| epoch.index_date()),
+            )),
+            TaskSortKey::Cost => Some(SortValue::Cost(task.cost)),
+            TaskSortKey::Title => Some(SortValue::Title(task.title.clone())),
+            TaskSortKey::CreatedAt => Some(SortValue::CreatedAt(task.created_at)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TaskOrder {
+    pub(crate) key: TaskSortKey,
+    pub(crate) descending: bool,
+}
+
+/// The position of a task in the listing: its id, and the value it is ordered by unless it is
+/// ordered by id.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TaskCursor {
+    id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<SortValue>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum SortValue {
+    ScheduledDate(Option<NaiveDate>),
+    Cost(Option<i32>),
+    Title(String),
+    CreatedAt(DateTime<Utc>),
+}
+
+impl SortValue {
+    fn key(&self) -> TaskSortKey {
+        match self {
+            SortValue::ScheduledDate(_) => TaskSortKey::ScheduledDate,
+            SortValue::Cost(_) => TaskSortKey::Cost,
+            SortValue::Title(_) => TaskSortKey::Title,
+            SortValue::CreatedAt(_) => TaskSortKey::CreatedAt,
+        }
+    }
+
+    /// The value to compare the column with, or `None` if it is null.
+    fn into_value(self) -> Option<sea_orm::Value> {
+        match self {
+            SortValue::ScheduledDate(date) => date.map(Into::into),
+            SortValue::Cost(cost) => cost.map(Into::into),
+            SortValue::Title(title) => Some(title.into()),
+            SortValue::CreatedAt(created_at) => Some(created_at.into()),
+        }
+    }
+}
+
+impl Task {
+    pub(crate) fn cursor(&self, order: TaskOrder) -> TaskCursor {
+        TaskCursor {
+            id: self.id,
+            value: order.key.value(self),
+        }
+    }
+}
+
+impl TaskCursor {
+    /// The condition that a task comes after the cursor in the order, or before it if `before`.
+    fn condition(self, order: TaskOrder, before: bool) -> AppResult<SimpleExpr> {
+        let column = order.key.column();
+        // Whether the tasks beyond the cursor have greater values.
+        let ascending = order.descending == before;
+        let beyond = |value: sea_orm::Value| {
+            if ascending {
+                column.gt(value)
+            } else {
+                column.lt(value)
+            }
+        };
+        let beyond_id = if ascending {
+            entities::task::Column::Id.gt(self.id)
+        } else {
+            entities::task::Column::Id.lt(self.id)
+        };
+
+        let value = match self.value {
+            None if order.key == TaskSortKey::Id => return Ok(beyond_id),
+            Some(value) if value.key() == order.key => value.into_value(),
+            _ => {
+                return Err(AppError::invalid_input(
+                    "the cursor is of tasks listed in another order",
+                ))
+            }
+        };
+        // Null values come after all the others.
+        Ok(match value {
+            Some(value) => {
+                let condition = beyond(value.clone()).or(column.eq(value).and(beyond_id));
+                if before {
+                    condition
+                } else {
+                    condition.or(column.is_null())
+                }
+            }
+            None => {
+                let condition = column.is_null().and(beyond_id);
+                if before {
+                    condition.or(column.is_not_null())
+                } else {
+                    condition
+                }
+            }
+        })
+    }
+}
+
+/// Which tasks to list: the first or last `limit` tasks between the cursors.
+#[derive(Clone, Debug)]
+pub(crate) struct TaskPage {
+    pub(crate) after: Option<TaskCursor>,
+    pub(crate) before: Option<TaskCursor>,
+    pub(crate) limit: u64,
+    /// Whether to take the tasks from the end of the range instead of from its start.
+    pub(crate) from_end: bool,
+}
+
+/// Lists a page of the tasks that match the filter, in the order. Also returns whether there are
+/// more tasks in the range beyond the page, after it or before it if the page is taken from the
+/// end.
 pub(crate) async fn list_tasks(
     user_id: Uuid,
     filter: TaskFilter,
+    order: TaskOrder,
+    page: TaskPage,
     db_conn: &DatabaseConnection,
-) -> AppResult<Vec<Task>> {
-    let query = entities::task::Entity::find().filter(entities::task::Column::UserId.eq(user_id));
+) -> AppResult<(Vec<Task>, bool)> {
+    let mut query = visible_tasks(user_id, &filter, db_conn).await?;
+    if let Some(after) = page.after {
+        query = query.filter(after.condition(order, false)?);
+    }
+    if let Some(before) = page.before {
+        query = query.filter(before.condition(order, true)?);
+    }
+
+    // The page from the end is taken in the reverse order.
+    let sort_order = if order.descending != page.from_end {
+        Order::Desc
+    } else {
+        Order::Asc
+    };
+    if order.key != TaskSortKey::Id {
+        let nulls = if page.from_end {
+            NullOrdering::First
+        } else {
+            NullOrdering::Last
+        };
+        QueryTrait::query(&mut query).order_by_with_nulls(
+            order.key.column(),
+            sort_order.clone(),
+            nulls,
+        );
+    }
 
-    let tasks = query
+    // One more task than asked for tells whether there are more.
+    let mut tasks = query
+        .order_by(entities::task::Column::Id, sort_order)
+        .limit(page.limit + 1)
         .all(db_conn)
         .await?
         .into_iter()
         .map(Task::try_from)
-        .collect::<Result<_, _>>()?;
+        .collect::<Result<Vec<_>, _>>()?;
+    let has_more = tasks.len() as u64 > page.limit;
+    tasks.truncate(page.limit as usize);
+    if page.from_end {
+        tasks.reverse();
+    }
 
-    let Some(view_filter) = filter.view_filter else {
-        return Ok(tasks);
-    };
+    Ok((tasks, has_more))
+}
 
-    fn generalized_contains(e1: Option<Epoch>, e2: Option<Epoch>) -> bool {
-        match (e1, e2) {
-            (Some(e1), Some(e2)) => e1.contains(e2),
-            (None, _) => true,
-            (_, None) => false,
-        }
-    }
+/// Counts the tasks that match the filter.
+pub(crate) async fn count_tasks(
+    user_id: Uuid,
+    filter: TaskFilter,
+    db_conn: &DatabaseConnection,
+) -> AppResult<u64> {
+    Ok(visible_tasks(user_id, &filter, db_conn)
+        .await?
+        .count(db_conn)
+        .await?)
+}
 
-    match view_filter.view_type {
-        ViewType::Scheduled => {
-            let filter = |task: &Task| generalized_contains(view_filter.epoch, task.scheduled_on);
-            Ok(tasks.into_iter().filter(filter).collect())
+/// The tasks that match the filter, of its workspace or of all the workspaces of the user.
+pub(super) async fn visible_tasks(
+    user_id: Uuid,
+    filter: &TaskFilter,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Select<entities::task::Entity>> {
+    let query = match filter.workspace_id {
+        Some(workspace_id) => {
+            require_role(user_id, workspace_id, Role::Viewer, db_conn).await?;
+            entities::task::Entity::find()
+                .filter(entities::task::Column::WorkspaceId.eq(workspace_id))
         }
-        ViewType::Planned => {
-            let filter = |task: &Task| generalized_contains(task.scheduled_on, view_filter.epoch);
-            Ok(tasks.into_iter().filter(filter).collect())
+        None => entities::task::Entity::find().filter(
+            entities::task::Column::WorkspaceId.in_subquery(
+                Query::select()
+                    .column(entities::workspace_members::Column::WorkspaceId)
+                    .from(entities::workspace_members::Entity)
+                    .and_where(entities::workspace_members::Column::UserId.eq(user_id))
+                    .to_owned(),
+            ),
+        ),
+    };
+    Ok(query.filter(filter.condition()))
+}
+
+/// Finds a task the user can see. A task in a workspace the user is not a member of is reported as
+/// not found.
+pub(crate) async fn find_task(
+    user_id: Uuid,
+    task_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Task> {
+    Ok(find_tasks(user_id, &[task_id], db_conn).await?.remove(0))
+}
+
+/// Finds the tasks the user can see, in the order of the ids. Fails if any of them is not found.
+pub(crate) async fn find_tasks(
+    user_id: Uuid,
+    task_ids: &[Uuid],
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<Task>> {
+    let tasks = visible_tasks(user_id, &TaskFilter::default(), db_conn)
+        .await?
+        .filter(entities::task::Column::Id.is_in(task_ids.iter().copied()))
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(|task| Task::try_from(task).map(|task| (task.id, task)))
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    task_ids
+        .iter()
+        .map(|id| {
+            tasks
+                .get(id)
+                .cloned()
+                .ok_or_else(|| AppError::task_not_found(*id))
+        })
+        .collect()
+}
+
+/// Loads the tasks with the ids, for the parents and the recurring tasks of tasks. Whether the user
+/// can see them is not checked: a task is in the workspace of its parent and of its recurring task,
+/// so whoever can see a task can see those and its subtasks.
+pub(crate) async fn load_tasks(
+    task_ids: &[Uuid],
+    db_conn: &DatabaseConnection,
+) -> AppResult<HashMap<Uuid, Task>> {
+    Ok(entities::task::Entity::find()
+        .filter(entities::task::Column::Id.is_in(task_ids.iter().copied()))
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(|task| Task::try_from(task).map(|task| (task.id, task)))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Loads the subtasks of the tasks, in the order they were created, see [`load_tasks`].
+pub(crate) async fn load_subtasks(
+    parent_ids: &[Uuid],
+    db_conn: &DatabaseConnection,
+) -> AppResult<HashMap<Uuid, Vec<Task>>> {
+    let mut subtasks = HashMap::<_, Vec<_>>::new();
+    for subtask in entities::task::Entity::find()
+        .filter(entities::task::Column::ParentId.is_in(parent_ids.iter().copied()))
+        .order_by_asc(entities::task::Column::CreatedAt)
+        .order_by_asc(entities::task::Column::Id)
+        .all(db_conn)
+        .await?
+    {
+        let subtask = Task::try_from(subtask)?;
+        if let Some(parent_id) = subtask.parent_id {
+            subtasks.entry(parent_id).or_default().push(subtask);
         }
     }
+    Ok(subtasks)
 }
 
 #[derive(Default)]
 pub(crate) struct UpdateTaskInput {
     pub(crate) id: Uuid,
+    /// The task to make the task a subtask of, or `None` to make it a top-level task.
+    pub(crate) parent_id: Maybe<Option<Uuid>>,
+    pub(crate) complete_with_subtasks: Maybe<bool>,
     pub(crate) scheduled_on: Maybe<Option<Epoch>>,
     pub(crate) complete_date: Maybe<Option<NaiveDate>>,
     pub(crate) title: Maybe<String>,
     pub(crate) cost: Maybe<Option<i32>>,
 }
 
+/// Which tasks of a recurring series a change applies to, as in calendars. A task that is not part
+/// of a series is changed alone whatever the scope.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, async_graphql::Enum)]
+pub(crate) enum SeriesScope {
+    /// Only the task.
+    #[default]
+    ThisOccurrence,
+    /// The occurrence and the occurrences scheduled after it, and the recurring task so that the
+    /// occurrences generated later follow. For the recurring task, the same as `WholeSeries`.
+    ThisAndFollowing,
+    /// The recurring task and all its occurrences.
+    WholeSeries,
+}
+
+/// The tasks of the series of a task that a change applies to, besides the task itself.
+#[derive(Default)]
+struct SeriesTasks {
+    /// The recurring task, unless the task is the recurring task.
+    template_id: Option<Uuid>,
+    occurrence_ids: Vec<Uuid>,
+}
+
+impl SeriesTasks {
+    async fn find(
+        task: &TaskModel,
+        scope: SeriesScope,
+        db: &impl ConnectionTrait,
+    ) -> AppResult<SeriesTasks> {
+        use entities::task::Column;
+
+        let template_id = match (&task.recurring_spec, task.recurring_template_id) {
+            _ if scope == SeriesScope::ThisOccurrence => None,
+            (Some(_), _) => Some(task.id),
+            (None, template_id) => template_id,
+        };
+        let Some(template_id) = template_id else {
+            return Ok(SeriesTasks::default());
+        };
+        let template_id = (template_id != task.id).then_some(template_id);
+
+        let mut occurrences = entities::task::Entity::find()
+            .select_only()
+            .column(Column::Id)
+            .filter(Column::RecurringTemplateId.eq(template_id.unwrap_or(task.id)))
+            .filter(Column::Id.ne(task.id));
+        if scope == SeriesScope::ThisAndFollowing && template_id.is_some() {
+            let Some(start) = task.scheduled_start else {
+                // An occurrence that is not scheduled has no following occurrences.
+                return Ok(SeriesTasks {
+                    template_id,
+                    occurrence_ids: Vec::new(),
+                });
+            };
+            occurrences = occurrences.filter(Column::ScheduledStart.gte(start));
+        }
+        Ok(SeriesTasks {
+            template_id,
+            occurrence_ids: occurrences
+                .order_by_asc(Column::Id)
+                .into_tuple()
+                .all(db)
+                .await?,
+        })
+    }
+}
+
+/// Ends the series of the recurring task on the date, unless it already ends before it, so that no
+/// occurrence starting on or after the date is generated.
+async fn end_series(
+    user_id: Uuid,
+    template_id: Uuid,
+    end_date: NaiveDate,
+    db: &impl ConnectionTrait,
+) -> AppResult<()> {
+    let template = find_editable_task(user_id, template_id, db).await?;
+    let Some(spec) = &template.recurring_spec else {
+        warn!("[BUG] end_series called on a non-recurring task.");
+        return Ok(());
+    };
+    let mut spec = serde_json::from_value::<RecurringSpec>(spec.clone())
+        .map_err(|err| anyhow::anyhow!("failed to parse `recurring_spec`: {err}"))?;
+    if spec.end_date.is_some_and(|end| end <= end_date) {
+        return Ok(());
+    }
+    spec.end_date = Some(end_date);
+
+    let mut template = template.into_active_model();
+    template.recurring_spec = Set(Some(serde_json::to_value(spec).map_err(|err| {
+        anyhow::anyhow!("failed to convert `recurring_spec` to JSON: {err}")
+    })?));
+    let template = template.update(db).await?;
+    notify_task_changed(
+        TaskChangeKind::Updated,
+        template.id,
+        template.workspace_id,
+        db,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Updates the task, and the tasks of its series in the scope. Only the title and the cost of the
+/// other tasks of the series are changed, since the other fields are of each occurrence.
 pub(crate) async fn update_task(
     user_id: Uuid,
     input: UpdateTaskInput,
+    scope: SeriesScope,
     db_conn: &DatabaseConnection,
 ) -> AppResult<Task> {
-    let id = input.id;
     let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let task = find_editable_task(user_id, input.id, &*tx).await?;
+        if input.title.is_defined() || input.cost.is_defined() {
+            let series = SeriesTasks::find(&task, scope, &*tx).await?;
+            for id in series.occurrence_ids.into_iter().chain(series.template_id) {
+                // The cost of an occurrence with subtasks is the sum of theirs, so it is kept.
+                let cost = if input.cost.is_defined() && has_subtasks(id, &*tx).await? {
+                    Maybe::Undefined
+                } else {
+                    input.cost.clone()
+                };
+                let series_input = UpdateTaskInput {
+                    id,
+                    title: input.title.clone(),
+                    cost,
+                    ..Default::default()
+                };
+                update_editable_task(user_id, series_input, &*tx).await?;
+            }
+        }
+        update_editable_task(user_id, input, &*tx).await
+    })
+    .await
+}
+
+/// Deletes the task, and the tasks of its series in the scope. When the following occurrences are
+/// deleted, the series ends at the occurrence, so that no more occurrences are generated. When a
+/// recurring task is deleted alone, its occurrences are kept as one-off tasks.
+pub(crate) async fn delete_task(
+    user_id: Uuid,
+    task_id: Uuid,
+    scope: SeriesScope,
+    db_conn: &DatabaseConnection,
+) -> AppResult<()> {
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let task = find_editable_task(user_id, task_id, &*tx).await?;
+        let series = SeriesTasks::find(&task, scope, &*tx).await?;
+        for id in series.occurrence_ids {
+            delete_editable_task(user_id, id, &*tx).await?;
+        }
+        delete_editable_task(user_id, task_id, &*tx).await?;
 
-    let mut task = entities::task::Entity::find_by_id(id)
-        .filter(entities::task::Column::UserId.eq(user_id))
-        .one(&tx)
+        let Some(template_id) = series.template_id else {
+            return Ok(());
+        };
+        if scope == SeriesScope::WholeSeries {
+            delete_editable_task(user_id, template_id, &*tx).await?;
+        } else {
+            let end_date = task.scheduled_start.unwrap_or_else(today);
+            end_series(user_id, template_id, end_date, &*tx).await?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// The outcome of the change of one task in a bulk change.
+pub(crate) type BulkResult<T> = (Uuid, AppResult<T>);
+
+/// Updates the tasks in one transaction, see [`change_each`].
+pub(crate) async fn bulk_update_tasks(
+    user_id: Uuid,
+    inputs: Vec<UpdateTaskInput>,
+    all_or_nothing: bool,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<BulkResult<Task>>> {
+    let inputs = inputs.into_iter().map(|input| (input.id, input)).collect();
+    change_each(
+        inputs,
+        all_or_nothing,
+        db_conn,
+        move |input, tx| async move { update_editable_task(user_id, input, &*tx).await },
+    )
+    .await
+}
+
+/// Completes the tasks on the date in one transaction, see [`change_each`].
+pub(crate) async fn bulk_complete_tasks(
+    user_id: Uuid,
+    task_ids: Vec<Uuid>,
+    complete_date: NaiveDate,
+    all_or_nothing: bool,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<BulkResult<Task>>> {
+    let inputs = task_ids
+        .into_iter()
+        .map(|id| {
+            let input = UpdateTaskInput {
+                id,
+                complete_date: Maybe::Some(Some(complete_date)),
+                ..Default::default()
+            };
+            (id, input)
+        })
+        .collect();
+    change_each(
+        inputs,
+        all_or_nothing,
+        db_conn,
+        move |input, tx| async move { update_editable_task(user_id, input, &*tx).await },
+    )
+    .await
+}
+
+/// Deletes the tasks in one transaction, see [`change_each`].
+pub(crate) async fn bulk_delete_tasks(
+    user_id: Uuid,
+    task_ids: Vec<Uuid>,
+    all_or_nothing: bool,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<BulkResult<()>>> {
+    let task_ids = task_ids.into_iter().map(|id| (id, id)).collect();
+    change_each(
+        task_ids,
+        all_or_nothing,
+        db_conn,
+        move |id, tx| async move { delete_editable_task(user_id, id, &*tx).await },
+    )
+    .await
+}
+
+/// Moves the tasks scheduled on `from` that are not completed to `to` in one transaction, see
+/// [`change_each`]. Only the tasks of the workspace are moved if one is given.
+pub(crate) async fn move_tasks(
+    user_id: Uuid,
+    from: Epoch,
+    to: Epoch,
+    workspace_id: Option<Uuid>,
+    all_or_nothing: bool,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<BulkResult<Task>>> {
+    let filter = TaskFilter {
+        workspace_id,
+        completed: Some(false),
+        ..Default::default()
+    };
+    let inputs = visible_tasks(user_id, &filter, db_conn)
         .await?
-        .ok_or_else(|| AppError::task_not_found(id))?
-        .into_active_model();
+        .filter(entities::task::Column::ScheduledKind.eq(DbEpochKind::from(from.kind())))
+        .filter(entities::task::Column::ScheduledStart.eq(from.start_date()))
+        .order_by_asc(entities::task::Column::Id)
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(|task| {
+            let input = UpdateTaskInput {
+                id: task.id,
+                scheduled_on: Maybe::Some(Some(to)),
+                ..Default::default()
+            };
+            (task.id, input)
+        })
+        .collect();
+    change_each(
+        inputs,
+        all_or_nothing,
+        db_conn,
+        move |input, tx| async move { update_editable_task(user_id, input, &*tx).await },
+    )
+    .await
+}
+
+/// Makes a change for each of the items, keyed by the id of the task they change, in one
+/// transaction and returns the outcome of each. A change that fails is rolled back on its own
+/// while the others are kept, unless `all_or_nothing` is set, in which case the first failure
+/// rolls back all the changes and is returned.
+async fn change_each<I, T, F, FUT>(
+    items: Vec<(Uuid, I)>,
+    all_or_nothing: bool,
+    db_conn: &DatabaseConnection,
+    change: F,
+) -> AppResult<Vec<BulkResult<T>>>
+where
+    I: Send,
+    T: Send,
+    F: Send + Sync + Fn(I, TransactionWrapper) -> FUT,
+    FUT: Future<Output = AppResult<T>> + Send,
+{
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let mut results = Vec::with_capacity(items.len());
+        for (task_id, item) in items {
+            let savepoint = tx.begin().await?;
+            let result = savepoint.with(|savepoint| change(item, savepoint)).await;
+            if all_or_nothing {
+                results.push((task_id, Ok(result?)));
+            } else {
+                results.push((task_id, result));
+            }
+        }
+        Ok(results)
+    })
+    .await
+}
+
+async fn update_editable_task(
+    user_id: Uuid,
+    input: UpdateTaskInput,
+    db: &impl ConnectionTrait,
+) -> AppResult<Task> {
+    let task = find_editable_task(user_id, input.id, db).await?;
+    let old_parent_id = task.parent_id;
+    // The parents are updated from their subtasks if the task changed as a subtask.
+    let changes_parents =
+        input.parent_id.is_defined() || input.complete_date.is_defined() || input.cost.is_defined();
+    let changes_subtasks = input.complete_with_subtasks.is_defined();
+    if let Maybe::Some(Some(parent_id)) = input.parent_id {
+        if task.recurring_spec.is_some() {
+            return Err(AppError::invalid_field(
+                "parentId",
+                "recurring tasks cannot be subtasks",
+            ));
+        }
+        let parent = find_editable_task(user_id, parent_id, db).await?;
+        validate_parent(task.id, task.workspace_id, &parent, db).await?;
+    }
+    if input.cost.is_defined() && has_subtasks(task.id, db).await? {
+        return Err(AppError::invalid_field(
+            "cost",
+            "the cost of a task with subtasks is the sum of their costs",
+        ));
+    }
+
+    let mut task = task.into_active_model();
+    if let Maybe::Some(parent_id) = input.parent_id {
+        task.parent_id = Set(parent_id);
+    }
+    if let Maybe::Some(complete_with_subtasks) = input.complete_with_subtasks {
+        task.complete_with_subtasks = Set(complete_with_subtasks);
+    }
     if let Maybe::Some(scheduled_on) = input.scheduled_on {
-        task.schedule_index_date = Set(scheduled_on.map(|e| e.index_date()));
-        task.scheduled_on = Set(scheduled_on
-            .map(serde_json::to_value)
-            .transpose()
-            .context("Failed to convert scheduled_on to JSON")?);
+        set_schedule(&mut task, scheduled_on);
     }
     if let Maybe::Some(complete_date) = input.complete_date {
         task.complete_date = Set(complete_date);
+        task.completed_by_subtasks = Set(false);
     }
     if let Maybe::Some(title) = input.title {
+        validate_title(&title)?;
         task.title = Set(title);
     }
     if let Maybe::Some(cost) = input.cost {
         task.cost = Set(cost);
     }
-    let task = task.update(db_conn).await?;
+    let task = task.update(db).await?;
+    notify_task_changed(TaskChangeKind::Updated, task.id, task.workspace_id, db).await?;
+
+    if changes_parents {
+        if let Some(parent_id) = old_parent_id.filter(|&id| Some(id) != task.parent_id) {
+            update_from_subtasks(parent_id, db).await?;
+        }
+        if let Some(parent_id) = task.parent_id {
+            update_from_subtasks(parent_id, db).await?;
+        }
+    }
+    if changes_subtasks && has_subtasks(task.id, db).await? {
+        update_from_subtasks(task.id, db).await?;
+    }
+    // The task is read again, since it may have been completed with its subtasks.
+    let task = entities::task::Entity::find_by_id(task.id)
+        .one(db)
+        .await?
+        .ok_or_else(|| AppError::task_not_found(task.id))?;
 
     Ok(task.try_into()?)
 }
 
-pub(crate) async fn delete_task(
+async fn has_subtasks(task_id: Uuid, db: &impl ConnectionTrait) -> AppResult<bool> {
+    Ok(entities::task::Entity::find()
+        .filter(entities::task::Column::ParentId.eq(task_id))
+        .count(db)
+        .await?
+        > 0)
+}
+
+async fn delete_editable_task(
     user_id: Uuid,
     task_id: Uuid,
-    db_conn: &DatabaseConnection,
+    db: &impl ConnectionTrait,
 ) -> AppResult<()> {
-    let entities = entities::task::Entity::delete_by_id(task_id)
-        .filter(entities::task::Column::UserId.eq(user_id))
-        .exec(db_conn)
+    let task = find_editable_task(user_id, task_id, db).await?;
+    // The subtasks are kept as top-level tasks, and the occurrences of a recurring task as one-off
+    // tasks.
+    let referring_ids = entities::task::Entity::find()
+        .select_only()
+        .column(entities::task::Column::Id)
+        .filter(
+            entities::task::Column::ParentId
+                .eq(task_id)
+                .or(entities::task::Column::RecurringTemplateId.eq(task_id)),
+        )
+        .into_tuple::<Uuid>()
+        .all(db)
         .await?;
-
-    if entities.rows_affected != 1 {
-        return Err(AppError::task_not_found(task_id));
+    entities::task::Entity::delete_by_id(task_id)
+        .exec(db)
+        .await?;
+    notify_task_changed(TaskChangeKind::Deleted, task_id, task.workspace_id, db).await?;
+    for referring_id in referring_ids {
+        notify_task_changed(TaskChangeKind::Updated, referring_id, task.workspace_id, db).await?;
+    }
+    if let Some(parent_id) = task.parent_id {
+        update_from_subtasks(parent_id, db).await?;
     }
 
     Ok(())
 }
 
+/// Finds and locks a task the user can modify. A task in a workspace the user is not a member of
+/// is reported as not found.
+async fn find_editable_task(
+    user_id: Uuid,
+    task_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<TaskModel> {
+    let task = entities::task::Entity::find_by_id(task_id)
+        .lock_exclusive()
+        .one(db)
+        .await?
+        .ok_or_else(|| AppError::task_not_found(task_id))?;
+    match member_role(user_id, task.workspace_id, db).await? {
+        None => Err(AppError::task_not_found(task_id)),
+        Some(Role::Viewer) => Err(AppError::permission_denied(
+            "viewers of a workspace cannot modify its tasks",
+        )),
+        Some(Role::Editor | Role::Owner) => Ok(task),
+    }
+}
+
 pub async fn schedule_all_recurring_tasks_until(
     db_conn: &DatabaseConnection,
     until: Option<NaiveDate>,
@@ -368,17 +1388,48 @@ impl TryFrom<TaskModel> for Task {
             }),
         };
 
+        let scheduled_on = match (value.scheduled_kind, value.scheduled_start) {
+            (None, Some(_)) | (Some(_), None) => {
+                anyhow::bail!("`scheduled_kind` and `scheduled_start` must both be set or unset");
+            }
+            (None, None) => None,
+            (Some(kind), Some(start_date)) => Some(Epoch::starting_on(kind.into(), start_date)),
+        };
+
         Ok(Self {
             id: value.id,
-            scheduled_on: value.scheduled_on.map(serde_json::from_value).transpose()?,
+            workspace_id: value.workspace_id,
+            scheduled_on,
             recurring_data,
+            recurring_template_id: value.recurring_template_id,
+            parent_id: value.parent_id,
+            complete_with_subtasks: value.complete_with_subtasks,
             complete_date: value.complete_date,
             title: value.title,
             cost: value.cost,
+            created_at: value.created_at.into(),
         })
     }
 }
 
+impl From<DbEpochKind> for EpochKind {
+    fn from(value: DbEpochKind) -> Self {
+        match value {
+            DbEpochKind::Date => EpochKind::Date,
+            DbEpochKind::Week => EpochKind::Week,
+        }
+    }
+}
+
+impl From<EpochKind> for DbEpochKind {
+    fn from(value: EpochKind) -> Self {
+        match value {
+            EpochKind::Date => DbEpochKind::Date,
+            EpochKind::Week => DbEpochKind::Week,
+        }
+    }
+}
+
 #[extend::ext]
 impl<T> MaybeUndefined<T> {
     fn into_option(self) -> Option<Option<T>> {
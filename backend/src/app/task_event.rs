@@ -0,0 +1,285 @@
+//! Task changes for the live updates, fanned out to all server instances through Postgres.
+//!
+//! A change is announced with `NOTIFY` in the transaction that makes it, so that it is delivered to
+//! the listeners exactly when it is committed. Every instance keeps a connection that `LISTEN`s to
+//! the channel and passes the changes on to its own subscribers. When that connection drops, the
+//! changes made until it is re-established are lost, so the subscribers are told to reload.
+//!
+//! The changes of workspace memberships are announced the same way on their own channel, so that
+//! the subscriptions can keep the workspaces of their user in memory.
+
+use std::{collections::HashSet, time::Duration};
+
+use futures::{stream, Stream};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, QueryFilter,
+    QuerySelect, Statement,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use super::{
+    task::{Task, TaskFilter},
+    workspace::{member_role, require_role, Role},
+    AppError, AppResult,
+};
+use crate::entities;
+
+/// The Postgres channel that task changes are announced on.
+const CHANNEL: &str = "task_changes";
+
+/// The Postgres channel that the changes of workspace memberships are announced on.
+const MEMBERSHIP_CHANNEL: &str = "workspace_membership_changes";
+
+/// How many events a slow subscriber can fall behind before it starts missing them.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// How long to wait before trying to reconnect the listener again.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+pub(crate) enum TaskChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A change of a task. `task` is the task as it is after the change, which is `None` for a deleted
+/// task.
+#[derive(Clone, Debug)]
+pub(crate) struct TaskEvent {
+    pub(crate) kind: TaskChangeKind,
+    pub(crate) task_id: Uuid,
+    pub(crate) workspace_id: Uuid,
+    pub(crate) task: Option<Task>,
+}
+
+/// What is sent over `NOTIFY`. Only the ids are sent since the payload is limited to 8000 bytes;
+/// the task itself is loaded by the listeners.
+#[derive(Debug, Serialize, Deserialize)]
+struct TaskNotification {
+    kind: TaskChangeKind,
+    task_id: Uuid,
+    workspace_id: Uuid,
+}
+
+/// A user joined or left a workspace.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct MembershipNotification {
+    user_id: Uuid,
+    workspace_id: Uuid,
+}
+
+#[derive(Clone, Debug)]
+enum Message {
+    Changed(TaskEvent),
+    MembershipChanged(MembershipNotification),
+    /// The listener connection dropped, so changes may have been missed.
+    Missed,
+}
+
+/// The task changes of all server instances, for the subscriptions to pick up.
+#[derive(Clone, Debug)]
+pub(crate) struct TaskEvents {
+    sender: broadcast::Sender<Message>,
+}
+
+impl TaskEvents {
+    /// Starts listening to the task changes in the background.
+    pub(crate) async fn listen(db_conn: DatabaseConnection) -> anyhow::Result<Self> {
+        let mut listener = PgListener::connect_with(db_conn.get_postgres_connection_pool()).await?;
+        listener.listen_all([CHANNEL, MEMBERSHIP_CHANNEL]).await?;
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        tokio::spawn(forward_notifications(listener, sender.clone(), db_conn));
+
+        Ok(Self { sender })
+    }
+}
+
+/// Announces a change of a task to all server instances once the transaction of `db` commits.
+pub(crate) async fn notify_task_changed(
+    kind: TaskChangeKind,
+    task_id: Uuid,
+    workspace_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<()> {
+    let payload = serde_json::to_string(&TaskNotification {
+        kind,
+        task_id,
+        workspace_id,
+    })
+    .map_err(anyhow::Error::from)?;
+    notify(CHANNEL, payload, db).await
+}
+
+/// Announces that the user joined or left the workspace to all server instances once the
+/// transaction of `db` commits.
+pub(crate) async fn notify_membership_changed(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<()> {
+    let payload = serde_json::to_string(&MembershipNotification {
+        user_id,
+        workspace_id,
+    })
+    .map_err(anyhow::Error::from)?;
+    notify(MEMBERSHIP_CHANNEL, payload, db).await
+}
+
+async fn notify(channel: &str, payload: String, db: &impl ConnectionTrait) -> AppResult<()> {
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "SELECT pg_notify($1, $2)",
+        [channel.into(), payload.into()],
+    ))
+    .await?;
+
+    Ok(())
+}
+
+async fn forward_notifications(
+    mut listener: PgListener,
+    sender: broadcast::Sender<Message>,
+    db_conn: DatabaseConnection,
+) {
+    loop {
+        let message = match listener.try_recv().await {
+            Ok(Some(notification)) if notification.channel() == MEMBERSHIP_CHANNEL => {
+                match serde_json::from_str(notification.payload()) {
+                    Ok(membership) => Message::MembershipChanged(membership),
+                    Err(err) => {
+                        error!("Failed to parse a membership change: {err:?}");
+                        Message::Missed
+                    }
+                }
+            }
+            Ok(Some(notification)) => match load_event(notification.payload(), &db_conn).await {
+                Ok(Some(event)) => Message::Changed(event),
+                // The task was deleted in the meantime, which is announced on its own.
+                Ok(None) => continue,
+                Err(err) => {
+                    error!("Failed to load a task change: {err:?}");
+                    Message::Missed
+                }
+            },
+            // The next `try_recv` reconnects.
+            Ok(None) => {
+                warn!("The connection listening to task changes was lost, reconnecting");
+                Message::Missed
+            }
+            Err(err) => {
+                error!("Failed to listen to task changes, retrying in {RECONNECT_DELAY:?}: {err}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                Message::Missed
+            }
+        };
+        // Sending only fails when nobody is subscribed, in which case nobody misses the event.
+        let _ = sender.send(message);
+    }
+}
+
+/// Parses a notification and loads the task it is about, or returns `None` if it is gone.
+async fn load_event(
+    payload: &str,
+    db_conn: &DatabaseConnection,
+) -> anyhow::Result<Option<TaskEvent>> {
+    let TaskNotification {
+        kind,
+        task_id,
+        workspace_id,
+    } = serde_json::from_str(payload)?;
+    let task = match kind {
+        TaskChangeKind::Deleted => None,
+        TaskChangeKind::Created | TaskChangeKind::Updated => {
+            let Some(task) = entities::task::Entity::find_by_id(task_id)
+                .one(db_conn)
+                .await?
+            else {
+                return Ok(None);
+            };
+            Some(task.try_into()?)
+        }
+    };
+
+    Ok(Some(TaskEvent {
+        kind,
+        task_id,
+        workspace_id,
+        task,
+    }))
+}
+
+/// Streams the changes of the tasks that the user can see and that match the filter. A deleted
+/// task only has to match the workspace of the filter, since it is not known whether it matched
+/// the rest.
+///
+/// The workspaces of the user are loaded once, and a membership is only checked again when it
+/// changes, so that a user stops receiving the changes of a workspace as soon as they are removed
+/// from it. The stream ends with an error if changes may have been missed, after which the tasks
+/// should be reloaded.
+pub(crate) async fn watch_tasks(
+    user_id: Uuid,
+    filter: TaskFilter,
+    events: &TaskEvents,
+    db_conn: DatabaseConnection,
+) -> AppResult<impl Stream<Item = AppResult<TaskEvent>>> {
+    if let Some(workspace_id) = filter.workspace_id {
+        require_role(user_id, workspace_id, Role::Viewer, &db_conn).await?;
+    }
+
+    // Subscribe first, so that no membership change is missed between loading and watching.
+    let receiver = events.sender.subscribe();
+    let workspace_ids = entities::workspace_members::Entity::find()
+        .select_only()
+        .column(entities::workspace_members::Column::WorkspaceId)
+        .filter(entities::workspace_members::Column::UserId.eq(user_id))
+        .into_tuple::<Uuid>()
+        .all(&db_conn)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    Ok(stream::unfold(
+        Some((receiver, workspace_ids, filter)),
+        move |state| {
+            let db_conn = db_conn.clone();
+            async move {
+                let (mut receiver, mut workspace_ids, filter) = state?;
+                loop {
+                    let event = match receiver.recv().await {
+                        Ok(Message::Changed(event)) => event,
+                        Ok(Message::MembershipChanged(membership)) => {
+                            if membership.user_id == user_id {
+                                let workspace_id = membership.workspace_id;
+                                match member_role(user_id, workspace_id, &db_conn).await {
+                                    Ok(Some(_)) => workspace_ids.insert(workspace_id),
+                                    Ok(None) => workspace_ids.remove(&workspace_id),
+                                    Err(err) => return Some((Err(err), None)),
+                                };
+                            }
+                            continue;
+                        }
+                        Ok(Message::Missed) => return Some((Err(AppError::EventsMissed), None)),
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!(skipped, "A task subscription fell behind and missed events");
+                            return Some((Err(AppError::EventsMissed), None));
+                        }
+                        Err(RecvError::Closed) => return None,
+                    };
+                    let matches = workspace_ids.contains(&event.workspace_id)
+                        && filter
+                            .workspace_id
+                            .is_none_or(|workspace_id| event.workspace_id == workspace_id)
+                        && event.task.as_ref().is_none_or(|task| filter.matches(task));
+                    if matches {
+                        return Some((Ok(event), Some((receiver, workspace_ids, filter))));
+                    }
+                }
+            }
+        },
+    ))
+}
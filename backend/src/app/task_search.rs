@@ -0,0 +1,170 @@
+//! Full-text search of the tasks by their titles, with Postgres text search. The titles are
+//! indexed with `task_title_search_idx`, which the expressions here have to match to use it.
+
+use std::collections::HashMap;
+
+use sea_orm::{
+    sea_query::{extension::postgres::PgExpr, Alias, Expr, Func, SimpleExpr},
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, Order, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use uuid::Uuid;
+
+use crate::entities;
+
+use super::{
+    task::{visible_tasks, Task, TaskFilter},
+    AppResult,
+};
+
+/// The `tsvector` of a title, as in the index.
+const TITLE_DOCUMENT: &str = "to_tsvector('english', title)";
+/// Marks where the matches in a headline start and stop. Control characters are used since they
+/// are not in titles, unlike any markup.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_STOP: char = '\u{3}';
+
+pub(crate) struct SearchResult {
+    pub(crate) task: Task,
+    /// How well the task matches, higher is better.
+    pub(crate) rank: f32,
+    /// The title, split into the parts that match the query and the parts between them.
+    pub(crate) snippet: Vec<SnippetPart>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SnippetPart {
+    pub(crate) text: String,
+    pub(crate) highlighted: bool,
+}
+
+#[derive(FromQueryResult)]
+struct SearchHit {
+    id: Uuid,
+    rank: f32,
+    headline: String,
+}
+
+/// Searches the tasks that match the filter for those whose title has all the words of the query,
+/// best matches first. Each word matches the words of the title that start with it, after both
+/// are reduced to their stems, e.g. `renew pass` matches "Renewing the passport".
+pub(crate) async fn search_tasks(
+    user_id: Uuid,
+    query: &str,
+    filter: TaskFilter,
+    limit: u64,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<SearchResult>> {
+    let Some(ts_query) = prefix_ts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let ts_query = || Expr::cust_with_values("to_tsquery('english', $1)", [ts_query.clone()]);
+    let matches = Expr::cust(TITLE_DOCUMENT).matches(ts_query());
+    let rank = Func::cust(Alias::new("ts_rank"))
+        .arg(Expr::cust(TITLE_DOCUMENT))
+        .arg(ts_query());
+    let headline = Func::cust(Alias::new("ts_headline"))
+        .arg(Expr::cust("'english'"))
+        .arg(Expr::col(entities::task::Column::Title))
+        .arg(ts_query())
+        .arg(Expr::val(format!(
+            "StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_STOP}, HighlightAll=true"
+        )));
+
+    let hits = visible_tasks(user_id, &filter, db_conn)
+        .await?
+        .select_only()
+        .column(entities::task::Column::Id)
+        .column_as(SimpleExpr::from(rank), "rank")
+        .column_as(SimpleExpr::from(headline), "headline")
+        .filter(matches)
+        .order_by(Expr::col(Alias::new("rank")), Order::Desc)
+        .order_by(entities::task::Column::Id, Order::Asc)
+        .limit(limit)
+        .into_model::<SearchHit>()
+        .all(db_conn)
+        .await?;
+
+    let mut tasks = entities::task::Entity::find()
+        .filter(entities::task::Column::Id.is_in(hits.iter().map(|hit| hit.id)))
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .map(|task| Ok((task.id, Task::try_from(task)?)))
+        .collect::<anyhow::Result<HashMap<_, _>>>()?;
+    Ok(hits
+        .into_iter()
+        .filter_map(|hit| {
+            Some(SearchResult {
+                task: tasks.remove(&hit.id)?,
+                rank: hit.rank,
+                snippet: parse_headline(&hit.headline),
+            })
+        })
+        .collect())
+}
+
+/// A `tsquery` that matches the titles with words starting with each word of the query. Only the
+/// letters and digits of the query are kept, so that it cannot contain `tsquery` operators.
+fn prefix_ts_query(query: &str) -> Option<String> {
+    let terms = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{word}:*"))
+        .collect::<Vec<_>>();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+/// Splits a headline of `ts_headline` at the highlight markers.
+fn parse_headline(headline: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut highlighted = false;
+    for text in headline.split([HIGHLIGHT_START, HIGHLIGHT_STOP]) {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: text.to_owned(),
+                highlighted,
+            });
+        }
+        highlighted = !highlighted;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn each_word_of_the_query_is_a_prefix() {
+        expect_that!(prefix_ts_query("renew  pass"), some(eq("renew:* & pass:*")));
+        expect_that!(
+            prefix_ts_query("it's a & b | !c"),
+            some(eq("it:* & s:* & a:* & b:* & c:*"))
+        );
+        expect_that!(prefix_ts_query(" &! "), none());
+    }
+
+    #[googletest::test]
+    fn headlines_are_split_at_the_highlights() {
+        expect_that!(
+            parse_headline("\u{2}Renew\u{3} the \u{2}passport\u{3}"),
+            elements_are![
+                eq(&SnippetPart {
+                    text: "Renew".to_owned(),
+                    highlighted: true
+                }),
+                eq(&SnippetPart {
+                    text: " the ".to_owned(),
+                    highlighted: false
+                }),
+                eq(&SnippetPart {
+                    text: "passport".to_owned(),
+                    highlighted: true
+                }),
+            ]
+        );
+    }
+}
@@ -131,6 +131,21 @@ impl EpochLike for Epoch {
 }
 
 impl Epoch {
+    /// The epoch of the kind that starts on the date.
+    pub(crate) fn starting_on(kind: EpochKind, start_date: NaiveDate) -> Self {
+        match kind {
+            EpochKind::Date => Epoch::Date(start_date),
+            EpochKind::Week => Epoch::Week(Week::from_start_date(start_date)),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> EpochKind {
+        match self {
+            Epoch::Date(_) => EpochKind::Date,
+            Epoch::Week(_) => EpochKind::Week,
+        }
+    }
+
     pub(crate) fn contains(&self, other: Epoch) -> bool {
         self.date_range().contains(other.date_range())
     }
@@ -164,6 +179,9 @@ mod serde_naive_date {
 pub(crate) struct RecurringSpec {
     pub(crate) start_date: NaiveDate,
     pub(crate) pattern: RecurringPattern,
+    /// The date the series ends on: no occurrence starting on or after it is generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) end_date: Option<NaiveDate>,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -207,6 +225,12 @@ impl RecurringSpec {
     pub(crate) fn next_starting_from(&self, date: NaiveDate) -> Epoch {
         self.next_after(date - TimeDelta::days(1))
     }
+
+    /// Whether the series has ended before the epoch, so that it has no occurrence on it.
+    pub(crate) fn has_ended_before(&self, epoch: Epoch) -> bool {
+        self.end_date
+            .is_some_and(|end_date| epoch.start_date() >= end_date)
+    }
 }
 
 #[cfg(test)]
@@ -233,6 +257,7 @@ mod tests {
                 kind: EpochKind::Week,
                 every: 2,
             },
+            end_date: None,
         };
 
         expect_eq!(
@@ -264,6 +289,7 @@ mod tests {
                 kind: EpochKind::Week,
                 every: 2,
             },
+            end_date: None,
         };
 
         expect_eq!(
@@ -281,4 +307,23 @@ mod tests {
             })
         );
     }
+
+    #[googletest::test]
+    fn recurring_series_ends_before_the_epochs_from_its_end_date() {
+        let recurring = RecurringSpec {
+            start_date: NaiveDate::from_ymd_opt(2024, 9, 23).unwrap(),
+            pattern: RecurringPattern::EveryEpoch {
+                kind: EpochKind::Week,
+                every: 1,
+            },
+            end_date: Some(NaiveDate::from_ymd_opt(2024, 10, 7).unwrap()),
+        };
+
+        expect_false!(recurring.has_ended_before(
+            recurring.next_starting_from(NaiveDate::from_ymd_opt(2024, 9, 30).unwrap())
+        ));
+        expect_true!(recurring.has_ended_before(
+            recurring.next_starting_from(NaiveDate::from_ymd_opt(2024, 10, 7).unwrap())
+        ));
+    }
 }
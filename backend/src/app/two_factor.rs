@@ -0,0 +1,207 @@
+use chrono::Utc;
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, PaginatorTrait, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use uuid::Uuid;
+
+use super::{AppError, AppResult};
+use crate::{
+    auth::{
+        check_code, encode_totp_secret, find_enabled_totp, generate_totp_secret, provisioning_uri,
+        record_security_event, replace_recovery_codes, AcceptedCodes, ClientInfo,
+        SecurityEventKind, TotpEncryptionKey,
+    },
+    db::DatabaseTransactionExt,
+    entities,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) struct TwoFactorStatus {
+    pub(crate) enabled: bool,
+    pub(crate) recovery_codes_left: u64,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TotpEnrollment {
+    /// The secret in base32, for authenticator apps that cannot scan the URI.
+    pub(crate) secret: String,
+    pub(crate) provisioning_uri: String,
+}
+
+pub(crate) async fn two_factor_status(
+    user_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<TwoFactorStatus> {
+    let enabled = find_enabled_totp(user_id, db_conn).await?.is_some();
+    let recovery_codes_left = entities::totp_recovery_codes::Entity::find()
+        .filter(entities::totp_recovery_codes::Column::UserId.eq(user_id))
+        .filter(entities::totp_recovery_codes::Column::UsedAt.is_null())
+        .count(db_conn)
+        .await?;
+
+    Ok(TwoFactorStatus {
+        enabled,
+        recovery_codes_left,
+    })
+}
+
+/// Generates a new TOTP secret for the user. Two-factor authentication is only enabled once a code
+/// from it is confirmed with [`confirm_totp`]; enrolling again before that replaces the secret.
+pub(crate) async fn enroll_totp(
+    user_id: Uuid,
+    key: &TotpEncryptionKey,
+    db_conn: &DatabaseConnection,
+) -> AppResult<TotpEnrollment> {
+    if find_enabled_totp(user_id, db_conn).await?.is_some() {
+        return Err(AppError::invalid_input(
+            "two-factor authentication is already enabled",
+        ));
+    }
+    let user = entities::users::Entity::find_by_id(user_id)
+        .one(db_conn)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("user {user_id} does not exist"))?;
+
+    let secret = generate_totp_secret();
+    entities::user_totp::Entity::insert(entities::user_totp::ActiveModel {
+        user_id: Set(user_id),
+        secret_ciphertext: Set(key.encrypt(user_id, &secret)?),
+        confirmed_at: Set(None),
+        last_used_step: Set(None),
+        created_at: Set(Utc::now().into()),
+    })
+    .on_conflict(
+        OnConflict::column(entities::user_totp::Column::UserId)
+            .update_columns([
+                entities::user_totp::Column::SecretCiphertext,
+                entities::user_totp::Column::LastUsedStep,
+                entities::user_totp::Column::CreatedAt,
+            ])
+            // Never replace the secret of a confirmed enrollment.
+            .action_and_where(entities::user_totp::Column::ConfirmedAt.is_null())
+            .to_owned(),
+    )
+    .exec_without_returning(db_conn)
+    .await?;
+
+    Ok(TotpEnrollment {
+        secret: encode_totp_secret(&secret),
+        provisioning_uri: provisioning_uri(&secret, &user.username),
+    })
+}
+
+/// Enables two-factor authentication with a code from the enrolled authenticator, and returns the
+/// recovery codes.
+pub(crate) async fn confirm_totp(
+    user_id: Uuid,
+    code: String,
+    key: &TotpEncryptionKey,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<String>> {
+    let (key, client) = (key.clone(), client.clone());
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let totp = entities::user_totp::Entity::find_by_id(user_id)
+            .lock_exclusive()
+            .one(&*tx)
+            .await?
+            .filter(|totp| totp.confirmed_at.is_none())
+            .ok_or_else(|| AppError::invalid_input("there is no pending TOTP enrollment"))?;
+        if !check_code(&key, &totp, &code, AcceptedCodes::TotpOnly, &*tx).await? {
+            return Err(invalid_code());
+        }
+
+        let mut totp = totp.into_active_model();
+        totp.confirmed_at = Set(Some(Utc::now().into()));
+        totp.update(&*tx).await?;
+        record_security_event(
+            user_id,
+            SecurityEventKind::TwoFactorEnabled,
+            None,
+            &client,
+            &*tx,
+        )
+        .await?;
+        Ok(replace_recovery_codes(user_id, &*tx).await?)
+    })
+    .await
+}
+
+/// Replaces the recovery codes of the user, e.g. when most of them are used up.
+pub(crate) async fn regenerate_recovery_codes(
+    user_id: Uuid,
+    code: String,
+    key: &TotpEncryptionKey,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<String>> {
+    let (key, client) = (key.clone(), client.clone());
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let totp = find_enabled_totp(user_id, &*tx)
+            .await?
+            .ok_or_else(not_enabled)?;
+        if !check_code(&key, &totp, &code, AcceptedCodes::TotpOnly, &*tx).await? {
+            return Err(invalid_code());
+        }
+        record_security_event(
+            user_id,
+            SecurityEventKind::RecoveryCodesRegenerated,
+            None,
+            &client,
+            &*tx,
+        )
+        .await?;
+        Ok(replace_recovery_codes(user_id, &*tx).await?)
+    })
+    .await
+}
+
+/// Disables two-factor authentication. A code is required, so that a stolen session cannot turn
+/// it off.
+pub(crate) async fn disable_totp(
+    user_id: Uuid,
+    code: String,
+    key: &TotpEncryptionKey,
+    client: &ClientInfo,
+    db_conn: &DatabaseConnection,
+) -> AppResult<()> {
+    let (key, client) = (key.clone(), client.clone());
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let totp = find_enabled_totp(user_id, &*tx)
+            .await?
+            .ok_or_else(not_enabled)?;
+        if !check_code(&key, &totp, &code, AcceptedCodes::TotpOrRecovery, &*tx).await? {
+            return Err(invalid_code());
+        }
+
+        entities::user_totp::Entity::delete_by_id(user_id)
+            .exec(&*tx)
+            .await?;
+        entities::totp_recovery_codes::Entity::delete_many()
+            .filter(entities::totp_recovery_codes::Column::UserId.eq(user_id))
+            .exec(&*tx)
+            .await?;
+        record_security_event(
+            user_id,
+            SecurityEventKind::TwoFactorDisabled,
+            None,
+            &client,
+            &*tx,
+        )
+        .await?;
+        Ok(())
+    })
+    .await
+}
+
+fn not_enabled() -> AppError {
+    AppError::invalid_input("two-factor authentication is not enabled")
+}
+
+fn invalid_code() -> AppError {
+    AppError::invalid_field("code", "the code is not valid")
+}
@@ -0,0 +1,451 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
+};
+use uuid::Uuid;
+
+use super::{task_event::notify_membership_changed, AppError, AppResult};
+use crate::{
+    db::DatabaseTransactionExt,
+    entities::{self, sea_orm_active_enums::WorkspaceRole},
+};
+
+/// The role of a member in a workspace. Each role can do everything the roles before it can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, async_graphql::Enum)]
+#[graphql(name = "WorkspaceRole")]
+pub(crate) enum Role {
+    /// Can see the tasks of the workspace.
+    Viewer,
+    /// Can also create, update and delete the tasks of the workspace.
+    Editor,
+    /// Can also manage the members of the workspace.
+    Owner,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Workspace {
+    pub(crate) id: Uuid,
+    pub(crate) name: String,
+    pub(crate) is_personal: bool,
+    pub(crate) created_at: DateTime<Utc>,
+    /// The role of the user who requested the workspace.
+    pub(crate) role: Role,
+    pub(crate) members: Vec<WorkspaceMember>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct WorkspaceMember {
+    pub(crate) user_id: Uuid,
+    pub(crate) username: String,
+    pub(crate) role: Role,
+}
+
+/// Returns the role of the user in the workspace, or `None` if the user is not a member.
+pub(crate) async fn member_role(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<Option<Role>> {
+    Ok(
+        entities::workspace_members::Entity::find_by_id((workspace_id, user_id))
+            .one(db)
+            .await?
+            .map(|member| member.role.into()),
+    )
+}
+
+/// Checks that the user has at least the `required` role in the workspace. A workspace the user
+/// is not a member of is reported as not found, so that its existence is not revealed.
+pub(crate) async fn require_role(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    required: Role,
+    db: &impl ConnectionTrait,
+) -> AppResult<Role> {
+    let role = member_role(user_id, workspace_id, db)
+        .await?
+        .ok_or_else(|| AppError::workspace_not_found(workspace_id))?;
+    if role < required {
+        return Err(AppError::permission_denied(format!(
+            "the {role:?} role in the workspace does not allow this"
+        )));
+    }
+    Ok(role)
+}
+
+/// Returns the personal workspace of the user, creating it if the user does not have one yet.
+pub(crate) async fn personal_workspace_id(
+    user_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<Uuid> {
+    if let Some(workspace) = find_personal_workspace(user_id, db).await? {
+        return Ok(workspace.id);
+    }
+
+    let user = entities::users::Entity::find_by_id(user_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("user {user_id} does not exist"))?;
+    let workspace_id = Uuid::new_v4();
+    // A concurrent request may create the personal workspace first, in which case this is a no-op.
+    let inserted = entities::workspaces::Entity::insert(entities::workspaces::ActiveModel {
+        id: Set(workspace_id),
+        name: Set(user.username),
+        personal_user_id: Set(Some(user_id)),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(entities::workspaces::Column::PersonalUserId)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    if inserted == 0 {
+        return find_personal_workspace(user_id, db)
+            .await?
+            .map(|workspace| workspace.id)
+            .ok_or_else(|| anyhow::anyhow!("personal workspace of {user_id} vanished").into());
+    }
+
+    entities::workspace_members::ActiveModel {
+        workspace_id: Set(workspace_id),
+        user_id: Set(user_id),
+        role: Set(Role::Owner.into()),
+    }
+    .insert(db)
+    .await?;
+    notify_membership_changed(user_id, workspace_id, db).await?;
+
+    Ok(workspace_id)
+}
+
+async fn find_personal_workspace(
+    user_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<Option<entities::workspaces::Model>> {
+    Ok(entities::workspaces::Entity::find()
+        .filter(entities::workspaces::Column::PersonalUserId.eq(user_id))
+        .one(db)
+        .await?)
+}
+
+/// Lists the workspaces the user is a member of, starting with the personal one.
+pub(crate) async fn list_workspaces(
+    user_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Vec<Workspace>> {
+    personal_workspace_id(user_id, db_conn).await?;
+
+    let mut workspaces = entities::workspace_members::Entity::find()
+        .filter(entities::workspace_members::Column::UserId.eq(user_id))
+        .find_also_related(entities::workspaces::Entity)
+        .all(db_conn)
+        .await?
+        .into_iter()
+        .filter_map(|(member, workspace)| Some((workspace?, Role::from(member.role))))
+        .collect::<Vec<_>>();
+    workspaces.sort_by_key(|(workspace, _)| {
+        (
+            workspace.personal_user_id != Some(user_id),
+            workspace.created_at,
+        )
+    });
+
+    let workspace_ids = workspaces.iter().map(|(workspace, _)| workspace.id);
+    let members = entities::workspace_members::Entity::find()
+        .filter(entities::workspace_members::Column::WorkspaceId.is_in(workspace_ids))
+        .find_also_related(entities::users::Entity)
+        .order_by_asc(entities::users::Column::Username)
+        .all(db_conn)
+        .await?;
+
+    Ok(workspaces
+        .into_iter()
+        .map(|(workspace, role)| Workspace {
+            id: workspace.id,
+            name: workspace.name,
+            is_personal: workspace.personal_user_id.is_some(),
+            created_at: workspace.created_at.into(),
+            role,
+            members: members
+                .iter()
+                .filter(|(member, _)| member.workspace_id == workspace.id)
+                .filter_map(|(member, user)| {
+                    Some(WorkspaceMember::new(member.clone(), user.clone()?))
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Creates a shared workspace with the user as its only owner.
+pub(crate) async fn create_workspace(
+    user_id: Uuid,
+    name: String,
+    db_conn: &DatabaseConnection,
+) -> AppResult<Workspace> {
+    if name.trim().is_empty() {
+        return Err(AppError::invalid_field(
+            "name",
+            "workspace name must not be empty",
+        ));
+    }
+
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let workspace = entities::workspaces::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            name: Set(name),
+            ..Default::default()
+        }
+        .insert(&*tx)
+        .await?;
+        let member = entities::workspace_members::ActiveModel {
+            workspace_id: Set(workspace.id),
+            user_id: Set(user_id),
+            role: Set(Role::Owner.into()),
+        }
+        .insert(&*tx)
+        .await?;
+        notify_membership_changed(user_id, workspace.id, &*tx).await?;
+        let user = entities::users::Entity::find_by_id(user_id)
+            .one(&*tx)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("user {user_id} does not exist"))?;
+
+        Ok::<_, AppError>(Workspace {
+            id: workspace.id,
+            name: workspace.name,
+            is_personal: false,
+            created_at: workspace.created_at.into(),
+            role: Role::Owner,
+            members: vec![WorkspaceMember::new(member, user)],
+        })
+    })
+    .await
+}
+
+/// Adds the user named `username` to the workspace. Only owners can add members.
+pub(crate) async fn add_workspace_member(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    username: &str,
+    role: Role,
+    db_conn: &DatabaseConnection,
+) -> AppResult<WorkspaceMember> {
+    require_role(user_id, workspace_id, Role::Owner, db_conn).await?;
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(username))
+        .one(db_conn)
+        .await?
+        .ok_or_else(|| {
+            AppError::invalid_field("username", format!("user `{username}` does not exist"))
+        })?;
+
+    let member = entities::workspace_members::ActiveModel {
+        workspace_id: Set(workspace_id),
+        user_id: Set(user.id),
+        role: Set(role.into()),
+    }
+    .insert(db_conn)
+    .await
+    .map_err(|err| match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => AppError::invalid_field(
+            "username",
+            format!("user `{username}` is already a member of the workspace"),
+        ),
+        _ => err.into(),
+    })?;
+    notify_membership_changed(user.id, workspace_id, db_conn).await?;
+
+    Ok(WorkspaceMember::new(member, user))
+}
+
+/// Changes the role of a member. Only owners can change roles, and the last owner cannot be
+/// demoted.
+pub(crate) async fn update_workspace_member(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    member_id: Uuid,
+    role: Role,
+    db_conn: &DatabaseConnection,
+) -> AppResult<WorkspaceMember> {
+    let tx = db_conn.begin().await?;
+    let member = tx
+        .with(|tx| async move {
+            let members = lock_members(user_id, workspace_id, &*tx).await?;
+            require_member_role(user_id, &members, Role::Owner)?;
+            let member = find_member(&members, workspace_id, member_id)?;
+            if role != Role::Owner {
+                check_not_last_owner(workspace_id, &members, member_id, &*tx).await?;
+            }
+
+            let mut member = entities::workspace_members::ActiveModel::from(member.clone());
+            member.role = Set(role.into());
+            Ok::<_, AppError>(member.update(&*tx).await?)
+        })
+        .await?;
+
+    let user = entities::users::Entity::find_by_id(member_id)
+        .one(db_conn)
+        .await?
+        .ok_or_else(|| AppError::workspace_member_not_found(member_id))?;
+    Ok(WorkspaceMember::new(member, user))
+}
+
+/// Removes a member from the workspace. Owners can remove anyone, and every member can leave, but
+/// the last owner cannot.
+pub(crate) async fn remove_workspace_member(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    member_id: Uuid,
+    db_conn: &DatabaseConnection,
+) -> AppResult<()> {
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let members = lock_members(user_id, workspace_id, &*tx).await?;
+        let required = if member_id == user_id {
+            Role::Viewer
+        } else {
+            Role::Owner
+        };
+        require_member_role(user_id, &members, required)?;
+        find_member(&members, workspace_id, member_id)?;
+        check_not_last_owner(workspace_id, &members, member_id, &*tx).await?;
+
+        entities::workspace_members::Entity::delete_by_id((workspace_id, member_id))
+            .exec(&*tx)
+            .await?;
+        notify_membership_changed(member_id, workspace_id, &*tx).await?;
+        Ok::<_, AppError>(())
+    })
+    .await
+}
+
+/// Locks the memberships of the workspace, so that concurrent changes cannot leave it without an
+/// owner.
+async fn lock_members(
+    user_id: Uuid,
+    workspace_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<Vec<entities::workspace_members::Model>> {
+    let members = entities::workspace_members::Entity::find()
+        .filter(entities::workspace_members::Column::WorkspaceId.eq(workspace_id))
+        .lock_exclusive()
+        .all(db)
+        .await?;
+    if !members.iter().any(|member| member.user_id == user_id) {
+        return Err(AppError::workspace_not_found(workspace_id));
+    }
+    Ok(members)
+}
+
+fn require_member_role(
+    user_id: Uuid,
+    members: &[entities::workspace_members::Model],
+    required: Role,
+) -> AppResult<()> {
+    let role = members
+        .iter()
+        .find(|member| member.user_id == user_id)
+        .map(|member| Role::from(member.role.clone()));
+    match role {
+        Some(role) if role >= required => Ok(()),
+        Some(role) => Err(AppError::permission_denied(format!(
+            "the {role:?} role in the workspace does not allow this"
+        ))),
+        None => Err(AppError::permission_denied("not a member of the workspace")),
+    }
+}
+
+fn find_member(
+    members: &[entities::workspace_members::Model],
+    workspace_id: Uuid,
+    member_id: Uuid,
+) -> AppResult<&entities::workspace_members::Model> {
+    members
+        .iter()
+        .find(|member| member.workspace_id == workspace_id && member.user_id == member_id)
+        .ok_or_else(|| AppError::workspace_member_not_found(member_id))
+}
+
+/// Checks that the workspace keeps an owner if `member_id` stops being one. The user of a personal
+/// workspace must always stay its owner.
+async fn check_not_last_owner(
+    workspace_id: Uuid,
+    members: &[entities::workspace_members::Model],
+    member_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> AppResult<()> {
+    let workspace = entities::workspaces::Entity::find_by_id(workspace_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| AppError::workspace_not_found(workspace_id))?;
+    if workspace.personal_user_id == Some(member_id) {
+        return Err(AppError::invalid_input(
+            "the user of a personal workspace must stay its owner",
+        ));
+    }
+
+    let has_other_owner = members.iter().any(|member| {
+        member.user_id != member_id && Role::from(member.role.clone()) == Role::Owner
+    });
+    if !has_other_owner {
+        return Err(AppError::invalid_input(
+            "a workspace must have at least one owner",
+        ));
+    }
+    Ok(())
+}
+
+impl WorkspaceMember {
+    fn new(member: entities::workspace_members::Model, user: entities::users::Model) -> Self {
+        Self {
+            user_id: user.id,
+            username: user.username,
+            role: member.role.into(),
+        }
+    }
+}
+
+impl From<WorkspaceRole> for Role {
+    fn from(value: WorkspaceRole) -> Self {
+        match value {
+            WorkspaceRole::Viewer => Role::Viewer,
+            WorkspaceRole::Editor => Role::Editor,
+            WorkspaceRole::Owner => Role::Owner,
+        }
+    }
+}
+
+impl From<Role> for WorkspaceRole {
+    fn from(value: Role) -> Self {
+        match value {
+            Role::Viewer => WorkspaceRole::Viewer,
+            Role::Editor => WorkspaceRole::Editor,
+            Role::Owner => WorkspaceRole::Owner,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn roles_are_ordered_by_permissions() {
+        expect_that!(Role::Viewer, lt(Role::Editor));
+        expect_that!(Role::Editor, lt(Role::Owner));
+    }
+
+    #[googletest::test]
+    fn roles_round_trip_through_the_database_enum() {
+        for role in [Role::Viewer, Role::Editor, Role::Owner] {
+            expect_that!(Role::from(WorkspaceRole::from(role)), eq(role));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! The security audit log, a record of the logins, sessions, tokens and credential changes of each
+//! user, so that they can tell activity that was not theirs.
+
+use std::convert::Infallible;
+
+use axum::{async_trait, extract::FromRequestParts, http::header, http::request};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ConnectionTrait, DbErr, Set};
+use uuid::Uuid;
+
+pub(crate) use crate::entities::sea_orm_active_enums::SecurityEventKind;
+use crate::{entities, rate_limit::RateLimiter};
+
+/// The client that made a request, as recorded in the audit log.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ClientInfo {
+    pub(crate) ip_address: Option<String>,
+    pub(crate) user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        // The rate limiter knows which header the reverse proxy puts the client IP in.
+        let ip_address = parts
+            .extensions
+            .get::<RateLimiter>()
+            .and_then(|limiter| limiter.client_ip(&parts.headers, &parts.extensions));
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        Ok(Self {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+/// How a user logged in, recorded as the detail of [`SecurityEventKind::LoginSucceeded`] and
+/// [`SecurityEventKind::LoginFailed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum LoginMethod {
+    Password,
+    Totp,
+    MagicLink,
+    Oidc,
+    Registration,
+}
+
+/// Adds an event to the audit log of the user. Events of a change are recorded in the same
+/// transaction as the change, so that a change that is rolled back leaves no trace.
+pub(crate) async fn record_security_event(
+    user_id: Uuid,
+    kind: SecurityEventKind,
+    detail: Option<String>,
+    client: &ClientInfo,
+    db: &impl ConnectionTrait,
+) -> Result<(), DbErr> {
+    entities::security_events::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user_id),
+        kind: Set(kind),
+        detail: Set(detail),
+        ip_address: Set(client.ip_address.clone()),
+        user_agent: Set(client.user_agent.clone()),
+        // Not the default of `now()`, which is the same for all events of a transaction.
+        created_at: Set(Utc::now().into()),
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
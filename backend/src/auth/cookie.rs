@@ -0,0 +1,211 @@
+//! Sessions in cookies as an alternative to bearer tokens, so that the tokens are out of reach of
+//! scripts on the page.
+//!
+//! When enabled, the endpoints that start a session put the access token and the refresh token in
+//! `HttpOnly` cookies instead of the response body. Since browsers attach cookies to requests that
+//! other sites trigger as well, a request authenticated by cookie must also repeat the value of the
+//! [`CSRF_TOKEN_COOKIE`] cookie in the [`CSRF_TOKEN_HEADER`] header, which other sites can neither
+//! read nor set.
+
+use axum::{
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use serde::Serialize;
+
+use crate::utils::parse_bool_env;
+
+use super::{
+    secret::generate_secret, session::refresh_token_lifetime, AuthError, LoginResponse, LoginResult,
+};
+
+pub(crate) const ACCESS_TOKEN_COOKIE: &str = "planner_access_token";
+pub(crate) const REFRESH_TOKEN_COOKIE: &str = "planner_refresh_token";
+pub(crate) const CSRF_TOKEN_COOKIE: &str = "planner_csrf_token";
+pub(crate) const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+
+/// The refresh token is only needed by `/auth/refresh` and `/auth/logout`.
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/auth";
+
+#[derive(Clone, Debug)]
+pub struct CookieConfig {
+    /// Whether the cookies are only sent over HTTPS. Only to be turned off for development over
+    /// plain HTTP.
+    pub secure: bool,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self { secure: true }
+    }
+}
+
+impl CookieConfig {
+    /// Reads the config from `$SESSION_COOKIES` and `$SESSION_COOKIES_SECURE`. Returns `None` unless
+    /// `$SESSION_COOKIES` is `true`.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        if !parse_bool_env("SESSION_COOKIES")?.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            secure: parse_bool_env("SESSION_COOKIES_SECURE")?.unwrap_or(true),
+        }))
+    }
+
+    fn cookie(&self, name: &'static str, value: String, path: &'static str) -> Cookie<'static> {
+        Cookie::build((name, value))
+            .path(path)
+            .http_only(name != CSRF_TOKEN_COOKIE)
+            .secure(self.secure)
+            .same_site(SameSite::Strict)
+            // The access token expires long before the cookie does, so that an expired token is
+            // still sent and reported as such, telling the client to refresh the session.
+            .max_age(
+                refresh_token_lifetime()
+                    .to_std()
+                    .expect("the lifetime is positive")
+                    .try_into()
+                    .expect("the lifetime is in range"),
+            )
+            .build()
+    }
+}
+
+/// The response body of a new session in cookie mode, in place of [`LoginResponse`].
+#[derive(Debug, Serialize)]
+struct CookieSessionResponse {
+    /// The value to send in the [`CSRF_TOKEN_HEADER`] header, which is also readable from the
+    /// [`CSRF_TOKEN_COOKIE`] cookie.
+    csrf_token: String,
+}
+
+/// Hands the result of a login to the client: in the response body, or in cookies if enabled.
+pub(crate) fn login_result_response(
+    cookie_config: Option<&CookieConfig>,
+    result: LoginResult,
+) -> Response {
+    match (cookie_config, result) {
+        (Some(cookie_config), LoginResult::Session(session)) => {
+            session_cookies_response(cookie_config, session)
+        }
+        (_, result) => Json(result).into_response(),
+    }
+}
+
+/// Like [`login_result_response`], for the endpoints that always start a session.
+pub(crate) fn session_response(
+    cookie_config: Option<&CookieConfig>,
+    session: LoginResponse,
+) -> Response {
+    login_result_response(cookie_config, LoginResult::Session(session))
+}
+
+fn session_cookies_response(cookie_config: &CookieConfig, session: LoginResponse) -> Response {
+    let csrf_token = generate_secret();
+    let jar = CookieJar::new()
+        .add(cookie_config.cookie(ACCESS_TOKEN_COOKIE, session.token, "/"))
+        .add(cookie_config.cookie(
+            REFRESH_TOKEN_COOKIE,
+            session.refresh_token,
+            REFRESH_TOKEN_COOKIE_PATH,
+        ))
+        .add(cookie_config.cookie(CSRF_TOKEN_COOKIE, csrf_token.clone(), "/"));
+
+    (jar, Json(CookieSessionResponse { csrf_token })).into_response()
+}
+
+/// The cookies that remove the session cookies of the request from the browser.
+pub(crate) fn clear_session_cookies(headers: &HeaderMap) -> CookieJar {
+    [
+        (ACCESS_TOKEN_COOKIE, "/"),
+        (REFRESH_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE_PATH),
+        (CSRF_TOKEN_COOKIE, "/"),
+    ]
+    .into_iter()
+    .fold(CookieJar::from_headers(headers), |jar, (name, path)| {
+        jar.remove(Cookie::build(name).path(path))
+    })
+}
+
+/// Returns the refresh token from the cookie, if any.
+pub(crate) fn refresh_token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    CookieJar::from_headers(headers)
+        .get(REFRESH_TOKEN_COOKIE)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+/// Returns the access token from the cookie, if any, after checking that the request carries the
+/// CSRF token of the session.
+pub(crate) fn access_token_from_cookie(headers: &HeaderMap) -> Result<Option<String>, AuthError> {
+    let jar = CookieJar::from_headers(headers);
+    let Some(access_token) = jar.get(ACCESS_TOKEN_COOKIE) else {
+        return Ok(None);
+    };
+
+    let csrf_cookie = jar.get(CSRF_TOKEN_COOKIE).map(Cookie::value);
+    let csrf_header = headers
+        .get(CSRF_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (csrf_cookie, csrf_header) {
+        (Some(cookie), Some(header)) if !cookie.is_empty() && cookie == header => {
+            Ok(Some(access_token.value().to_owned()))
+        }
+        _ => Err(AuthError::InvalidCsrfToken),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use googletest::prelude::*;
+
+    use super::*;
+
+    fn headers(cookie: &str, csrf_token: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", HeaderValue::from_str(cookie).unwrap());
+        if let Some(csrf_token) = csrf_token {
+            headers.insert(
+                CSRF_TOKEN_HEADER,
+                HeaderValue::from_str(csrf_token).unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[googletest::test]
+    fn access_token_from_cookie_accepts_matching_csrf_token() {
+        expect_that!(
+            access_token_from_cookie(&headers(
+                "planner_access_token=jwt; planner_csrf_token=csrf",
+                Some("csrf")
+            )),
+            ok(some(eq("jwt")))
+        );
+    }
+
+    #[googletest::test]
+    fn access_token_from_cookie_rejects_missing_or_wrong_csrf_token() {
+        let cookie = "planner_access_token=jwt; planner_csrf_token=csrf";
+
+        expect_that!(
+            access_token_from_cookie(&headers(cookie, None)),
+            err(pat!(AuthError::InvalidCsrfToken))
+        );
+        expect_that!(
+            access_token_from_cookie(&headers(cookie, Some("other"))),
+            err(pat!(AuthError::InvalidCsrfToken))
+        );
+    }
+
+    #[googletest::test]
+    fn access_token_from_cookie_without_cookie_returns_none() {
+        expect_that!(
+            access_token_from_cookie(&headers("theme=dark", None)),
+            ok(none())
+        );
+    }
+}
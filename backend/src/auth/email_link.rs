@@ -0,0 +1,311 @@
+//! Logins and password resets with single-use links sent by email, for users who have forgotten
+//! their password or never had one.
+//!
+//! The frontend sends the email address to `/auth/magic-link` or `/auth/password-reset`, which
+//! mails a link with a token to the frontend if the address belongs to a user. The frontend then
+//! exchanges the token at `/auth/magic-link/login` or `/auth/password-reset/confirm`.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State, http::StatusCode, response::Response, routing, Extension, Json, Router,
+};
+use chrono::{TimeDelta, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, IntoActiveModel, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use super::{
+    audit::{record_security_event, ClientInfo, LoginMethod, SecurityEventKind},
+    cookie::{login_result_response, session_response, CookieConfig},
+    hash_password,
+    register::validate_password,
+    secret::{generate_secret, hash_secret},
+    session::start_session,
+    totp, AuthError, LoginResult,
+};
+use crate::{
+    db::DatabaseTransactionExt,
+    entities::{self, sea_orm_active_enums::EmailTokenPurpose},
+    mail::{normalize_address, Mailer},
+    rate_limit::RateLimiter,
+};
+
+const MAGIC_LINK_PATH: &str = "/login/magic-link";
+const PASSWORD_RESET_PATH: &str = "/reset-password";
+
+fn magic_link_lifetime() -> TimeDelta {
+    TimeDelta::minutes(15)
+}
+
+fn password_reset_lifetime() -> TimeDelta {
+    TimeDelta::hours(1)
+}
+
+pub(crate) fn routes(mailer: Mailer) -> Router {
+    Router::new()
+        .route("/magic-link", routing::post(request_magic_link_handler))
+        .route("/magic-link/login", routing::post(magic_link_login_handler))
+        .route(
+            "/password-reset",
+            routing::post(request_password_reset_handler),
+        )
+        .route(
+            "/password-reset/confirm",
+            routing::post(password_reset_handler),
+        )
+        .with_state(Arc::new(mailer))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct EmailLinkRequest {
+    pub(crate) email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MagicLinkLoginRequest {
+    pub(crate) token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PasswordResetRequest {
+    pub(crate) token: String,
+    pub(crate) password: String,
+}
+
+/// Mails a login link to the user with the email address. The response is the same whether or not
+/// such a user exists, so that it cannot be used to find out who has an account.
+pub(crate) async fn request_magic_link_handler(
+    State(mailer): State<Arc<Mailer>>,
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Json(payload): Json<EmailLinkRequest>,
+) -> Result<StatusCode, AuthError> {
+    send_link(
+        &mailer,
+        &payload.email,
+        EmailTokenPurpose::MagicLink,
+        &db_conn,
+    )
+    .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Mails a password reset link to the user with the email address. Like
+/// [`request_magic_link_handler`], it does not reveal whether such a user exists.
+pub(crate) async fn request_password_reset_handler(
+    State(mailer): State<Arc<Mailer>>,
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Json(payload): Json<EmailLinkRequest>,
+) -> Result<StatusCode, AuthError> {
+    send_link(
+        &mailer,
+        &payload.email,
+        EmailTokenPurpose::PasswordReset,
+        &db_conn,
+    )
+    .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Logs in with the token of a login link. The link takes the place of the password, so users with
+/// two-factor authentication still need to answer the challenge.
+pub(crate) async fn magic_link_login_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    Json(payload): Json<MagicLinkLoginRequest>,
+) -> Result<Response, AuthError> {
+    let tx = db_conn.begin().await?;
+    let user = tx
+        .with(|tx| async move {
+            consume_token(&payload.token, EmailTokenPurpose::MagicLink, &*tx).await
+        })
+        .await?;
+    info!(username = user.username, "Logged in with a login link");
+
+    if totp::find_enabled_totp(user.id, &db_conn).await?.is_some() {
+        let challenge = totp::start_challenge(&user, client.user_agent.clone(), &db_conn).await?;
+        return Ok(login_result_response(
+            cookie_config.as_ref(),
+            LoginResult::TotpRequired(challenge),
+        ));
+    }
+    Ok(session_response(
+        cookie_config.as_ref(),
+        start_session(&user, LoginMethod::MagicLink, &client, &db_conn).await?,
+    ))
+}
+
+/// Sets a new password with the token of a password reset link. All sessions of the user are
+/// revoked, since the reset may well be because the old password was leaked.
+pub(crate) async fn password_reset_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(rate_limiter): Extension<RateLimiter>,
+    client: ClientInfo,
+    Json(payload): Json<PasswordResetRequest>,
+) -> Result<StatusCode, AuthError> {
+    validate_password(&payload.password)?;
+    let password_hash = hash_password(&payload.password)?;
+    let client_ip = client.ip_address.clone();
+
+    let tx = db_conn.begin().await?;
+    let username = tx
+        .with(|tx| async move {
+            let user =
+                consume_token(&payload.token, EmailTokenPurpose::PasswordReset, &*tx).await?;
+            // Other links that are still out there are no longer wanted either.
+            entities::email_tokens::Entity::delete_many()
+                .filter(entities::email_tokens::Column::UserId.eq(user.id))
+                .exec(&*tx)
+                .await?;
+            entities::sessions::Entity::update_many()
+                .col_expr(
+                    entities::sessions::Column::RevokedAt,
+                    Expr::current_timestamp().into(),
+                )
+                .filter(entities::sessions::Column::UserId.eq(user.id))
+                .filter(entities::sessions::Column::RevokedAt.is_null())
+                .exec(&*tx)
+                .await?;
+
+            record_security_event(
+                user.id,
+                SecurityEventKind::PasswordChanged,
+                Some("reset".to_owned()),
+                &client,
+                &*tx,
+            )
+            .await?;
+
+            let username = user.username.clone();
+            let mut user = user.into_active_model();
+            user.password_hash = Set(Some(password_hash));
+            user.update(&*tx).await?;
+            Ok::<_, AuthError>(username)
+        })
+        .await?;
+
+    // Whoever was guessing the old password has no use for the lockout anymore, but the owner
+    // should be able to log in with the new one right away, from any IP.
+    rate_limiter.lift_login_lockouts(&username).await?;
+    rate_limiter
+        .clear_login_failures(&username, client_ip.as_deref())
+        .await?;
+    info!(username, "Password reset");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the tokens that were never used and have expired. Returns the number of deleted tokens.
+pub async fn delete_expired_email_tokens(db_conn: &DatabaseConnection) -> Result<u64, DbErr> {
+    let result = entities::email_tokens::Entity::delete_many()
+        .filter(entities::email_tokens::Column::ExpiresAt.lt(Utc::now()))
+        .exec(db_conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Creates a token for the user with the email address if any, and mails the link with it in the
+/// background.
+async fn send_link(
+    mailer: &Arc<Mailer>,
+    email: &str,
+    purpose: EmailTokenPurpose,
+    db_conn: &DatabaseConnection,
+) -> Result<(), AuthError> {
+    let email = normalize_address(email)
+        .ok_or_else(|| AuthError::invalid_input("email", "email is not a valid email address"))?;
+    let Some(user) = entities::users::Entity::find()
+        .filter(entities::users::Column::Email.eq(&email))
+        .one(db_conn)
+        .await?
+    else {
+        info!(email, "Requested an email link for an unknown address");
+        return Ok(());
+    };
+
+    // Only the latest link of each kind works, so that the user is not left guessing which one to
+    // click.
+    entities::email_tokens::Entity::delete_many()
+        .filter(entities::email_tokens::Column::UserId.eq(user.id))
+        .filter(entities::email_tokens::Column::Purpose.eq(purpose.clone()))
+        .exec(db_conn)
+        .await?;
+
+    let token = generate_secret();
+    let lifetime = match purpose {
+        EmailTokenPurpose::MagicLink => magic_link_lifetime(),
+        EmailTokenPurpose::PasswordReset => password_reset_lifetime(),
+    };
+    entities::email_tokens::ActiveModel {
+        token_hash: Set(hash_secret(&token)),
+        user_id: Set(user.id),
+        purpose: Set(purpose.clone()),
+        expires_at: Set((Utc::now() + lifetime).into()),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+
+    let (subject, body) = match purpose {
+        EmailTokenPurpose::MagicLink => (
+            "Your Planner login link",
+            format!(
+                "Hi {},\n\nOpen this link to log in to Planner:\n\n{}\n\nThe link expires in {} \
+                 minutes and can only be used once. If you did not ask for it, you can ignore \
+                 this email.\n",
+                user.username,
+                mailer.link(MAGIC_LINK_PATH, &token),
+                lifetime.num_minutes(),
+            ),
+        ),
+        EmailTokenPurpose::PasswordReset => (
+            "Reset your Planner password",
+            format!(
+                "Hi {},\n\nOpen this link to choose a new password for Planner:\n\n{}\n\nThe link \
+                 expires in {} minutes and can only be used once. If you did not ask for it, you \
+                 can ignore this email and keep your current password.\n",
+                user.username,
+                mailer.link(PASSWORD_RESET_PATH, &token),
+                lifetime.num_minutes(),
+            ),
+        ),
+    };
+    // Sending in the background keeps the response time the same for unknown addresses.
+    let mailer = mailer.clone();
+    tokio::spawn(async move {
+        if let Err(err) = mailer.send(&email, subject, body).await {
+            error!(username = user.username, "Failed to send email: {err:?}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Returns the user of an unexpired token, deleting the token so that the link only works once.
+async fn consume_token(
+    token: &str,
+    purpose: EmailTokenPurpose,
+    db: &impl ConnectionTrait,
+) -> Result<entities::users::Model, AuthError> {
+    // Locking the row makes concurrent uses of the same token wait for each other, so only the
+    // first one finds it.
+    let token = entities::email_tokens::Entity::find_by_id(hash_secret(token))
+        .filter(entities::email_tokens::Column::Purpose.eq(purpose))
+        .filter(entities::email_tokens::Column::ExpiresAt.gt(Utc::now()))
+        .lock_exclusive()
+        .one(db)
+        .await?
+        .ok_or(AuthError::InvalidCredential)?;
+    entities::email_tokens::Entity::delete_by_id(token.token_hash)
+        .exec(db)
+        .await?;
+
+    entities::users::Entity::find_by_id(token.user_id)
+        .one(db)
+        .await?
+        .ok_or(AuthError::InvalidCredential)
+}
@@ -0,0 +1,477 @@
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    routing, Extension, Json, Router,
+};
+use axum_extra::headers::{authorization::Bearer, Authorization, HeaderMapExt};
+use chrono::TimeDelta;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel,
+    QueryFilter, Set, SqlErr,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    entities,
+    error::{ApiError, ErrorCode},
+    mail::{normalize_address, Mailer, SmtpConfig},
+    rate_limit::{too_many_requests, RateLimitError, RateLimiter},
+};
+
+mod api_token;
+mod audit;
+mod cookie;
+mod email_link;
+mod keys;
+mod oidc;
+mod password;
+mod register;
+mod secret;
+mod session;
+mod totp;
+
+pub(crate) use api_token::generate_api_token;
+pub(crate) use audit::{record_security_event, ClientInfo, SecurityEventKind};
+pub use cookie::CookieConfig;
+pub(crate) use cookie::CSRF_TOKEN_HEADER;
+pub use email_link::delete_expired_email_tokens;
+pub use oidc::{delete_expired_oidc_auth_requests, OidcConfig};
+pub use password::hash_password;
+pub use register::create_invite_code;
+pub(crate) use session::active_session_condition;
+pub(crate) use totp::{
+    check_code, encode_totp_secret, find_enabled_totp, generate_totp_secret, provisioning_uri,
+    replace_recovery_codes, require_key, AcceptedCodes,
+};
+pub use totp::{delete_expired_login_challenges, reset_two_factor, TotpEncryptionKey};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Claims {
+    pub(crate) sub: String,
+    /// The id of the session in the `sessions` table that this token belongs to.
+    pub(crate) sid: Uuid,
+    pub(crate) exp: u64,
+}
+
+/// The credential that a request is authenticated with.
+///
+/// Once extracted, it is cached in the request extensions, so that a middleware and the handler
+/// can both extract it without authenticating the request twice.
+#[derive(Clone, Debug)]
+pub(crate) enum Principal {
+    /// An access token of a login session.
+    Session { claims: Claims, user_id: Uuid },
+    /// A personal API token.
+    ApiToken(entities::api_tokens::Model),
+}
+
+impl Principal {
+    pub(crate) fn user_id(&self) -> Uuid {
+        match self {
+            Principal::Session { user_id, .. } => *user_id,
+            Principal::ApiToken(api_token) => api_token.user_id,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LoginRequest {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct LoginResponse {
+    /// The short-lived access token to be sent as `Authorization: Bearer`.
+    pub(crate) token: String,
+    /// The long-lived token to get a new access token from `/auth/refresh`.
+    pub(crate) refresh_token: String,
+}
+
+/// The response of `/auth/login`: either the tokens of the new session, or a challenge for the
+/// second factor if the user has two-factor authentication enabled.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub(crate) enum LoginResult {
+    Session(LoginResponse),
+    TotpRequired(totp::TotpChallenge),
+}
+
+/// Why the bearer token of a request was not accepted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum TokenError {
+    #[error("No bearer token was given")]
+    Missing,
+    /// The client should get a new access token with its refresh token.
+    #[error("The token has expired")]
+    Expired,
+    #[error("The token is malformed")]
+    Malformed,
+    /// The signature is wrong, or the session or API token that the token belongs to is gone.
+    #[error("The token is invalid")]
+    Invalid,
+}
+
+impl TokenError {
+    /// The `WWW-Authenticate` header of the response, as specified in RFC 6750.
+    pub(crate) fn www_authenticate(self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            TokenError::Missing => r#"Bearer realm="planner""#,
+            TokenError::Expired => {
+                r#"Bearer realm="planner", error="invalid_token", error_description="The token has expired""#
+            }
+            TokenError::Malformed => {
+                r#"Bearer realm="planner", error="invalid_token", error_description="The token is malformed""#
+            }
+            TokenError::Invalid => {
+                r#"Bearer realm="planner", error="invalid_token", error_description="The token is invalid""#
+            }
+        })
+    }
+}
+
+impl From<&TokenError> for ApiError {
+    fn from(value: &TokenError) -> Self {
+        let code = match value {
+            TokenError::Missing => ErrorCode::Unauthorized,
+            TokenError::Expired => ErrorCode::TokenExpired,
+            TokenError::Malformed => ErrorCode::TokenMalformed,
+            TokenError::Invalid => ErrorCode::TokenInvalid,
+        };
+        ApiError::new(code, value.to_string())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum AuthError {
+    #[error("Invalid credentials")]
+    InvalidCredential,
+    #[error(transparent)]
+    Token(#[from] TokenError),
+    #[error("Failed to generate JWT token")]
+    TokenGenerationError,
+    #[error("Invalid invite code")]
+    InvalidInviteCode,
+    #[error("Username is already taken")]
+    UsernameTaken,
+    /// `field` is the name of the request field that is not valid.
+    #[error("Invalid input: {reason}")]
+    InvalidInput { field: &'static str, reason: String },
+    /// A request authenticated by the session cookie did not repeat the CSRF token of the session.
+    #[error("The CSRF token is missing or wrong")]
+    InvalidCsrfToken,
+    #[error("Too many requests, retry after {retry_after}")]
+    TooManyRequests { retry_after: TimeDelta },
+    #[error("Internal error: {0}")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl AuthError {
+    fn invalid_input(field: &'static str, reason: impl Into<String>) -> Self {
+        AuthError::InvalidInput {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl From<DbErr> for AuthError {
+    fn from(value: DbErr) -> Self {
+        AuthError::Internal(value.into())
+    }
+}
+
+impl From<RateLimitError> for AuthError {
+    fn from(value: RateLimitError) -> Self {
+        match value {
+            RateLimitError::Limited { retry_after } => AuthError::TooManyRequests { retry_after },
+            RateLimitError::Db(err) => err.into(),
+        }
+    }
+}
+
+pub(crate) async fn login_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(rate_limiter): Extension<RateLimiter>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Response, AuthError> {
+    // The lockout applies even to the correct password, otherwise guessing could just go on.
+    rate_limiter
+        .check_login_lockout(&payload.username, client.ip_address.as_deref())
+        .await?;
+
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(&payload.username))
+        .one(&db_conn)
+        .await?;
+
+    let password_hash = user.as_ref().and_then(|user| user.password_hash.as_deref());
+    if !password::verify_password(&payload.password, password_hash) {
+        warn!(username = payload.username, "Failed login attempt");
+        rate_limiter
+            .record_login_failure(&payload.username, client.ip_address.as_deref())
+            .await?;
+        if let Some(user) = &user {
+            audit::record_security_event(
+                user.id,
+                SecurityEventKind::LoginFailed,
+                Some(audit::LoginMethod::Password.to_string()),
+                &client,
+                &db_conn,
+            )
+            .await?;
+        }
+        return Err(AuthError::InvalidCredential);
+    }
+    let user = user.ok_or(AuthError::InvalidCredential)?;
+
+    if totp::find_enabled_totp(user.id, &db_conn).await?.is_some() {
+        // The failures are only cleared once the second factor is accepted, otherwise logging in
+        // again with the password would allow guessing codes without ever being locked out.
+        let challenge = totp::start_challenge(&user, client.user_agent.clone(), &db_conn).await?;
+        return Ok(cookie::login_result_response(
+            cookie_config.as_ref(),
+            LoginResult::TotpRequired(challenge),
+        ));
+    }
+
+    rate_limiter
+        .clear_login_failures(&payload.username, client.ip_address.as_deref())
+        .await?;
+    Ok(cookie::session_response(
+        cookie_config.as_ref(),
+        session::start_session(&user, audit::LoginMethod::Password, &client, &db_conn).await?,
+    ))
+}
+
+/// Sets the login password of the user with `username`, replacing the existing one if any.
+pub async fn set_user_password(
+    username: &str,
+    password: &str,
+    db_conn: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let mut user = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(username))
+        .one(db_conn)
+        .await?
+        .with_context(|| format!("user `{username}` does not exist"))?
+        .into_active_model();
+    user.password_hash = Set(Some(hash_password(password)?));
+    let user = user.update(db_conn).await?;
+    record_security_event(
+        user.id,
+        SecurityEventKind::PasswordChanged,
+        Some("admin".to_owned()),
+        &ClientInfo::default(),
+        db_conn,
+    )
+    .await?;
+    info!(username, "Password updated");
+
+    Ok(())
+}
+
+/// Sets the email address that login links and password resets of the user with `username` are
+/// sent to, or removes it if `None`.
+pub async fn set_user_email(
+    username: &str,
+    email: Option<&str>,
+    db_conn: &DatabaseConnection,
+) -> anyhow::Result<()> {
+    let email = email
+        .map(|email| {
+            normalize_address(email).with_context(|| format!("`{email}` is not a valid address"))
+        })
+        .transpose()?;
+    let mut user = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(username))
+        .one(db_conn)
+        .await?
+        .with_context(|| format!("user `{username}` does not exist"))?
+        .into_active_model();
+    user.email = Set(email);
+    user.update(db_conn)
+        .await
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => {
+                anyhow::anyhow!("the address is already used by another user")
+            }
+            _ => err.into(),
+        })?;
+    info!(username, "Email address updated");
+
+    Ok(())
+}
+
+pub(crate) fn routes(
+    oidc_config: Option<OidcConfig>,
+    smtp_config: Option<SmtpConfig>,
+    jwt_allow_ephemeral: bool,
+) -> Router {
+    // Load the keys and check the SMTP config eagerly, so that a misconfiguration fails at startup
+    // instead of at the first login.
+    keys::JwtKeys::init(jwt_allow_ephemeral);
+    let mailer = smtp_config.map(|config| Mailer::new(&config).expect("Invalid SMTP config"));
+
+    let router = Router::new()
+        .route("/login", routing::post(login_handler))
+        .route("/login/totp", routing::post(totp::totp_login_handler))
+        .route("/register", routing::post(register::register_handler))
+        .route("/refresh", routing::post(session::refresh_handler))
+        .route("/logout", routing::post(session::logout_handler));
+
+    let router = match oidc_config {
+        Some(oidc_config) => router.nest("/oidc", oidc::routes(oidc_config)),
+        None => router,
+    };
+    match mailer {
+        Some(mailer) => router.merge(email_link::routes(mailer)),
+        None => router,
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(principal) = parts.extensions.get::<Principal>() {
+            return Ok(principal.clone());
+        }
+
+        let principal = authenticate(&parts.headers, db_conn(parts)?).await?;
+        parts.extensions.insert(principal.clone());
+
+        Ok(principal)
+    }
+}
+
+/// Authenticates a request by the bearer token in its headers, or by the session cookie if there
+/// is none.
+pub(crate) async fn authenticate(
+    headers: &HeaderMap,
+    db_conn: &DatabaseConnection,
+) -> Result<Principal, AuthError> {
+    // The header takes precedence, so that a script with a token is not mistaken for the browser
+    // session that it runs in.
+    match headers.typed_get::<Authorization<Bearer>>() {
+        Some(Authorization(bearer)) if api_token::is_api_token(bearer.token()) => Ok(
+            Principal::ApiToken(api_token::authenticate_api_token(bearer.token(), db_conn).await?),
+        ),
+        Some(Authorization(bearer)) => authenticate_session(bearer.token(), db_conn).await,
+        None => {
+            let access_token =
+                cookie::access_token_from_cookie(headers)?.ok_or(TokenError::Missing)?;
+            authenticate_session(&access_token, db_conn).await
+        }
+    }
+}
+
+async fn authenticate_session(
+    access_token: &str,
+    db_conn: &DatabaseConnection,
+) -> Result<Principal, AuthError> {
+    let claims = session::decode_access_token(access_token)?;
+    let session = session::find_active_session(claims.sid, db_conn)
+        .await?
+        .ok_or(TokenError::Invalid)?;
+    Ok(Principal::Session {
+        claims,
+        user_id: session.user_id,
+    })
+}
+
+fn db_conn(parts: &request::Parts) -> Result<&DatabaseConnection, AuthError> {
+    Ok(parts
+        .extensions
+        .get::<DatabaseConnection>()
+        .context("`DatabaseConnection` is not in the request extensions")?)
+}
+
+impl From<&AuthError> for ApiError {
+    fn from(value: &AuthError) -> Self {
+        match value {
+            AuthError::InvalidCredential => {
+                ApiError::new(ErrorCode::InvalidCredentials, value.to_string())
+            }
+            AuthError::Token(err) => err.into(),
+            AuthError::TokenGenerationError => {
+                ApiError::internal(&anyhow::anyhow!("failed to generate JWT token"))
+            }
+            AuthError::InvalidInviteCode => {
+                ApiError::new(ErrorCode::InvalidInviteCode, value.to_string())
+            }
+            AuthError::UsernameTaken => ApiError::new(ErrorCode::UsernameTaken, value.to_string()),
+            AuthError::InvalidInput { field, reason } => {
+                ApiError::new(ErrorCode::InvalidInput, reason).with_field(field)
+            }
+            AuthError::InvalidCsrfToken => {
+                ApiError::new(ErrorCode::InvalidCsrfToken, value.to_string())
+            }
+            AuthError::TooManyRequests { retry_after } => too_many_requests(*retry_after),
+            AuthError::Internal(err) => ApiError::internal(err),
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = ApiError::from(&self).into_response();
+        if let AuthError::Token(err) = self {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, err.www_authenticate());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    #[tokio::test]
+    async fn when_token_incorrect_validate_returns_error() -> Result<()> {
+        let (mut request_part, _) = Request::get("http://localhost/")
+            .header("Authorization", "Bearer invalid-token")
+            .extension(DatabaseConnection::Disconnected)
+            .body(())?
+            .into_parts();
+
+        verify_that!(
+            Principal::from_request_parts(&mut request_part, &()).await,
+            err(pat!(AuthError::Token(eq(&TokenError::Malformed))))
+        )
+    }
+
+    #[googletest::test]
+    #[tokio::test]
+    async fn when_token_missing_validate_returns_error() -> Result<()> {
+        let (mut request_part, _) = Request::get("http://localhost/")
+            .extension(DatabaseConnection::Disconnected)
+            .body(())?
+            .into_parts();
+
+        verify_that!(
+            Principal::from_request_parts(&mut request_part, &()).await,
+            err(pat!(AuthError::Token(eq(&TokenError::Missing))))
+        )
+    }
+}
@@ -0,0 +1,435 @@
+//! Login with an OpenID Connect provider using the authorization code flow with PKCE.
+//!
+//! The frontend gets the authorization URL from `/auth/oidc/authorize` and sends the user there.
+//! The provider redirects back to the frontend with `code` and `state`, which the frontend then
+//! exchanges for a session at `/auth/oidc/callback`.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::{extract::State, response::Response, routing, Extension, Json, Router};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    audit::{ClientInfo, LoginMethod},
+    cookie::{session_response, CookieConfig},
+    register::MAX_USERNAME_LENGTH,
+    secret::{generate_secret, hash_secret},
+    session::start_session,
+    AuthError,
+};
+use crate::{db::DatabaseTransactionExt, entities};
+
+/// How long the user has to complete the login at the provider.
+fn auth_request_lifetime() -> TimeDelta {
+    TimeDelta::minutes(10)
+}
+
+/// Deletes the auth requests that were never completed and have expired. Returns the number of
+/// deleted requests.
+pub async fn delete_expired_oidc_auth_requests(db_conn: &DatabaseConnection) -> Result<u64, DbErr> {
+    let result = entities::oidc_auth_requests::Entity::delete_many()
+        .filter(
+            entities::oidc_auth_requests::Column::CreatedAt
+                .lt(Utc::now() - auth_request_lifetime()),
+        )
+        .exec(db_conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// The issuer URL of the provider, which serves the discovery document under
+    /// `/.well-known/openid-configuration`.
+    pub issuer_url: String,
+    pub client_id: String,
+    /// The client secret for confidential clients. Public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// The frontend URL that the provider redirects back to after the login.
+    pub redirect_url: String,
+}
+
+impl OidcConfig {
+    /// Reads the config from `$OIDC_ISSUER_URL`, `$OIDC_CLIENT_ID`, `$OIDC_CLIENT_SECRET` and
+    /// `$OIDC_REDIRECT_URL`. Returns `None` if `$OIDC_ISSUER_URL` is not set.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(issuer_url) = std::env::var("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            issuer_url,
+            client_id: std::env::var("OIDC_CLIENT_ID")
+                .context("$OIDC_CLIENT_ID must be set when $OIDC_ISSUER_URL is")?,
+            client_secret: std::env::var("OIDC_CLIENT_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+            redirect_url: std::env::var("OIDC_REDIRECT_URL")
+                .context("$OIDC_REDIRECT_URL must be set when $OIDC_ISSUER_URL is")?,
+        }))
+    }
+}
+
+pub(crate) fn routes(config: OidcConfig) -> Router {
+    Router::new()
+        .route("/authorize", routing::get(authorize_handler))
+        .route("/callback", routing::post(callback_handler))
+        .with_state(Arc::new(OidcClient::new(config)))
+}
+
+struct OidcClient {
+    config: OidcConfig,
+    http_client: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+/// The subset of the provider metadata from the discovery document that the login needs.
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    preferred_username: Option<String>,
+}
+
+impl OidcClient {
+    fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http_client: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+        }
+    }
+
+    /// Returns the provider metadata, fetching the discovery document on first use.
+    async fn metadata(&self) -> anyhow::Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer_url.trim_end_matches('/')
+                );
+                self.http_client
+                    .get(&url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .with_context(|| format!("Failed to fetch the OIDC discovery document {url}"))
+            })
+            .await
+    }
+
+    async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<String, AuthError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .context("Failed to send the OIDC token request")?;
+        if !response.status().is_success() {
+            warn!(status = %response.status(), "OIDC token request was rejected");
+            return Err(AuthError::InvalidCredential);
+        }
+        let response: TokenResponse = response
+            .json()
+            .await
+            .context("Failed to parse the OIDC token response")?;
+
+        Ok(response.id_token)
+    }
+
+    async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AuthError> {
+        let metadata = self.metadata().await?;
+        let header = jsonwebtoken::decode_header(id_token).map_err(|err| {
+            warn!("Malformed OIDC ID token: {err}");
+            AuthError::InvalidCredential
+        })?;
+        // A symmetric key cannot come from the public key set, so only asymmetric algorithms are
+        // accepted.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            warn!(alg = ?header.alg, "OIDC ID token is signed with an unsupported algorithm");
+            return Err(AuthError::InvalidCredential);
+        }
+
+        // The key set is fetched for every login, so that key rotations at the provider are picked
+        // up without a restart.
+        let jwks: JwkSet = self
+            .http_client
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("Failed to fetch the OIDC key set")?
+            .json()
+            .await
+            .context("Failed to parse the OIDC key set")?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| {
+            warn!(
+                kid = header.kid,
+                "No key in the OIDC key set matches the ID token"
+            );
+            AuthError::InvalidCredential
+        })?;
+        let key = DecodingKey::from_jwk(jwk).context("Invalid key in the OIDC key set")?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|err| {
+                warn!("Invalid OIDC ID token: {err}");
+                AuthError::InvalidCredential
+            })?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            warn!("OIDC ID token nonce does not match");
+            return Err(AuthError::InvalidCredential);
+        }
+
+        Ok(claims)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuthorizeResponse {
+    /// The URL of the provider to send the user to.
+    authorization_url: String,
+}
+
+async fn authorize_handler(
+    State(client): State<Arc<OidcClient>>,
+    Extension(db_conn): Extension<DatabaseConnection>,
+) -> Result<Json<AuthorizeResponse>, AuthError> {
+    let metadata = client.metadata().await?;
+    let state = generate_secret();
+    let nonce = generate_secret();
+    let code_verifier = generate_secret();
+
+    entities::oidc_auth_requests::ActiveModel {
+        state_hash: Set(hash_secret(&state)),
+        code_verifier: Set(code_verifier.clone()),
+        nonce: Set(nonce.clone()),
+        ..Default::default()
+    }
+    .insert(&db_conn)
+    .await?;
+
+    let authorization_url = reqwest::Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        [
+            ("response_type", "code"),
+            ("client_id", &client.config.client_id),
+            ("redirect_uri", &client.config.redirect_url),
+            ("scope", "openid profile"),
+            ("state", &state),
+            ("nonce", &nonce),
+            ("code_challenge", &pkce_challenge(&code_verifier)),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .context("Invalid OIDC authorization endpoint")?;
+
+    Ok(Json(AuthorizeResponse {
+        authorization_url: authorization_url.into(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackRequest {
+    code: String,
+    state: String,
+}
+
+async fn callback_handler(
+    State(client): State<Arc<OidcClient>>,
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client_info: ClientInfo,
+    Json(payload): Json<CallbackRequest>,
+) -> Result<Response, AuthError> {
+    let auth_request = take_auth_request(&payload.state, &db_conn).await?;
+
+    let id_token = client
+        .exchange_code(&payload.code, &auth_request.code_verifier)
+        .await?;
+    let claims = client
+        .verify_id_token(&id_token, &auth_request.nonce)
+        .await?;
+    let issuer = &client.metadata().await?.issuer;
+    let user = find_or_create_user(issuer, claims, &db_conn).await?;
+
+    Ok(session_response(
+        cookie_config.as_ref(),
+        start_session(&user, LoginMethod::Oidc, &client_info, &db_conn).await?,
+    ))
+}
+
+/// Looks up the pending login request of the state and deletes it, so that each state can only
+/// be used once.
+async fn take_auth_request(
+    state: &str,
+    db_conn: &DatabaseConnection,
+) -> Result<entities::oidc_auth_requests::Model, AuthError> {
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        let auth_request = entities::oidc_auth_requests::Entity::find_by_id(hash_secret(state))
+            .lock_exclusive()
+            .one(&*tx)
+            .await?
+            .ok_or(AuthError::InvalidCredential)?;
+        entities::oidc_auth_requests::Entity::delete_by_id(&auth_request.state_hash)
+            .exec(&*tx)
+            .await?;
+
+        if auth_request.created_at < Utc::now() - auth_request_lifetime() {
+            return Err(AuthError::InvalidCredential);
+        }
+        Ok(auth_request)
+    })
+    .await
+}
+
+/// Finds the user linked to the subject at the issuer, creating one if this is their first login.
+async fn find_or_create_user(
+    issuer: &str,
+    claims: IdTokenClaims,
+    db_conn: &DatabaseConnection,
+) -> Result<entities::users::Model, AuthError> {
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::OidcIssuer.eq(issuer))
+        .filter(entities::users::Column::OidcSubject.eq(&claims.sub))
+        .one(db_conn)
+        .await?;
+    if let Some(user) = user {
+        return Ok(user);
+    }
+
+    let mut username = base_username(&claims);
+    let username_taken = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(&username))
+        .count(db_conn)
+        .await?
+        > 0;
+    if username_taken {
+        // Never link to an existing user by name, since anyone can pick any name at the provider.
+        let prefix: String = username.chars().take(MAX_USERNAME_LENGTH - 7).collect();
+        username = format!("{prefix}-{}", &generate_secret()[..6]);
+    }
+
+    let user = entities::users::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        username: Set(username),
+        oidc_issuer: Set(Some(issuer.to_owned())),
+        oidc_subject: Set(Some(claims.sub)),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+    info!(username = user.username, "New user registered with OIDC");
+
+    Ok(user)
+}
+
+fn base_username(claims: &IdTokenClaims) -> String {
+    claims
+        .preferred_username
+        .as_deref()
+        .filter(|username| !username.is_empty())
+        .unwrap_or(&claims.sub)
+        .chars()
+        .take(MAX_USERNAME_LENGTH)
+        .collect()
+}
+
+fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn pkce_challenge_is_base64url_sha256_of_verifier() {
+        expect_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mJ92K9wftEMv6eDlSHg1ChJDNQk6oPo"),
+            "uEBGbmXWUPfXBDpSZkXv9IH06-NB4tbtn_pFZr6iacM"
+        );
+    }
+
+    #[googletest::test]
+    fn base_username_falls_back_to_subject() {
+        let claims = IdTokenClaims {
+            sub: "248289761001".to_owned(),
+            nonce: None,
+            preferred_username: Some(String::new()),
+        };
+
+        expect_eq!(base_username(&claims), "248289761001");
+    }
+
+    #[googletest::test]
+    fn base_username_is_truncated() {
+        let claims = IdTokenClaims {
+            sub: "248289761001".to_owned(),
+            nonce: None,
+            preferred_username: Some("a".repeat(80)),
+        };
+
+        expect_eq!(base_username(&claims).len(), MAX_USERNAME_LENGTH);
+    }
+}
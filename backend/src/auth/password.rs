@@ -0,0 +1,78 @@
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+
+/// Hashes `password` with Argon2id and a random salt, returning the PHC string to be stored in
+/// `users.password_hash`.
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| anyhow!("failed to hash password: {err}"))?
+        .to_string())
+}
+
+/// Checks `password` against the stored PHC string.
+///
+/// When the user does not exist or has no password, `password_hash` is `None` and the password is
+/// checked against a dummy hash instead, so that the response time does not reveal whether the
+/// username exists. The comparison itself is done in constant time by `argon2`.
+pub(crate) fn verify_password(password: &str, password_hash: Option<&str>) -> bool {
+    let (password_hash, is_dummy) = match password_hash {
+        Some(hash) => (hash, false),
+        None => (dummy_hash(), true),
+    };
+    let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+    let verified = Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok();
+
+    verified && !is_dummy
+}
+
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        hash_password("dummy password").expect("hashing the dummy password should never fail")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn when_password_correct_verify_returns_true() {
+        let hash = hash_password("hunter2").unwrap();
+
+        expect_true!(verify_password("hunter2", Some(&hash)));
+    }
+
+    #[googletest::test]
+    fn when_password_incorrect_verify_returns_false() {
+        let hash = hash_password("hunter2").unwrap();
+
+        expect_false!(verify_password("hunter3", Some(&hash)));
+    }
+
+    #[googletest::test]
+    fn when_hash_missing_verify_returns_false() {
+        expect_false!(verify_password("dummy password", None));
+    }
+
+    #[googletest::test]
+    fn hashes_are_salted() {
+        expect_ne!(
+            hash_password("hunter2").unwrap(),
+            hash_password("hunter2").unwrap()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use axum::{response::Response, Extension, Json};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    Set, SqlErr, TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{
+    audit::{ClientInfo, LoginMethod},
+    cookie::{session_response, CookieConfig},
+    hash_password,
+    secret::{generate_secret, hash_secret},
+    session::start_session,
+    AuthError,
+};
+use crate::{db::DatabaseTransactionExt, entities};
+
+pub(super) const MAX_USERNAME_LENGTH: usize = 50;
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RegisterRequest {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    pub(crate) invite_code: String,
+}
+
+pub(crate) async fn register_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    Json(payload): Json<RegisterRequest>,
+) -> Result<Response, AuthError> {
+    validate_credentials(&payload.username, &payload.password)?;
+    let password_hash = hash_password(&payload.password)?;
+    let code_hash = hash_secret(&payload.invite_code);
+
+    let tx = db_conn.begin().await?;
+    let response = tx
+        .with(|tx| async move {
+            let user = entities::users::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                username: Set(payload.username),
+                password_hash: Set(Some(password_hash)),
+                ..Default::default()
+            }
+            .insert(&*tx)
+            .await
+            .map_err(|err| match err.sql_err() {
+                Some(SqlErr::UniqueConstraintViolation(_)) => AuthError::UsernameTaken,
+                _ => err.into(),
+            })?;
+
+            // Claiming the code with a conditional update makes concurrent registrations with the
+            // same code race on the row lock, so only one of them can succeed.
+            let claimed = entities::invite_codes::Entity::update_many()
+                .col_expr(
+                    entities::invite_codes::Column::UsedBy,
+                    Expr::value(Some(user.id)),
+                )
+                .col_expr(
+                    entities::invite_codes::Column::UsedAt,
+                    Expr::current_timestamp().into(),
+                )
+                .filter(entities::invite_codes::Column::CodeHash.eq(code_hash))
+                .filter(entities::invite_codes::Column::UsedAt.is_null())
+                .exec(&*tx)
+                .await?;
+            if claimed.rows_affected != 1 {
+                return Err(AuthError::InvalidInviteCode);
+            }
+
+            info!(username = user.username, "New user registered");
+
+            start_session(&user, LoginMethod::Registration, &client, &*tx).await
+        })
+        .await?;
+
+    Ok(session_response(cookie_config.as_ref(), response))
+}
+
+fn validate_credentials(username: &str, password: &str) -> Result<(), AuthError> {
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err(AuthError::invalid_input(
+            "username",
+            format!("username must be between 1 and {MAX_USERNAME_LENGTH} characters"),
+        ));
+    }
+    validate_password(password)
+}
+
+pub(super) fn validate_password(password: &str) -> Result<(), AuthError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::invalid_input(
+            "password",
+            format!("password must be at least {MIN_PASSWORD_LENGTH} characters"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Mints a new single-use invite code and returns it. Only the hash of the code is stored, so the
+/// returned value is the only copy of it.
+pub async fn create_invite_code(db_conn: &DatabaseConnection) -> anyhow::Result<String> {
+    let code = generate_secret();
+    entities::invite_codes::ActiveModel {
+        code_hash: Set(hash_secret(&code)),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+    info!("Invite code created");
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn when_username_empty_validate_returns_error() {
+        expect_that!(
+            validate_credentials("", "long enough password"),
+            err(pat!(AuthError::InvalidInput {
+                field: eq(&"username"),
+                reason: anything(),
+            }))
+        );
+    }
+
+    #[googletest::test]
+    fn when_password_too_short_validate_returns_error() {
+        expect_that!(
+            validate_credentials("meteor", "short"),
+            err(pat!(AuthError::InvalidInput {
+                field: eq(&"password"),
+                reason: anything(),
+            }))
+        );
+    }
+
+    #[googletest::test]
+    fn when_credentials_valid_validate_returns_ok() {
+        expect_that!(
+            validate_credentials("meteor", "long enough password"),
+            ok(anything())
+        );
+    }
+}
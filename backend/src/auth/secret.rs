@@ -0,0 +1,38 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generates a random URL-safe secret with 256 bits of entropy, e.g. for invite codes.
+pub(crate) fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hashes a secret generated by [`generate_secret`] for storage.
+///
+/// The secrets have enough entropy that a plain SHA-256 is sufficient, and being deterministic it
+/// allows looking the secret up by its hash.
+pub(crate) fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn generated_secrets_are_unique() {
+        expect_ne!(generate_secret(), generate_secret());
+    }
+
+    #[googletest::test]
+    fn hash_secret_is_deterministic() {
+        let secret = generate_secret();
+
+        expect_eq!(hash_secret(&secret), hash_secret(&secret));
+        expect_ne!(hash_secret(&secret), secret);
+    }
+}
@@ -0,0 +1,273 @@
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{TimeDelta, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    EntityTrait, IntoActiveModel, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{
+    audit::{record_security_event, ClientInfo, LoginMethod, SecurityEventKind},
+    cookie::{self, CookieConfig},
+    keys::JwtKeys,
+    secret::{generate_secret, hash_secret},
+    AuthError, Claims, LoginResponse, TokenError,
+};
+use crate::{db::DatabaseTransactionExt, entities};
+
+/// Access tokens are short-lived to limit how long a leaked one stays useful; the refresh token is
+/// what keeps a session alive.
+fn access_token_lifetime() -> TimeDelta {
+    TimeDelta::minutes(15)
+}
+
+/// A session expires when it is not refreshed for this long.
+pub(super) fn refresh_token_lifetime() -> TimeDelta {
+    TimeDelta::days(30)
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RefreshRequest {
+    /// Taken from the cookie instead if not given, for sessions in cookies.
+    #[serde(default)]
+    pub(crate) refresh_token: Option<String>,
+}
+
+impl RefreshRequest {
+    fn refresh_token(self, headers: &HeaderMap) -> Result<String, AuthError> {
+        self.refresh_token
+            .or_else(|| cookie::refresh_token_from_cookie(headers))
+            .ok_or(AuthError::InvalidCredential)
+    }
+}
+
+/// Starts a new session for the user and returns the initial token pair. The login is recorded in
+/// the audit log.
+pub(crate) async fn start_session(
+    user: &entities::users::Model,
+    method: LoginMethod,
+    client: &ClientInfo,
+    db_conn: &impl ConnectionTrait,
+) -> Result<LoginResponse, AuthError> {
+    let refresh_token = generate_secret();
+    let session = entities::sessions::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        user_id: Set(user.id),
+        refresh_token_hash: Set(hash_secret(&refresh_token)),
+        user_agent: Set(client.user_agent.clone()),
+        expires_at: Set((Utc::now() + refresh_token_lifetime()).into()),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+    record_security_event(
+        user.id,
+        SecurityEventKind::LoginSucceeded,
+        Some(method.to_string()),
+        client,
+        db_conn,
+    )
+    .await?;
+
+    Ok(LoginResponse {
+        token: issue_access_token(user.username.clone(), session.id)?,
+        refresh_token,
+    })
+}
+
+/// Exchanges a refresh token for a new token pair. The refresh token is rotated, so each one can
+/// only be used once.
+pub(crate) async fn refresh_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Response, AuthError> {
+    let refresh_token = payload.refresh_token(&headers)?;
+    let tx = db_conn.begin().await?;
+    let response = tx
+        .with(|tx| async move {
+            // Locking the row makes concurrent refreshes with the same token wait for each other,
+            // so only the first one sees the old hash.
+            let Some(session) = entities::sessions::Entity::find()
+                .filter(
+                    entities::sessions::Column::RefreshTokenHash.eq(hash_secret(&refresh_token)),
+                )
+                .filter(active_session_condition())
+                .lock_exclusive()
+                .one(&*tx)
+                .await?
+            else {
+                return Err(AuthError::InvalidCredential);
+            };
+            let user = entities::users::Entity::find_by_id(session.user_id)
+                .one(&*tx)
+                .await?
+                .ok_or(AuthError::InvalidCredential)?;
+
+            let refresh_token = generate_secret();
+            let now = Utc::now();
+            let mut session = session.into_active_model();
+            session.refresh_token_hash = Set(hash_secret(&refresh_token));
+            session.last_refreshed_at = Set(now.into());
+            session.expires_at = Set((now + refresh_token_lifetime()).into());
+            let session = session.update(&*tx).await?;
+            record_security_event(
+                user.id,
+                SecurityEventKind::SessionRefreshed,
+                None,
+                &client,
+                &*tx,
+            )
+            .await?;
+
+            Ok(LoginResponse {
+                token: issue_access_token(user.username, session.id)?,
+                refresh_token,
+            })
+        })
+        .await?;
+
+    Ok(cookie::session_response(cookie_config.as_ref(), response))
+}
+
+/// Revokes the session of the refresh token. Logging out of a session that is already revoked or
+/// does not exist is not an error.
+pub(crate) async fn logout_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Response, AuthError> {
+    let refresh_token = payload.refresh_token(&headers)?;
+    let revoked = entities::sessions::Entity::update_many()
+        .col_expr(
+            entities::sessions::Column::RevokedAt,
+            Expr::current_timestamp().into(),
+        )
+        .filter(entities::sessions::Column::RefreshTokenHash.eq(hash_secret(&refresh_token)))
+        .filter(entities::sessions::Column::RevokedAt.is_null())
+        .exec_with_returning(&db_conn)
+        .await?;
+    for session in revoked {
+        record_security_event(
+            session.user_id,
+            SecurityEventKind::SessionRevoked,
+            Some("logout".to_owned()),
+            &client,
+            &db_conn,
+        )
+        .await?;
+    }
+
+    match cookie_config {
+        Some(_) => Ok((
+            cookie::clear_session_cookies(&headers),
+            StatusCode::NO_CONTENT,
+        )
+            .into_response()),
+        None => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+pub(crate) fn issue_access_token(username: String, session_id: Uuid) -> Result<String, AuthError> {
+    let claim = Claims {
+        sub: username,
+        sid: session_id,
+        exp: (Utc::now() + access_token_lifetime()).timestamp() as u64,
+    };
+
+    JwtKeys::get()
+        .encode(&claim)
+        .map_err(|_| AuthError::TokenGenerationError)
+}
+
+pub(crate) fn decode_access_token(token: &str) -> Result<Claims, TokenError> {
+    use jsonwebtoken::errors::ErrorKind;
+
+    JwtKeys::get()
+        .decode::<Claims>(token)
+        .map(|data| data.claims)
+        .map_err(|err| match err.kind() {
+            ErrorKind::ExpiredSignature => TokenError::Expired,
+            ErrorKind::InvalidToken
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_)
+            | ErrorKind::MissingRequiredClaim(_) => TokenError::Malformed,
+            _ => TokenError::Invalid,
+        })
+}
+
+pub(crate) async fn find_active_session(
+    session_id: Uuid,
+    db_conn: &impl ConnectionTrait,
+) -> Result<Option<entities::sessions::Model>, AuthError> {
+    Ok(entities::sessions::Entity::find_by_id(session_id)
+        .filter(active_session_condition())
+        .one(db_conn)
+        .await?)
+}
+
+pub(crate) fn active_session_condition() -> Condition {
+    Condition::all()
+        .add(entities::sessions::Column::RevokedAt.is_null())
+        .add(entities::sessions::Column::ExpiresAt.gt(Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn issued_access_token_decodes() {
+        let session_id = Uuid::new_v4();
+        let token = issue_access_token("meteor".to_owned(), session_id).unwrap();
+
+        expect_that!(
+            decode_access_token(&token),
+            ok(pat!(Claims {
+                sub: eq("meteor"),
+                sid: eq(&session_id),
+            }))
+        );
+    }
+
+    #[googletest::test]
+    fn expired_access_token_is_rejected() {
+        let claim = Claims {
+            sub: "meteor".to_owned(),
+            sid: Uuid::new_v4(),
+            exp: (Utc::now() - TimeDelta::hours(1)).timestamp() as u64,
+        };
+        let token = JwtKeys::get().encode(&claim).unwrap();
+
+        expect_that!(decode_access_token(&token), err(eq(&TokenError::Expired)));
+    }
+
+    #[googletest::test]
+    fn malformed_access_token_is_rejected() {
+        expect_that!(
+            decode_access_token("not-a-jwt"),
+            err(eq(&TokenError::Malformed))
+        );
+    }
+
+    #[googletest::test]
+    fn access_token_with_wrong_signature_is_rejected() {
+        let token = issue_access_token("meteor".to_owned(), Uuid::new_v4()).unwrap();
+        let (payload, _) = token.rsplit_once('.').unwrap();
+        let forged = format!("{payload}.{}", generate_secret());
+
+        expect_that!(decode_access_token(&forged), err(eq(&TokenError::Invalid)));
+    }
+}
@@ -0,0 +1,549 @@
+//! Time-based one-time passwords (RFC 6238) as the optional second factor of password logins.
+
+use std::{fmt, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, Payload},
+    AeadCore, Aes256Gcm, KeyInit, Nonce,
+};
+use anyhow::{anyhow, Context};
+use axum::{response::Response, Extension, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, TimeDelta, Utc};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    audit::{record_security_event, ClientInfo, LoginMethod, SecurityEventKind},
+    cookie::{session_response, CookieConfig},
+    secret::{generate_secret, hash_secret},
+    session::start_session,
+    AuthError,
+};
+use crate::{db::DatabaseTransactionExt, entities, rate_limit::RateLimiter};
+
+const ISSUER: &str = "Planner";
+/// 160 bits, the length of an HMAC-SHA1 key recommended by RFC 4226.
+const SECRET_LENGTH: usize = 20;
+const DIGITS: u32 = 6;
+const STEP_SECONDS: i64 = 30;
+/// Codes of the steps next to the current one are also accepted, to allow for clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const NONCE_LENGTH: usize = 12;
+
+/// How long the client has to answer the challenge after the password is accepted.
+fn challenge_lifetime() -> TimeDelta {
+    TimeDelta::minutes(5)
+}
+
+/// The AES-256-GCM key that TOTP secrets are encrypted with in the database.
+#[derive(Clone)]
+pub struct TotpEncryptionKey(Arc<Aes256Gcm>);
+
+impl TotpEncryptionKey {
+    /// Parses a base64 encoded 256-bit key, e.g. one generated by `openssl rand -base64 32`.
+    pub fn from_base64(key: &str) -> anyhow::Result<Self> {
+        let key = STANDARD
+            .decode(key.trim())
+            .context("The TOTP encryption key is not valid base64")?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow!("The TOTP encryption key must be 32 bytes long"))?;
+        Ok(Self(Arc::new(cipher)))
+    }
+
+    /// Generates a random key, which is only useful for tests since the secrets encrypted with it
+    /// are lost on restart.
+    pub fn generate() -> Self {
+        Self(Arc::new(Aes256Gcm::new(&Aes256Gcm::generate_key(
+            rand::rngs::OsRng,
+        ))))
+    }
+
+    /// Reads the key from `$TOTP_ENCRYPTION_KEY`. Two-factor authentication cannot be enabled when
+    /// it is not set.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("TOTP_ENCRYPTION_KEY") {
+            Ok(key) if !key.is_empty() => Ok(Some(Self::from_base64(&key)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Encrypts the secret of the user. The user id is authenticated along with it, so that a
+    /// ciphertext copied to another user's row does not decrypt.
+    pub(crate) fn encrypt(&self, user_id: Uuid, secret: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(rand::rngs::OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: secret,
+                    aad: user_id.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt the TOTP secret"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub(crate) fn decrypt(&self, user_id: Uuid, ciphertext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(
+            ciphertext.len() > NONCE_LENGTH,
+            "the encrypted TOTP secret is truncated"
+        );
+        let (nonce, ciphertext) = ciphertext.split_at(NONCE_LENGTH);
+        self.0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: user_id.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("failed to decrypt the TOTP secret of user {user_id}"))
+    }
+}
+
+impl fmt::Debug for TotpEncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TotpEncryptionKey(..)")
+    }
+}
+
+/// Returns the key, or an error if two-factor authentication is not configured.
+pub(crate) fn require_key(key: Option<&TotpEncryptionKey>) -> anyhow::Result<&TotpEncryptionKey> {
+    key.context("Two-factor authentication needs $TOTP_ENCRYPTION_KEY to be set")
+}
+
+pub(crate) fn generate_totp_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LENGTH];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// The secret in the base32 form that authenticator apps accept for manual entry.
+pub(crate) fn encode_totp_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// The `otpauth://` URI to be shown as a QR code to enroll an authenticator app.
+pub(crate) fn provisioning_uri(secret: &[u8], username: &str) -> String {
+    let mut uri = url::Url::parse("otpauth://totp/").expect("the base URI is valid");
+    uri.set_path(&format!("{ISSUER}:{username}"));
+    uri.query_pairs_mut()
+        .append_pair("secret", &encode_totp_secret(secret))
+        .append_pair("issuer", ISSUER)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP_SECONDS.to_string());
+    uri.into()
+}
+
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac =
+        <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    binary % 10u32.pow(DIGITS)
+}
+
+fn time_step(time: DateTime<Utc>) -> i64 {
+    time.timestamp().div_euclid(STEP_SECONDS)
+}
+
+/// Checks the code against the secret and returns the time step it belongs to. Codes of steps up
+/// to `last_used_step` are rejected, so that an observed code cannot be replayed.
+pub(crate) fn verify_totp(
+    secret: &[u8],
+    code: &str,
+    now: DateTime<Utc>,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+
+    let current_step = time_step(now);
+    (current_step - ALLOWED_DRIFT_STEPS..=current_step + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| hotp(secret, *step as u64) == code)
+}
+
+/// Generates single-use recovery codes in the form `xxxx-xxxx-xxxx-xxxx`. With 80 bits of entropy
+/// each, they are stored with [`hash_recovery_code`] like the other generated secrets.
+pub(crate) fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let code = BASE32_NOPAD.encode(&bytes).to_ascii_lowercase();
+            code.as_bytes()
+                .chunks(4)
+                .map(|chunk| std::str::from_utf8(chunk).unwrap())
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+/// Hashes a recovery code, ignoring the case and the dashes that users may type differently.
+pub(crate) fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_secret(&normalized)
+}
+
+/// Replaces the recovery codes of the user with new ones and returns them.
+pub(crate) async fn replace_recovery_codes(
+    user_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> Result<Vec<String>, sea_orm::DbErr> {
+    entities::totp_recovery_codes::Entity::delete_many()
+        .filter(entities::totp_recovery_codes::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+    let codes = generate_recovery_codes();
+    entities::totp_recovery_codes::Entity::insert_many(codes.iter().map(|code| {
+        entities::totp_recovery_codes::ActiveModel {
+            user_id: Set(user_id),
+            code_hash: Set(hash_recovery_code(code)),
+            used_at: Set(None),
+        }
+    }))
+    .exec(db)
+    .await?;
+    Ok(codes)
+}
+
+/// The kinds of codes accepted by [`check_code`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AcceptedCodes {
+    TotpOnly,
+    TotpOrRecovery,
+}
+
+/// Checks a code from the authenticator app, or an unused recovery code if accepted, and marks it
+/// as used.
+pub(crate) async fn check_code(
+    key: &TotpEncryptionKey,
+    totp: &entities::user_totp::Model,
+    code: &str,
+    accepted: AcceptedCodes,
+    db: &impl ConnectionTrait,
+) -> anyhow::Result<bool> {
+    let secret = key.decrypt(totp.user_id, &totp.secret_ciphertext)?;
+    if let Some(step) = verify_totp(&secret, code, Utc::now(), totp.last_used_step) {
+        // The condition on the previous step makes a concurrent use of the same code fail.
+        let updated = entities::user_totp::Entity::update_many()
+            .col_expr(entities::user_totp::Column::LastUsedStep, Expr::value(step))
+            .filter(entities::user_totp::Column::UserId.eq(totp.user_id))
+            .filter(
+                Condition::any()
+                    .add(entities::user_totp::Column::LastUsedStep.is_null())
+                    .add(entities::user_totp::Column::LastUsedStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+        return Ok(updated.rows_affected == 1);
+    }
+    if accepted == AcceptedCodes::TotpOnly {
+        return Ok(false);
+    }
+
+    let used = entities::totp_recovery_codes::Entity::update_many()
+        .col_expr(
+            entities::totp_recovery_codes::Column::UsedAt,
+            Expr::current_timestamp().into(),
+        )
+        .filter(entities::totp_recovery_codes::Column::UserId.eq(totp.user_id))
+        .filter(entities::totp_recovery_codes::Column::CodeHash.eq(hash_recovery_code(code)))
+        .filter(entities::totp_recovery_codes::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+    if used.rows_affected == 1 {
+        info!(user_id = ?totp.user_id, "Recovery code used");
+    }
+    Ok(used.rows_affected == 1)
+}
+
+/// Returns the TOTP enrollment of the user if it has been confirmed.
+pub(crate) async fn find_enabled_totp(
+    user_id: Uuid,
+    db: &impl ConnectionTrait,
+) -> Result<Option<entities::user_totp::Model>, sea_orm::DbErr> {
+    entities::user_totp::Entity::find_by_id(user_id)
+        .filter(entities::user_totp::Column::ConfirmedAt.is_not_null())
+        .one(db)
+        .await
+}
+
+/// The response of `/auth/login` when the user has two-factor authentication enabled.
+#[derive(Debug, Serialize)]
+pub(crate) struct TotpChallenge {
+    /// To be sent to `/auth/login/totp` along with the code.
+    pub(crate) totp_challenge: String,
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+/// Deletes the challenges that were never answered and have expired. Returns the number of deleted
+/// challenges.
+pub async fn delete_expired_login_challenges(db_conn: &DatabaseConnection) -> Result<u64, DbErr> {
+    let result = entities::login_challenges::Entity::delete_many()
+        .filter(entities::login_challenges::Column::ExpiresAt.lt(Utc::now()))
+        .exec(db_conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Starts the second step of a login whose password has been accepted.
+pub(super) async fn start_challenge(
+    user: &entities::users::Model,
+    user_agent: Option<String>,
+    db_conn: &DatabaseConnection,
+) -> Result<TotpChallenge, AuthError> {
+    let challenge = generate_secret();
+    let expires_at = Utc::now() + challenge_lifetime();
+    entities::login_challenges::ActiveModel {
+        token_hash: Set(hash_secret(&challenge)),
+        user_id: Set(user.id),
+        user_agent: Set(user_agent),
+        expires_at: Set(expires_at.into()),
+    }
+    .insert(db_conn)
+    .await?;
+
+    Ok(TotpChallenge {
+        totp_challenge: challenge,
+        expires_at,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TotpLoginRequest {
+    pub(crate) totp_challenge: String,
+    /// A code from the authenticator app, or a recovery code.
+    pub(crate) code: String,
+}
+
+/// Completes a login with the code for the challenge returned by `/auth/login`. Wrong codes count
+/// towards the same lockout as wrong passwords.
+pub(crate) async fn totp_login_handler(
+    Extension(db_conn): Extension<DatabaseConnection>,
+    Extension(rate_limiter): Extension<RateLimiter>,
+    Extension(totp_key): Extension<Option<TotpEncryptionKey>>,
+    Extension(cookie_config): Extension<Option<CookieConfig>>,
+    client: ClientInfo,
+    Json(payload): Json<TotpLoginRequest>,
+) -> Result<Response, AuthError> {
+    let key = require_key(totp_key.as_ref())?;
+    // The failure is recorded outside of the transaction, which is rolled back.
+    let audit_db_conn = db_conn.clone();
+
+    let tx = db_conn.begin().await?;
+    let response = tx
+        .with(|tx| async move {
+            let challenge = entities::login_challenges::Entity::find_by_id(hash_secret(
+                &payload.totp_challenge,
+            ))
+            .filter(entities::login_challenges::Column::ExpiresAt.gt(Utc::now()))
+            .lock_exclusive()
+            .one(&*tx)
+            .await?
+            .ok_or(AuthError::InvalidCredential)?;
+            let user = entities::users::Entity::find_by_id(challenge.user_id)
+                .one(&*tx)
+                .await?
+                .ok_or(AuthError::InvalidCredential)?;
+            rate_limiter
+                .check_login_lockout(&user.username, client.ip_address.as_deref())
+                .await?;
+            let totp = find_enabled_totp(user.id, &*tx)
+                .await?
+                .ok_or(AuthError::InvalidCredential)?;
+
+            if !check_code(
+                key,
+                &totp,
+                &payload.code,
+                AcceptedCodes::TotpOrRecovery,
+                &*tx,
+            )
+            .await?
+            {
+                warn!(username = user.username, "Failed two-factor login attempt");
+                rate_limiter
+                    .record_login_failure(&user.username, client.ip_address.as_deref())
+                    .await?;
+                record_security_event(
+                    user.id,
+                    SecurityEventKind::LoginFailed,
+                    Some(LoginMethod::Totp.to_string()),
+                    &client,
+                    &audit_db_conn,
+                )
+                .await?;
+                return Err(AuthError::InvalidCredential);
+            }
+
+            entities::login_challenges::Entity::delete_by_id(challenge.token_hash)
+                .exec(&*tx)
+                .await?;
+            rate_limiter
+                .clear_login_failures(&user.username, client.ip_address.as_deref())
+                .await?;
+            let client = ClientInfo {
+                user_agent: client.user_agent.or(challenge.user_agent),
+                ..client
+            };
+            start_session(&user, LoginMethod::Totp, &client, &*tx).await
+        })
+        .await?;
+
+    Ok(session_response(cookie_config.as_ref(), response))
+}
+
+/// Turns off two-factor authentication for the user with `username`, e.g. when they have lost both
+/// their authenticator and their recovery codes.
+pub async fn reset_two_factor(username: &str, db_conn: &DatabaseConnection) -> anyhow::Result<()> {
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::Username.eq(username))
+        .one(db_conn)
+        .await?
+        .with_context(|| format!("user `{username}` does not exist"))?;
+
+    let tx = db_conn.begin().await?;
+    tx.with(|tx| async move {
+        entities::user_totp::Entity::delete_by_id(user.id)
+            .exec(&*tx)
+            .await?;
+        record_security_event(
+            user.id,
+            SecurityEventKind::TwoFactorDisabled,
+            Some("admin".to_owned()),
+            &ClientInfo::default(),
+            &*tx,
+        )
+        .await?;
+        entities::totp_recovery_codes::Entity::delete_many()
+            .filter(entities::totp_recovery_codes::Column::UserId.eq(user.id))
+            .exec(&*tx)
+            .await?;
+        entities::login_challenges::Entity::delete_many()
+            .filter(entities::login_challenges::Column::UserId.eq(user.id))
+            .exec(&*tx)
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    })
+    .await?;
+    info!(username, "Two-factor authentication reset");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use googletest::prelude::*;
+
+    use super::*;
+
+    /// The SHA-1 secret of the test vectors in RFC 6238, appendix B.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    #[googletest::test]
+    fn hotp_matches_rfc_6238_test_vectors() {
+        // The RFC lists 8-digit codes; the 6-digit codes are their last 6 digits.
+        expect_that!(hotp(RFC_SECRET, time_step(at(59)) as u64), eq(287082));
+        expect_that!(
+            hotp(RFC_SECRET, time_step(at(1111111109)) as u64),
+            eq(81804)
+        );
+        expect_that!(hotp(RFC_SECRET, time_step(at(1234567890)) as u64), eq(5924));
+    }
+
+    #[googletest::test]
+    fn verify_totp_accepts_adjacent_steps() {
+        let now = at(1111111109);
+
+        expect_that!(
+            verify_totp(RFC_SECRET, "081804", now, None),
+            some(anything())
+        );
+        let previous = format!("{:06}", hotp(RFC_SECRET, (time_step(now) - 1) as u64));
+        expect_that!(
+            verify_totp(RFC_SECRET, &previous, now, None),
+            some(anything())
+        );
+        let too_old = format!("{:06}", hotp(RFC_SECRET, (time_step(now) - 2) as u64));
+        expect_that!(verify_totp(RFC_SECRET, &too_old, now, None), none());
+    }
+
+    #[googletest::test]
+    fn verify_totp_rejects_used_steps() {
+        let now = at(1111111109);
+        let step = verify_totp(RFC_SECRET, "081804", now, None).unwrap();
+
+        expect_that!(verify_totp(RFC_SECRET, "081804", now, Some(step)), none());
+    }
+
+    #[googletest::test]
+    fn verify_totp_rejects_malformed_codes() {
+        let now = at(1111111109);
+
+        expect_that!(verify_totp(RFC_SECRET, "81804", now, None), none());
+        expect_that!(verify_totp(RFC_SECRET, "+81804", now, None), none());
+    }
+
+    #[googletest::test]
+    fn encrypted_secret_is_bound_to_the_user() {
+        let key = TotpEncryptionKey::generate();
+        let user_id = Uuid::new_v4();
+        let ciphertext = key.encrypt(user_id, RFC_SECRET).unwrap();
+
+        expect_that!(
+            key.decrypt(user_id, &ciphertext),
+            ok(eq(&RFC_SECRET.to_vec()))
+        );
+        expect_that!(key.decrypt(Uuid::new_v4(), &ciphertext), err(anything()));
+    }
+
+    #[googletest::test]
+    fn recovery_codes_hash_ignores_formatting() {
+        let code = &generate_recovery_codes()[0];
+
+        expect_that!(code.len(), eq(19));
+        expect_eq!(
+            hash_recovery_code(&code.to_ascii_uppercase().replace('-', "")),
+            hash_recovery_code(code)
+        );
+    }
+
+    #[googletest::test]
+    fn provisioning_uri_contains_the_secret() {
+        let uri = provisioning_uri(RFC_SECRET, "meteor");
+
+        expect_that!(
+            uri,
+            starts_with("otpauth://totp/Planner:meteor?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&")
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    auth::{CookieConfig, OidcConfig, TotpEncryptionKey},
+    graphql::GraphqlConfig,
+    mail::SmtpConfig,
+    rate_limit::RateLimitConfig,
+    utils::parse_bool_env,
+};
+
+/// The server configuration that is not tied to a single module.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// The OpenID Connect provider to log in with. OIDC login is disabled when it is not set.
+    pub oidc: Option<OidcConfig>,
+    pub rate_limit: RateLimitConfig,
+    /// The key to encrypt TOTP secrets with. Two-factor authentication cannot be enabled when it is
+    /// not set.
+    pub totp_encryption_key: Option<TotpEncryptionKey>,
+    /// The relay to send login links and password resets through. Both are disabled when it is not
+    /// set.
+    pub smtp: Option<SmtpConfig>,
+    /// Keeps the tokens of login sessions in cookies instead of handing them to the frontend.
+    /// Bearer tokens are still accepted either way.
+    pub session_cookies: Option<CookieConfig>,
+    pub graphql: GraphqlConfig,
+    /// Shows clients the details of internal errors instead of a generic message. They are always
+    /// logged, so this is only meant for development.
+    pub expose_internal_errors: bool,
+    /// Signs tokens with a random secret when no JWT key is configured, instead of failing at
+    /// startup. The tokens do not survive a restart, so this is only meant for development.
+    pub jwt_allow_ephemeral: bool,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self {
+            oidc: OidcConfig::from_env()?,
+            rate_limit: RateLimitConfig::from_env()?,
+            totp_encryption_key: TotpEncryptionKey::from_env()?,
+            smtp: SmtpConfig::from_env()?,
+            session_cookies: CookieConfig::from_env()?,
+            graphql: GraphqlConfig::from_env()?,
+            expose_internal_errors: parse_bool_env("EXPOSE_INTERNAL_ERRORS")?.unwrap_or(false),
+            jwt_allow_ephemeral: parse_bool_env("JWT_ALLOW_EPHEMERAL")?.unwrap_or(false),
+        })
+    }
+}
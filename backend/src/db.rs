@@ -50,9 +50,7 @@ pub(crate) impl DatabaseTransaction {
         let result = f(wrapper).await;
 
         match result {
-            Ok(t) => {
-                Ok((t, self))
-            }
+            Ok(t) => Ok((t, self)),
             Err(e) => {
                 self.rollback().await?;
                 Err(e)
@@ -2,5 +2,19 @@
 
 pub mod prelude;
 
+pub mod api_tokens;
+pub mod email_tokens;
+pub mod invite_codes;
+pub mod login_challenges;
+pub mod login_lockouts;
+pub mod oidc_auth_requests;
+pub mod rate_limit_counters;
+pub mod sea_orm_active_enums;
+pub mod security_events;
+pub mod sessions;
 pub mod task;
+pub mod totp_recovery_codes;
+pub mod user_totp;
 pub mod users;
+pub mod workspace_members;
+pub mod workspaces;
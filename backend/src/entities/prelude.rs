@@ -1,4 +1,17 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::api_tokens::Entity as ApiTokens;
+pub use super::email_tokens::Entity as EmailTokens;
+pub use super::invite_codes::Entity as InviteCodes;
+pub use super::login_challenges::Entity as LoginChallenges;
+pub use super::login_lockouts::Entity as LoginLockouts;
+pub use super::oidc_auth_requests::Entity as OidcAuthRequests;
+pub use super::rate_limit_counters::Entity as RateLimitCounters;
+pub use super::security_events::Entity as SecurityEvents;
+pub use super::sessions::Entity as Sessions;
 pub use super::task::Entity as Task;
+pub use super::totp_recovery_codes::Entity as TotpRecoveryCodes;
+pub use super::user_totp::Entity as UserTotp;
 pub use super::users::Entity as Users;
+pub use super::workspace_members::Entity as WorkspaceMembers;
+pub use super::workspaces::Entity as Workspaces;
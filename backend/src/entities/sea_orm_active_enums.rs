@@ -3,10 +3,63 @@
 use sea_orm::entity::prelude::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
-#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "task_status")]
-pub enum TaskStatus {
-    #[sea_orm(string_value = "active")]
-    Active,
-    #[sea_orm(string_value = "completed")]
-    Completed,
+#[sea_orm(
+    rs_type = "String",
+    db_type = "Enum",
+    enum_name = "email_token_purpose"
+)]
+pub enum EmailTokenPurpose {
+    #[sea_orm(string_value = "magic_link")]
+    MagicLink,
+    #[sea_orm(string_value = "password_reset")]
+    PasswordReset,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "epoch_kind")]
+pub enum EpochKind {
+    #[sea_orm(string_value = "date")]
+    Date,
+    #[sea_orm(string_value = "week")]
+    Week,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "Enum",
+    enum_name = "security_event_kind"
+)]
+pub enum SecurityEventKind {
+    #[sea_orm(string_value = "api_token_created")]
+    ApiTokenCreated,
+    #[sea_orm(string_value = "api_token_deleted")]
+    ApiTokenDeleted,
+    #[sea_orm(string_value = "login_failed")]
+    LoginFailed,
+    #[sea_orm(string_value = "login_succeeded")]
+    LoginSucceeded,
+    #[sea_orm(string_value = "password_changed")]
+    PasswordChanged,
+    #[sea_orm(string_value = "recovery_codes_regenerated")]
+    RecoveryCodesRegenerated,
+    #[sea_orm(string_value = "session_refreshed")]
+    SessionRefreshed,
+    #[sea_orm(string_value = "session_revoked")]
+    SessionRevoked,
+    #[sea_orm(string_value = "two_factor_disabled")]
+    TwoFactorDisabled,
+    #[sea_orm(string_value = "two_factor_enabled")]
+    TwoFactorEnabled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "workspace_role")]
+pub enum WorkspaceRole {
+    #[sea_orm(string_value = "editor")]
+    Editor,
+    #[sea_orm(string_value = "owner")]
+    Owner,
+    #[sea_orm(string_value = "viewer")]
+    Viewer,
 }
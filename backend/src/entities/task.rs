@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+use super::sea_orm_active_enums::EpochKind;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -9,13 +10,20 @@ pub struct Model {
     pub id: Uuid,
     pub user_id: Uuid,
     pub recurring_spec: Option<Json>,
-    pub scheduled_on: Option<Json>,
     pub schedule_index_date: Option<Date>,
     pub complete_date: Option<Date>,
     pub parent_id: Option<Uuid>,
     pub title: String,
     pub cost: Option<i32>,
     pub next_recurring_check_date: Option<Date>,
+    pub workspace_id: Uuid,
+    pub scheduled_kind: Option<EpochKind>,
+    pub scheduled_start: Option<Date>,
+    pub scheduled_end: Option<Date>,
+    pub recurring_template_id: Option<Uuid>,
+    pub created_at: DateTimeWithTimeZone,
+    pub complete_with_subtasks: bool,
+    pub completed_by_subtasks: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -27,7 +35,15 @@ pub enum Relation {
         on_update = "NoAction",
         on_delete = "SetNull"
     )]
-    SelfRef,
+    SelfRef2,
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::RecurringTemplateId",
+        to = "Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    SelfRef1,
     #[sea_orm(
         belongs_to = "super::users::Entity",
         from = "Column::UserId",
@@ -36,6 +52,14 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Users,
+    #[sea_orm(
+        belongs_to = "super::workspaces::Entity",
+        from = "Column::WorkspaceId",
+        to = "super::workspaces::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Workspaces,
 }
 
 impl Related<super::users::Entity> for Entity {
@@ -44,4 +68,10 @@ impl Related<super::users::Entity> for Entity {
     }
 }
 
+impl Related<super::workspaces::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Workspaces.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
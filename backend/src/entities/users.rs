@@ -9,12 +9,73 @@ pub struct Model {
     pub id: Uuid,
     #[sea_orm(unique)]
     pub username: String,
+    pub password_hash: Option<String>,
+    pub oidc_issuer: Option<String>,
+    pub oidc_subject: Option<String>,
+    #[sea_orm(unique)]
+    pub email: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_tokens::Entity")]
+    ApiTokens,
+    #[sea_orm(has_many = "super::email_tokens::Entity")]
+    EmailTokens,
+    #[sea_orm(has_many = "super::invite_codes::Entity")]
+    InviteCodes,
+    #[sea_orm(has_many = "super::login_challenges::Entity")]
+    LoginChallenges,
+    #[sea_orm(has_many = "super::security_events::Entity")]
+    SecurityEvents,
+    #[sea_orm(has_many = "super::sessions::Entity")]
+    Sessions,
     #[sea_orm(has_many = "super::task::Entity")]
     Task,
+    #[sea_orm(has_many = "super::totp_recovery_codes::Entity")]
+    TotpRecoveryCodes,
+    #[sea_orm(has_one = "super::user_totp::Entity")]
+    UserTotp,
+    #[sea_orm(has_many = "super::workspace_members::Entity")]
+    WorkspaceMembers,
+    #[sea_orm(has_one = "super::workspaces::Entity")]
+    Workspaces,
+}
+
+impl Related<super::api_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiTokens.def()
+    }
+}
+
+impl Related<super::email_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailTokens.def()
+    }
+}
+
+impl Related<super::invite_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::InviteCodes.def()
+    }
+}
+
+impl Related<super::login_challenges::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoginChallenges.def()
+    }
+}
+
+impl Related<super::security_events::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SecurityEvents.def()
+    }
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
 }
 
 impl Related<super::task::Entity> for Entity {
@@ -23,4 +84,28 @@ impl Related<super::task::Entity> for Entity {
     }
 }
 
+impl Related<super::totp_recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TotpRecoveryCodes.def()
+    }
+}
+
+impl Related<super::user_totp::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserTotp.def()
+    }
+}
+
+impl Related<super::workspace_members::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WorkspaceMembers.def()
+    }
+}
+
+impl Related<super::workspaces::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Workspaces.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,319 @@
+//! The errors as clients see them: a stable code to tell them apart, a message for people, and
+//! details that depend on the code. GraphQL reports them in the `extensions` of its errors and the
+//! other endpoints as the JSON body of the response, both with the id of the request so that it
+//! can be found in the logs.
+
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextSubscribe},
+    Response as GraphQLResponse,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::{stream::BoxStream, StreamExt};
+use serde::Serialize;
+use tracing::{error, info_span, Instrument};
+use uuid::Uuid;
+
+/// The header that carries the id of a request. One sent by the client or a proxy is kept.
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The message that replaces the details of internal errors, unless they are exposed.
+const INTERNAL_ERROR_MESSAGE: &str = "Internal error";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, strum::IntoStaticStr)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum ErrorCode {
+    /// The request is malformed, e.g. a GraphQL query that does not match the schema.
+    BadRequest,
+    /// A GraphQL query is nested too deep, too complex or has too many aliases.
+    QueryLimitExceeded,
+    PayloadTooLarge,
+    /// An input is not valid. `field` is the path of the input if it is known.
+    InvalidInput,
+    /// The resource does not exist or the user cannot see it. `resource` is its type.
+    NotFound,
+    /// The user is not allowed to do this to the resource, e.g. a viewer modifying a task.
+    PermissionDenied,
+    /// The credential does not grant access to this, e.g. an API token without the scope.
+    Forbidden,
+    /// No credential was given.
+    Unauthorized,
+    TokenExpired,
+    TokenMalformed,
+    TokenInvalid,
+    InvalidCredentials,
+    InvalidInviteCode,
+    UsernameTaken,
+    InvalidCsrfToken,
+    /// `retry_after` is the number of seconds to wait, as in the `Retry-After` header.
+    TooManyRequests,
+    /// A subscription may have missed task changes, so the tasks should be reloaded.
+    EventsMissed,
+    Internal,
+}
+
+impl ErrorCode {
+    pub(crate) fn as_str(self) -> &'static str {
+        self.into()
+    }
+
+    fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest | ErrorCode::QueryLimitExceeded | ErrorCode::InvalidInput => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::PermissionDenied
+            | ErrorCode::Forbidden
+            | ErrorCode::InvalidInviteCode
+            | ErrorCode::InvalidCsrfToken => StatusCode::FORBIDDEN,
+            ErrorCode::Unauthorized
+            | ErrorCode::TokenExpired
+            | ErrorCode::TokenMalformed
+            | ErrorCode::TokenInvalid
+            | ErrorCode::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ErrorCode::UsernameTaken | ErrorCode::EventsMissed => StatusCode::CONFLICT,
+            ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// An error as it is reported to clients. The errors of the modules are converted to this, so
+/// that they are reported the same way by all the endpoints. The fields are in snake case in JSON
+/// bodies, like the rest of the REST endpoints, and in camel case in GraphQL extensions.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct ApiError {
+    pub(crate) code: ErrorCode,
+    pub(crate) message: String,
+    /// The path of the input that is not valid, e.g. `["input", "title"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) field: Option<Vec<String>>,
+    /// The type of the resource that was not found, e.g. `Task`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) resource: Option<String>,
+    /// How many seconds to wait before retrying.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) retry_after: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) request_id: Option<String>,
+}
+
+impl ApiError {
+    pub(crate) fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            field: None,
+            resource: None,
+            retry_after: None,
+            request_id: None,
+        }
+    }
+
+    /// An internal error, which is logged with its details since clients may not see them.
+    pub(crate) fn internal(err: &anyhow::Error) -> Self {
+        error!("Internal error: {err:?}");
+        Self::new(ErrorCode::Internal, format!("Internal error: {err}"))
+    }
+
+    /// Sets the path of the input that is not valid, given as a dotted path, e.g. `input.title`.
+    pub(crate) fn with_field(mut self, field: &str) -> Self {
+        self.field = Some(field.split('.').map(str::to_owned).collect());
+        self
+    }
+
+    pub(crate) fn with_resource(mut self, resource: impl ToString) -> Self {
+        self.resource = Some(resource.to_string());
+        self
+    }
+
+    pub(crate) fn with_retry_after(mut self, seconds: i64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+
+    /// Converts the error to one of the GraphQL engine, for the errors of the extensions.
+    pub(crate) fn into_server_error(self) -> async_graphql::ServerError {
+        let mut error = async_graphql::ServerError::new(&self.message, None);
+        error.extensions = async_graphql::ErrorExtensions::extend(&self).extensions;
+        error
+    }
+
+    /// Fills in what is only known for the whole request.
+    fn reported(mut self, reporting: &ErrorReporting, request_id: &RequestId) -> Self {
+        if self.code == ErrorCode::Internal && !reporting.expose_internal_errors {
+            self.message = INTERNAL_ERROR_MESSAGE.to_owned();
+        }
+        self.request_id = Some(request_id.0.clone());
+        self
+    }
+}
+
+impl async_graphql::ErrorExtensions for ApiError {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(&self.message).extend_with(|_, e| {
+            e.set("code", self.code.as_str());
+            if let Some(field) = &self.field {
+                e.set("field", field.clone());
+            }
+            if let Some(resource) = &self.resource {
+                e.set("resource", resource.as_str());
+            }
+            if let Some(retry_after) = self.retry_after {
+                e.set("retryAfter", retry_after);
+            }
+        })
+    }
+}
+
+impl IntoResponse for ApiError {
+    /// Responds with the error as JSON. [`report_errors`] adds the request id and, if configured,
+    /// the details of internal errors.
+    fn into_response(self) -> Response {
+        let mut body = self.clone();
+        if body.code == ErrorCode::Internal {
+            body.message = INTERNAL_ERROR_MESSAGE.to_owned();
+        }
+        let mut response = body.json_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// How much of the errors is reported to clients.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ErrorReporting {
+    /// Whether clients see the details of internal errors, which should only be the case in
+    /// development.
+    pub(crate) expose_internal_errors: bool,
+}
+
+impl ErrorReporting {
+    /// Adds the request id to the errors of a GraphQL response, and hides the details of the
+    /// internal errors. Errors raised by the GraphQL engine itself, e.g. for a query that does not
+    /// match the schema, are given the `BAD_REQUEST` code.
+    pub(crate) fn report_graphql(
+        &self,
+        response: &mut GraphQLResponse,
+        request_id: Option<&RequestId>,
+    ) {
+        for error in &mut response.errors {
+            let extensions = error.extensions.get_or_insert_with(Default::default);
+            let is_internal = match extensions.get("code") {
+                Some(async_graphql::Value::String(code)) => code == ErrorCode::Internal.as_str(),
+                Some(_) => false,
+                None => {
+                    extensions.set("code", ErrorCode::BadRequest.as_str());
+                    false
+                }
+            };
+            if let Some(request_id) = request_id {
+                extensions.set("requestId", request_id.0.as_str());
+            }
+            if is_internal && !self.expose_internal_errors {
+                error.message = INTERNAL_ERROR_MESSAGE.to_owned();
+            }
+        }
+    }
+}
+
+impl ExtensionFactory for ErrorReporting {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+/// Reports the errors of GraphQL responses over WebSocket, whose request id is in the connection
+/// data. Responses over HTTP are reported by the handler, see [`ErrorReporting::report_graphql`].
+#[async_graphql::async_trait::async_trait]
+impl Extension for ErrorReporting {
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, GraphQLResponse>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, GraphQLResponse> {
+        let reporting = *self;
+        let request_id = ctx.data_opt::<RequestId>().cloned();
+        next.run(ctx, stream)
+            .map(move |mut response| {
+                reporting.report_graphql(&mut response, request_id.as_ref());
+                response
+            })
+            .boxed()
+    }
+}
+
+/// The id of a request, put into the request extensions by [`report_errors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RequestId(pub(crate) String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestId
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(|| RequestId(Uuid::new_v4().to_string())))
+    }
+}
+
+/// Gives each request an id, which is logged with everything done for the request and sent back
+/// in the `x-request-id` header, and reports the errors of the endpoints other than GraphQL.
+pub(crate) async fn report_errors(
+    State(reporting): State<ErrorReporting>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let request_id = RequestId(request_id);
+    request.extensions_mut().insert(request_id.clone());
+
+    let span = info_span!("request", request_id = %request_id.0);
+    let mut response = next.run(request).instrument(span).await;
+    if let Some(error) = response.extensions_mut().remove::<ApiError>() {
+        response = error.reported(&reporting, &request_id).json_response();
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+impl ApiError {
+    fn json_response(&self) -> Response {
+        let mut response = (self.code.status(), Json(self)).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
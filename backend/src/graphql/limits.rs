@@ -0,0 +1,243 @@
+//! Limits on the GraphQL requests that are executed, so that a single request cannot tie up the
+//! server, and the switches for what is only useful during development.
+
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextValidation},
+    parser::types::{ExecutableDocument, Field, Selection, SelectionSet},
+    ServerError, ServerResult, ValidationResult, Variables,
+};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body_util::LengthLimitError;
+
+use crate::{
+    error::{ApiError, ErrorCode},
+    utils::{parse_bool_env, parse_env},
+};
+
+#[derive(Clone, Debug)]
+pub struct GraphqlConfig {
+    /// The maximum nesting of the fields of a query.
+    pub max_depth: usize,
+    /// The maximum number of fields that a query may resolve, counting those of fragments as often
+    /// as they are spread.
+    pub max_complexity: usize,
+    /// The maximum number of aliased fields in a query, which could otherwise resolve the same
+    /// expensive field many times.
+    pub max_aliases: usize,
+    /// The maximum size of the body of a request in bytes.
+    pub max_body_bytes: usize,
+    /// Whether the schema can be queried, which the frontend codegen needs during development. Off
+    /// by default, so that production does not publish the schema.
+    pub introspection: bool,
+    /// Whether GraphiQL is served at `GET /graphql`. Off by default, like introspection.
+    pub graphiql: bool,
+}
+
+impl Default for GraphqlConfig {
+    fn default() -> Self {
+        Self {
+            // The introspection query of GraphiQL and codegen is nested 14 deep.
+            max_depth: 15,
+            max_complexity: 500,
+            max_aliases: 30,
+            max_body_bytes: 256 * 1024,
+            introspection: false,
+            graphiql: false,
+        }
+    }
+}
+
+impl GraphqlConfig {
+    /// Reads the config from `$GRAPHQL_MAX_DEPTH`, `$GRAPHQL_MAX_COMPLEXITY`,
+    /// `$GRAPHQL_MAX_ALIASES`, `$GRAPHQL_MAX_BODY_BYTES`, `$GRAPHQL_INTROSPECTION` and
+    /// `$GRAPHQL_GRAPHIQL`, using the defaults for the ones that are not set.
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        if let Some(max_depth) = parse_env("GRAPHQL_MAX_DEPTH")? {
+            config.max_depth = max_depth;
+        }
+        if let Some(max_complexity) = parse_env("GRAPHQL_MAX_COMPLEXITY")? {
+            config.max_complexity = max_complexity;
+        }
+        if let Some(max_aliases) = parse_env("GRAPHQL_MAX_ALIASES")? {
+            config.max_aliases = max_aliases;
+        }
+        if let Some(max_body_bytes) = parse_env("GRAPHQL_MAX_BODY_BYTES")? {
+            config.max_body_bytes = max_body_bytes;
+        }
+        if let Some(introspection) = parse_bool_env("GRAPHQL_INTROSPECTION")? {
+            config.introspection = introspection;
+        }
+        if let Some(graphiql) = parse_bool_env("GRAPHQL_GRAPHIQL")? {
+            config.graphiql = graphiql;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Rejects the queries over the limits of [`GraphqlConfig`] before they are executed. The limits
+/// on depth and complexity of the schema builder are not used, since their errors tell neither
+/// the limit nor how far it is exceeded. Likewise, introspection queries fail rather than
+/// resolve to null when introspection is disabled.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct QueryLimits {
+    max_depth: usize,
+    max_complexity: usize,
+    max_aliases: usize,
+    introspection: bool,
+}
+
+impl From<&GraphqlConfig> for QueryLimits {
+    fn from(config: &GraphqlConfig) -> Self {
+        Self {
+            max_depth: config.max_depth,
+            max_complexity: config.max_complexity,
+            max_aliases: config.max_aliases,
+            introspection: config.introspection,
+        }
+    }
+}
+
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for QueryLimits {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        if !self.introspection && is_introspection(&document) {
+            return Err(
+                ApiError::new(ErrorCode::BadRequest, "Introspection is disabled")
+                    .into_server_error(),
+            );
+        }
+        let aliases = count_aliases(&document);
+        if aliases > self.max_aliases {
+            return Err(limit_exceeded(format!(
+                "Query has {aliases} aliases, more than the limit of {}",
+                self.max_aliases
+            )));
+        }
+        Ok(document)
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+        if result.depth > self.max_depth {
+            return Err(vec![limit_exceeded(format!(
+                "Query is nested {} deep, more than the limit of {}",
+                result.depth, self.max_depth
+            ))]);
+        }
+        if result.complexity > self.max_complexity {
+            return Err(vec![limit_exceeded(format!(
+                "Query has a complexity of {}, more than the limit of {}",
+                result.complexity, self.max_complexity
+            ))]);
+        }
+        Ok(result)
+    }
+}
+
+fn limit_exceeded(message: String) -> ServerError {
+    ApiError::new(ErrorCode::QueryLimitExceeded, message).into_server_error()
+}
+
+/// Counts the aliases as written, i.e. those in a fragment once however often it is spread. The
+/// complexity limit covers fragments spread many times.
+fn count_aliases(document: &ExecutableDocument) -> usize {
+    let mut aliases = 0;
+    visit_fields(document, &mut |field| {
+        aliases += usize::from(field.alias.is_some());
+    });
+    aliases
+}
+
+/// Whether the document selects `__schema` or `__type`, which are only fields of the query root.
+fn is_introspection(document: &ExecutableDocument) -> bool {
+    let mut introspection = false;
+    visit_fields(document, &mut |field| {
+        introspection |= matches!(field.name.node.as_str(), "__schema" | "__type");
+    });
+    introspection
+}
+
+/// Calls `f` with each field of the operations and fragments of the document.
+fn visit_fields(document: &ExecutableDocument, f: &mut impl FnMut(&Field)) {
+    fn visit(selection_set: &SelectionSet, f: &mut impl FnMut(&Field)) {
+        for selection in &selection_set.items {
+            match &selection.node {
+                Selection::Field(field) => {
+                    f(&field.node);
+                    visit(&field.node.selection_set.node, f);
+                }
+                Selection::FragmentSpread(_) => {}
+                Selection::InlineFragment(fragment) => visit(&fragment.node.selection_set.node, f),
+            }
+        }
+    }
+
+    for (_, operation) in document.operations.iter() {
+        visit(&operation.node.selection_set.node, f);
+    }
+    for fragment in document.fragments.values() {
+        visit(&fragment.node.selection_set.node, f);
+    }
+}
+
+/// Rejects request bodies over `max_body_bytes`. The body is read here rather than limited as it
+/// is streamed, so that the rejection is reported like other errors instead of as a malformed
+/// request.
+pub(crate) async fn limit_body_size(
+    State(max_body_bytes): State<usize>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, max_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(err) if is_length_limit_error(&err) => {
+            return ApiError::new(
+                ErrorCode::PayloadTooLarge,
+                format!("The request body is larger than the limit of {max_body_bytes} bytes"),
+            )
+            .into_response()
+        }
+        Err(err) => {
+            return ApiError::new(
+                ErrorCode::BadRequest,
+                format!("Failed to read the request body: {err}"),
+            )
+            .into_response()
+        }
+    };
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+/// Whether reading the body failed because it is over the limit, rather than e.g. because the
+/// connection was closed.
+fn is_length_limit_error(err: &axum::Error) -> bool {
+    std::error::Error::source(err).is_some_and(|source| source.is::<LengthLimitError>())
+}
@@ -0,0 +1,49 @@
+//! The data loaders, which batch the loading of what the fields of many objects refer to, so that
+//! a list of tasks does not take a query per task for each such field.
+
+use std::collections::HashMap;
+
+use async_graphql::{dataloader::Loader, ResultExt as _};
+use sea_orm::DatabaseConnection;
+use uuid::Uuid;
+
+use crate::app;
+
+/// Loads the tasks by their ids, and their subtasks by [`SubtasksOf`]. It does not check whether
+/// the user can see the tasks, so it is only for those related to tasks the user can see, see
+/// [`app::task::load_tasks`].
+pub(crate) struct TaskLoader {
+    pub(crate) db_conn: DatabaseConnection,
+}
+
+/// The id of a task whose subtasks are loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct SubtasksOf(pub(crate) Uuid);
+
+impl Loader<Uuid> for TaskLoader {
+    type Value = app::task::Task;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        app::task::load_tasks(keys, &self.db_conn).await.extend()
+    }
+}
+
+impl Loader<SubtasksOf> for TaskLoader {
+    type Value = Vec<app::task::Task>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        keys: &[SubtasksOf],
+    ) -> Result<HashMap<SubtasksOf, Self::Value>, Self::Error> {
+        let parent_ids = keys.iter().map(|key| key.0).collect::<Vec<_>>();
+        let subtasks = app::task::load_subtasks(&parent_ids, &self.db_conn)
+            .await
+            .extend()?;
+        Ok(subtasks
+            .into_iter()
+            .map(|(parent_id, subtasks)| (SubtasksOf(parent_id), subtasks))
+            .collect())
+    }
+}
@@ -1,60 +1,314 @@
 use std::fmt::Display;
 
 use crate::{
-    app::{maybe::Maybe, task::ViewType, time::EpochLike},
-    auth::Claims,
+    app::{
+        api_token::Scope,
+        maybe::Maybe,
+        security_event::{EventKind, SecurityEventCursor},
+        task::{Recurrence, SeriesScope, TaskCursor, TaskSortKey, ViewType},
+        task_event::{TaskChangeKind, TaskEvents},
+        time::EpochLike,
+        workspace::Role,
+    },
+    auth::{
+        authenticate, require_key, AuthError, ClientInfo, Principal, TokenError, TotpEncryptionKey,
+        CSRF_TOKEN_HEADER,
+    },
     entities,
+    error::{ApiError, ErrorCode, ErrorReporting, RequestId},
     utils::OptionExt as _,
 };
 use async_graphql::{
-    http::GraphiQLSource, Context, EmptySubscription, ErrorExtensions, InputObject, MaybeUndefined,
-    Object, Schema, SimpleObject,
+    connection::{self, Connection, Edge, OpaqueCursor},
+    dataloader::DataLoader,
+    http::{GraphiQLSource, ALL_WEBSOCKET_PROTOCOLS},
+    ComplexObject, Context, Data, ErrorExtensions, Guard, InputObject, Interface, MaybeUndefined,
+    Object, ResultExt as _, Schema, SimpleObject, Subscription, ID,
 };
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
-    extract::State,
-    response::{Html, IntoResponse},
+    extract::{State, WebSocketUpgrade},
+    http::{header, HeaderMap, HeaderValue},
+    middleware,
+    response::{Html, IntoResponse, Response},
     routing, Router,
 };
-use chrono::NaiveDate;
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
-use tracing::info;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::{Stream, StreamExt};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::app;
 
-pub fn routes(db_conn: DatabaseConnection) -> Router {
-    let schema: AppSchema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+mod limits;
+mod loaders;
+
+pub use limits::GraphqlConfig;
+use limits::QueryLimits;
+use loaders::{SubtasksOf, TaskLoader};
+
+pub fn routes(
+    db_conn: DatabaseConnection,
+    totp_key: Option<TotpEncryptionKey>,
+    task_events: TaskEvents,
+    error_reporting: ErrorReporting,
+    config: GraphqlConfig,
+) -> Router {
+    let mut schema = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
         .extension(async_graphql::extensions::Logger)
+        .extension(error_reporting)
+        .extension(QueryLimits::from(&config))
         .data(db_conn.clone())
-        .finish();
-    let app_state = AppState { db_conn, schema };
+        .data(totp_key)
+        .data(task_events)
+        .data(DataLoader::new(
+            TaskLoader {
+                db_conn: db_conn.clone(),
+            },
+            tokio::spawn,
+        ));
+    if !config.introspection {
+        schema = schema.disable_introspection();
+    }
+    let schema: AppSchema = schema.finish();
+    let app_state = AppState {
+        db_conn,
+        schema,
+        error_reporting,
+    };
 
+    let mut graphql = routing::post(graphql_handler).layer(middleware::from_fn_with_state(
+        config.max_body_bytes,
+        limits::limit_body_size,
+    ));
+    if config.graphiql {
+        graphql = graphql.get(graphiql);
+    }
     Router::new()
-        .route("/", routing::get(graphiql).post(graphql_handler))
+        .route("/", graphql)
+        .route("/ws", routing::get(graphql_ws_handler))
         .with_state(app_state)
 }
 
-pub(crate) type AppSchema = async_graphql::Schema<QueryRoot, MutationRoot, EmptySubscription>;
+pub(crate) type AppSchema = async_graphql::Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
 pub(crate) struct QueryRoot;
 
 #[Object]
 impl QueryRoot {
+    /// The tasks that match the filter, in the order of `orderBy`, or of their ids by default.
+    /// Without `first` or `last`, the first `DEFAULT_TASK_PAGE_SIZE` tasks are listed.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    #[allow(clippy::too_many_arguments)] // The arguments of the field.
     async fn tasks(
         &self,
         ctx: &Context<'_>,
         filter: Option<TaskFilter>,
-    ) -> async_graphql::Result<Vec<Task>> {
-        Ok(app::task::list_tasks(
+        order_by: Option<TaskOrder>,
+        after: Option<String>,
+        before: Option<String>,
+        #[graphql(validator(minimum = 1, maximum = 200))] first: Option<i32>,
+        #[graphql(validator(minimum = 1, maximum = 200))] last: Option<i32>,
+    ) -> async_graphql::Result<Connection<OpaqueCursor<TaskCursor>, Task, TaskConnectionFields>>
+    {
+        let user_id = ctx.user()?.id;
+        let filter: app::task::TaskFilter = filter
+            .try_map(TryInto::try_into)
+            .extend()?
+            .unwrap_or_default();
+        let order = order_by.map(app::task::TaskOrder::from).unwrap_or_default();
+        let first = first.or(last.is_none().then_some(DEFAULT_TASK_PAGE_SIZE));
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<OpaqueCursor<TaskCursor>>,
+             before: Option<OpaqueCursor<TaskCursor>>,
+             first,
+             last| async move {
+                let from_end = last.is_some();
+                let page = app::task::TaskPage {
+                    after: after.map(|cursor| cursor.0),
+                    before: before.map(|cursor| cursor.0),
+                    limit: first.or(last).unwrap_or_default() as u64,
+                    from_end,
+                };
+                let (tasks, has_more) =
+                    app::task::list_tasks(user_id, filter.clone(), order, page, ctx.db_conn())
+                        .await
+                        .extend()?;
+                let mut connection = Connection::with_additional_fields(
+                    from_end && has_more,
+                    !from_end && has_more,
+                    TaskConnectionFields { user_id, filter },
+                );
+                connection.edges.extend(
+                    tasks
+                        .into_iter()
+                        .map(|task| Edge::new(OpaqueCursor(task.cursor(order)), Task::from(task))),
+                );
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// The tasks matching the filter whose titles have all the words of the query, best matches
+    /// first. Each word matches the words that start with it, e.g. `renew pass` matches "Renew the
+    /// passport".
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn search_tasks(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        filter: Option<TaskFilter>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] first: i32,
+    ) -> async_graphql::Result<Vec<TaskSearchResult>> {
+        let filter = filter
+            .try_map(TryInto::try_into)
+            .extend()?
+            .unwrap_or_default();
+        Ok(app::task_search::search_tasks(
             ctx.user()?.id,
-            filter.try_map(TryInto::try_into)?.unwrap_or_default(),
+            &query,
+            filter,
+            first as u64,
             ctx.db_conn(),
         )
-        .await?
+        .await
+        .extend()?
         .into_iter()
-        .map(Task::from)
-        .collect::<Vec<_>>())
+        .map(TaskSearchResult::from)
+        .collect())
+    }
+
+    /// The task with the given `taskId`. Use `node` to fetch a task by its global `id`.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn task(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Task> {
+        Ok(app::task::find_task(ctx.user()?.id, id, ctx.db_conn())
+            .await
+            .extend()?
+            .into())
+    }
+
+    /// Fetches an object by its global id, as specified by Relay.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> async_graphql::Result<Option<Node>> {
+        Ok(find_nodes(ctx, vec![id]).await?.pop().flatten())
+    }
+
+    /// Fetches objects by their global ids, in the same order.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn nodes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(max_items = 100))] ids: Vec<ID>,
+    ) -> async_graphql::Result<Vec<Option<Node>>> {
+        find_nodes(ctx, ids).await
+    }
+
+    /// The workspaces the current user is a member of, starting with the personal one.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn workspaces(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Workspace>> {
+        Ok(
+            app::workspace::list_workspaces(ctx.user()?.id, ctx.db_conn())
+                .await
+                .extend()?
+                .into_iter()
+                .map(Workspace::from)
+                .collect(),
+        )
+    }
+
+    /// The active login sessions of the current user.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn sessions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Session>> {
+        let user = ctx.user()?;
+        Ok(app::session::list_sessions(user.id, ctx.db_conn())
+            .await
+            .extend()?
+            .into_iter()
+            .map(|session| Session::new(session, user.session_id()))
+            .collect())
+    }
+
+    /// Whether the current user has two-factor authentication enabled.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn two_factor(&self, ctx: &Context<'_>) -> async_graphql::Result<TwoFactorStatus> {
+        Ok(
+            app::two_factor::two_factor_status(ctx.user()?.id, ctx.db_conn())
+                .await
+                .extend()?
+                .into(),
+        )
+    }
+
+    /// The personal API tokens of the current user.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn api_tokens(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ApiToken>> {
+        Ok(
+            app::api_token::list_api_tokens(ctx.user()?.id, ctx.db_conn())
+                .await
+                .extend()?
+                .into_iter()
+                .map(ApiToken::from)
+                .collect(),
+        )
+    }
+
+    /// The security audit log of the current user, newest first: logins, failed logins, session
+    /// and API token changes, and changes of the password and of two-factor authentication.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn security_events(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        #[graphql(validator(minimum = 1, maximum = 100), default = 20)] first: i32,
+    ) -> async_graphql::Result<Connection<OpaqueCursor<SecurityEventCursor>, SecurityEvent>> {
+        let user_id = ctx.user()?.id;
+        connection::query(
+            after,
+            None,
+            Some(first),
+            None,
+            |after: Option<OpaqueCursor<SecurityEventCursor>>, _, first, _| async move {
+                let (events, has_next_page) = app::security_event::list_security_events(
+                    user_id,
+                    after.map(|cursor| cursor.0),
+                    first.unwrap_or_default() as u64,
+                    ctx.db_conn(),
+                )
+                .await
+                .extend()?;
+                let mut connection = Connection::new(false, has_next_page);
+                connection.edges.extend(events.into_iter().map(|event| {
+                    Edge::new(OpaqueCursor(event.cursor()), SecurityEvent::from(event))
+                }));
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+}
+
+/// How many tasks are listed when neither `first` nor `last` is given.
+const DEFAULT_TASK_PAGE_SIZE: i32 = 100;
+
+pub(crate) struct TaskConnectionFields {
+    user_id: Uuid,
+    filter: app::task::TaskFilter,
+}
+
+#[Object]
+impl TaskConnectionFields {
+    /// The number of tasks that match the filter, on all pages.
+    async fn total_count(&self, ctx: &Context<'_>) -> async_graphql::Result<u64> {
+        app::task::count_tasks(self.user_id, self.filter.clone(), ctx.db_conn())
+            .await
+            .extend()
     }
 }
 
@@ -62,6 +316,7 @@ pub(crate) struct MutationRoot;
 
 #[Object]
 impl MutationRoot {
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
     async fn create_task(
         &self,
         ctx: &Context<'_>,
@@ -69,27 +324,336 @@ impl MutationRoot {
     ) -> async_graphql::Result<Task> {
         Ok(
             app::task::create_task(ctx.user()?.id, input.into(), ctx.db_conn())
-                .await?
+                .await
+                .extend()?
                 .into(),
         )
     }
 
+    /// Updates the task. For a task of a recurring series, `scope` tells which other tasks of the
+    /// series get the new title and cost; the other fields are only changed on the task.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
     async fn update_task(
         &self,
         ctx: &Context<'_>,
         input: UpdateTaskInput,
+        #[graphql(default)] scope: SeriesScope,
     ) -> async_graphql::Result<Task> {
+        Ok(app::task::update_task(
+            ctx.user()?.id,
+            input.try_into().extend()?,
+            scope,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?
+        .into())
+    }
+
+    /// Deletes the task with the given `taskId`. For a task of a recurring series, `scope` tells
+    /// which other tasks of the series are deleted. Deleting the following occurrences ends the
+    /// series at the occurrence, so that no more are generated, and deleting only the recurring
+    /// task keeps its occurrences as one-off tasks.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
+    async fn delete_task(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        #[graphql(default)] scope: SeriesScope,
+    ) -> async_graphql::Result<Uuid> {
+        app::task::delete_task(ctx.user()?.id, id, scope, ctx.db_conn())
+            .await
+            .extend()?;
+        Ok(id)
+    }
+
+    /// Updates the tasks in one transaction. A failed update is reported in its result and the
+    /// others are saved, unless `allOrNothing` is set, in which case the mutation fails and no
+    /// task is updated.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
+    async fn bulk_update_tasks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(max_items = 100))] inputs: Vec<UpdateTaskInput>,
+        #[graphql(default)] all_or_nothing: bool,
+    ) -> async_graphql::Result<Vec<BulkTaskResult>> {
+        let inputs = inputs
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<_>, Error>>()
+            .extend()?;
+        let results =
+            app::task::bulk_update_tasks(ctx.user()?.id, inputs, all_or_nothing, ctx.db_conn())
+                .await
+                .extend()?;
+        Ok(results.into_iter().map(BulkTaskResult::from).collect())
+    }
+
+    /// Completes the tasks with the given `taskId`s on the date in one transaction, see
+    /// `bulkUpdateTasks`.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
+    async fn bulk_complete_tasks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(max_items = 100))] ids: Vec<Uuid>,
+        complete_date: NaiveDate,
+        #[graphql(default)] all_or_nothing: bool,
+    ) -> async_graphql::Result<Vec<BulkTaskResult>> {
+        let results = app::task::bulk_complete_tasks(
+            ctx.user()?.id,
+            ids,
+            complete_date,
+            all_or_nothing,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?;
+        Ok(results.into_iter().map(BulkTaskResult::from).collect())
+    }
+
+    /// Deletes the tasks with the given `taskId`s in one transaction, see `bulkUpdateTasks`.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
+    async fn bulk_delete_tasks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(validator(max_items = 100))] ids: Vec<Uuid>,
+        #[graphql(default)] all_or_nothing: bool,
+    ) -> async_graphql::Result<Vec<BulkTaskResult>> {
+        let results =
+            app::task::bulk_delete_tasks(ctx.user()?.id, ids, all_or_nothing, ctx.db_conn())
+                .await
+                .extend()?;
+        Ok(results
+            .into_iter()
+            .map(|(task_id, result)| BulkTaskResult::from((task_id, result.map(|()| None))))
+            .collect())
+    }
+
+    /// Moves the tasks scheduled on `from` that are not completed to `to` in one transaction, see
+    /// `bulkUpdateTasks`. Defaults to the tasks of all the workspaces of the current user.
+    #[graphql(guard = "ScopeGuard(Scope::TasksWrite)")]
+    async fn move_tasks(
+        &self,
+        ctx: &Context<'_>,
+        from: Epoch,
+        to: Epoch,
+        workspace_id: Option<Uuid>,
+        #[graphql(default)] all_or_nothing: bool,
+    ) -> async_graphql::Result<Vec<BulkTaskResult>> {
+        let results = app::task::move_tasks(
+            ctx.user()?.id,
+            from.into(),
+            to.into(),
+            workspace_id,
+            all_or_nothing,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?;
+        Ok(results.into_iter().map(BulkTaskResult::from).collect())
+    }
+
+    /// Creates a shared workspace owned by the current user.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn create_workspace(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> async_graphql::Result<Workspace> {
         Ok(
-            app::task::update_task(ctx.user()?.id, input.try_into()?, ctx.db_conn())
-                .await?
+            app::workspace::create_workspace(ctx.user()?.id, name, ctx.db_conn())
+                .await
+                .extend()?
                 .into(),
         )
     }
 
-    async fn delete_task(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Uuid> {
-        app::task::delete_task(ctx.user()?.id, id, ctx.db_conn()).await?;
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn add_workspace_member(
+        &self,
+        ctx: &Context<'_>,
+        input: AddWorkspaceMemberInput,
+    ) -> async_graphql::Result<WorkspaceMember> {
+        Ok(app::workspace::add_workspace_member(
+            ctx.user()?.id,
+            input.workspace_id,
+            &input.username,
+            input.role,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?
+        .into())
+    }
+
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn update_workspace_member(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateWorkspaceMemberInput,
+    ) -> async_graphql::Result<WorkspaceMember> {
+        Ok(app::workspace::update_workspace_member(
+            ctx.user()?.id,
+            input.workspace_id,
+            input.user_id,
+            input.role,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?
+        .into())
+    }
+
+    /// Removes a member from a workspace, or leaves it when `userId` is the current user.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn remove_workspace_member(
+        &self,
+        ctx: &Context<'_>,
+        workspace_id: Uuid,
+        user_id: Uuid,
+    ) -> async_graphql::Result<Uuid> {
+        app::workspace::remove_workspace_member(
+            ctx.user()?.id,
+            workspace_id,
+            user_id,
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?;
+        Ok(user_id)
+    }
+
+    /// Revokes a login session of the current user, which logs out the device using it.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn revoke_session(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Uuid> {
+        app::session::revoke_session(ctx.user()?.id, id, ctx.client(), ctx.db_conn())
+            .await
+            .extend()?;
         Ok(id)
     }
+
+    /// Starts enrolling an authenticator app. Two-factor authentication is enabled once a code from
+    /// it is given to `confirmTotp`.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn enroll_totp(&self, ctx: &Context<'_>) -> async_graphql::Result<TotpEnrollment> {
+        Ok(
+            app::two_factor::enroll_totp(ctx.user()?.id, ctx.totp_key()?, ctx.db_conn())
+                .await
+                .extend()?
+                .into(),
+        )
+    }
+
+    /// Enables two-factor authentication and returns the recovery codes, which are shown only
+    /// once.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn confirm_totp(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> async_graphql::Result<Vec<String>> {
+        app::two_factor::confirm_totp(
+            ctx.user()?.id,
+            code,
+            ctx.totp_key()?,
+            ctx.client(),
+            ctx.db_conn(),
+        )
+        .await
+        .extend()
+    }
+
+    /// Replaces the recovery codes. Needs a code from the authenticator app.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn regenerate_recovery_codes(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+    ) -> async_graphql::Result<Vec<String>> {
+        app::two_factor::regenerate_recovery_codes(
+            ctx.user()?.id,
+            code,
+            ctx.totp_key()?,
+            ctx.client(),
+            ctx.db_conn(),
+        )
+        .await
+        .extend()
+    }
+
+    /// Disables two-factor authentication. Needs a code from the authenticator app or a recovery
+    /// code.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn disable_totp(&self, ctx: &Context<'_>, code: String) -> async_graphql::Result<bool> {
+        app::two_factor::disable_totp(
+            ctx.user()?.id,
+            code,
+            ctx.totp_key()?,
+            ctx.client(),
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?;
+        Ok(true)
+    }
+
+    /// Creates a personal API token. The returned `token` is shown only once.
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn create_api_token(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateApiTokenInput,
+    ) -> async_graphql::Result<CreatedApiToken> {
+        let (api_token, token) = app::api_token::create_api_token(
+            ctx.user()?.id,
+            input.name,
+            input.scopes,
+            ctx.client(),
+            ctx.db_conn(),
+        )
+        .await
+        .extend()?;
+        Ok(CreatedApiToken {
+            api_token: api_token.into(),
+            token,
+        })
+    }
+
+    #[graphql(guard = "LoginSessionGuard")]
+    async fn delete_api_token(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Uuid> {
+        app::api_token::delete_api_token(ctx.user()?.id, id, ctx.client(), ctx.db_conn())
+            .await
+            .extend()?;
+        Ok(id)
+    }
+}
+
+pub(crate) struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// The tasks that are created, updated or deleted from now on, among the tasks the current user
+    /// can see that match the filter.
+    #[graphql(guard = "ScopeGuard(Scope::TasksRead)")]
+    async fn task_changed(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TaskFilter>,
+    ) -> async_graphql::Result<impl Stream<Item = async_graphql::Result<TaskChangedEvent>>> {
+        let filter = filter
+            .try_map(TryInto::try_into)
+            .extend()?
+            .unwrap_or_default();
+        let events = app::task_event::watch_tasks(
+            ctx.user()?.id,
+            filter,
+            ctx.task_events(),
+            ctx.db_conn().clone(),
+        )
+        .await
+        .extend()?;
+        Ok(events.map(|event| Ok(event.extend()?.into())))
+    }
 }
 
 #[derive(Debug, InputObject)]
@@ -151,6 +715,8 @@ impl From<app::time::Epoch> for Epoch {
 struct RecurringSpec {
     pub(crate) start_date: NaiveDate,
     pub(crate) pattern: RecurringPattern,
+    /// The date the series ends on, if it does: no occurrence starts on or after it.
+    pub(crate) end_date: Option<NaiveDate>,
 }
 
 impl From<RecurringSpec> for app::time::RecurringSpec {
@@ -158,6 +724,7 @@ impl From<RecurringSpec> for app::time::RecurringSpec {
         app::time::RecurringSpec {
             start_date: value.start_date,
             pattern: value.pattern.into(),
+            end_date: value.end_date,
         }
     }
 }
@@ -179,7 +746,32 @@ struct RecurringPattern {
 
 #[derive(InputObject)]
 struct TaskFilter {
+    workspace_id: Option<Uuid>,
     view_filter: Option<ViewFilter>,
+    /// Only the tasks that are completed, or only those that are not.
+    completed: Option<bool>,
+    /// Only the tasks completed on or after `start` and before `end`.
+    complete_date: Option<DateRange>,
+    /// Only the tasks whose cost is in the range. Tasks without a cost are not in any range.
+    cost: Option<CostRange>,
+    recurrence: Option<Recurrence>,
+    /// Only the tasks that are not scheduled.
+    #[graphql(default)]
+    unscheduled_only: bool,
+}
+
+/// The costs from `min` to `max`, both included.
+#[derive(InputObject)]
+struct CostRange {
+    min: Option<i32>,
+    max: Option<i32>,
+}
+
+#[derive(InputObject)]
+struct TaskOrder {
+    key: TaskSortKey,
+    #[graphql(default)]
+    descending: bool,
 }
 
 #[derive(InputObject)]
@@ -188,10 +780,30 @@ struct ViewFilter {
     epoch: Option<Epoch>,
 }
 
-impl From<TaskFilter> for app::task::TaskFilter {
-    fn from(value: TaskFilter) -> Self {
-        app::task::TaskFilter {
+impl TryFrom<TaskFilter> for app::task::TaskFilter {
+    type Error = Error;
+
+    fn try_from(value: TaskFilter) -> Result<Self, Self::Error> {
+        Ok(app::task::TaskFilter {
+            workspace_id: value.workspace_id,
             view_filter: value.view_filter.map(app::task::ViewFilter::from),
+            completed: value.completed,
+            complete_date: value.complete_date.map(TryInto::try_into).transpose()?,
+            cost: value.cost.map(|cost| app::task::CostRange {
+                min: cost.min,
+                max: cost.max,
+            }),
+            recurrence: value.recurrence,
+            unscheduled_only: value.unscheduled_only,
+        })
+    }
+}
+
+impl From<TaskOrder> for app::task::TaskOrder {
+    fn from(value: TaskOrder) -> Self {
+        app::task::TaskOrder {
+            key: value.key,
+            descending: value.descending,
         }
     }
 }
@@ -205,21 +817,166 @@ impl From<ViewFilter> for app::task::ViewFilter {
     }
 }
 
+/// An object that can be fetched by its global id with `node`, as specified by Relay.
+#[derive(Interface)]
+#[graphql(field(name = "id", ty = "&ID"))]
+enum Node {
+    Task(Task),
+}
+
+/// The id of an object that is unique across all types: the name of the type and the id within
+/// the type, joined by a colon and encoded in base64.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GlobalId {
+    Task(Uuid),
+}
+
+impl GlobalId {
+    fn encode(self) -> ID {
+        let (typ, id) = match self {
+            GlobalId::Task(id) => ("Task", id),
+        };
+        ID(STANDARD.encode(format!("{typ}:{id}")))
+    }
+
+    fn decode(id: ID) -> Result<Self, Error> {
+        let parsed = STANDARD
+            .decode(id.as_str())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .and_then(|decoded| {
+                let (typ, uuid) = decoded.split_once(':')?;
+                let uuid = Uuid::parse_str(uuid).ok()?;
+                match typ {
+                    "Task" => Some(GlobalId::Task(uuid)),
+                    _ => None,
+                }
+            });
+        parsed.ok_or_else(|| Error::invalid_node_id(id))
+    }
+}
+
+/// Loads the objects with the given global ids, in the same order. Fails if any of them is not
+/// found.
+async fn find_nodes(ctx: &Context<'_>, ids: Vec<ID>) -> async_graphql::Result<Vec<Option<Node>>> {
+    let task_ids = ids
+        .into_iter()
+        .map(|id| {
+            let GlobalId::Task(task_id) = GlobalId::decode(id)?;
+            Ok(task_id)
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .extend()?;
+    Ok(
+        app::task::find_tasks(ctx.user()?.id, &task_ids, ctx.db_conn())
+            .await
+            .extend()?
+            .into_iter()
+            .map(|task| Some(Node::Task(task.into())))
+            .collect(),
+    )
+}
+
 #[derive(SimpleObject)]
+#[graphql(complex)]
 struct Task {
-    id: Uuid,
+    /// The global id of the task, which only `node` and `nodes` take.
+    id: ID,
+    /// The id of the task, which every other argument and input field referring to a task takes,
+    /// e.g. `task(id:)`, `UpdateTaskInput.id`, `parentId` and the ids of the bulk mutations.
+    task_id: Uuid,
+    workspace_id: Uuid,
+    /// The `taskId` of the task this task is a subtask of.
+    parent_id: Option<Uuid>,
+    /// The `taskId` of the recurring task this task is an occurrence of.
+    recurring_template_id: Option<Uuid>,
+    /// Whether the task is completed once all its subtasks are.
+    complete_with_subtasks: bool,
     scheduled_on: Option<Epoch>,
     is_completed: bool,
     title: String,
+    /// The cost of the task, which is the sum of the costs of its subtasks if it has any.
     cost: Option<i32>,
     recurring: Option<RecurringSpec>,
 }
 
+#[ComplexObject]
+impl Task {
+    /// The task this task is a subtask of.
+    async fn parent(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Task>> {
+        let Some(parent_id) = self.parent_id else {
+            return Ok(None);
+        };
+        Ok(ctx.task_loader().load_one(parent_id).await?.map(Task::from))
+    }
+
+    /// The recurring task this task is an occurrence of.
+    async fn recurring_template(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Task>> {
+        let Some(template_id) = self.recurring_template_id else {
+            return Ok(None);
+        };
+        Ok(ctx
+            .task_loader()
+            .load_one(template_id)
+            .await?
+            .map(Task::from))
+    }
+
+    /// The subtasks of the task, in the order they were created.
+    async fn children(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Task>> {
+        Ok(ctx
+            .task_loader()
+            .load_one(SubtasksOf(self.task_id))
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(Task::from)
+            .collect())
+    }
+}
+
+#[derive(SimpleObject)]
+struct TaskSearchResult {
+    task: Task,
+    /// How well the task matches the query, higher is better.
+    rank: f32,
+    /// The title of the task, split into the parts that match the query and those between them.
+    snippet: Vec<SnippetPart>,
+}
+
+#[derive(SimpleObject)]
+struct SnippetPart {
+    text: String,
+    highlighted: bool,
+}
+
+impl From<app::task_search::SearchResult> for TaskSearchResult {
+    fn from(value: app::task_search::SearchResult) -> Self {
+        Self {
+            task: value.task.into(),
+            rank: value.rank,
+            snippet: value
+                .snippet
+                .into_iter()
+                .map(|part| SnippetPart {
+                    text: part.text,
+                    highlighted: part.highlighted,
+                })
+                .collect(),
+        }
+    }
+}
+
 impl From<app::task::Task> for Task {
     fn from(value: app::task::Task) -> Self {
         let is_completed = value.is_completed();
         Self {
-            id: value.id,
+            id: GlobalId::Task(value.id).encode(),
+            task_id: value.id,
+            workspace_id: value.workspace_id,
+            parent_id: value.parent_id,
+            recurring_template_id: value.recurring_template_id,
+            complete_with_subtasks: value.complete_with_subtasks,
             scheduled_on: value.scheduled_on.map(From::from),
             is_completed,
             title: value.title,
@@ -228,6 +985,7 @@ impl From<app::task::Task> for Task {
                 let app::time::RecurringSpec {
                     start_date,
                     pattern,
+                    end_date,
                 } = r.spec;
                 let app::time::RecurringPattern::EveryEpoch {
                     kind: app::time::EpochKind::Week,
@@ -240,14 +998,250 @@ impl From<app::task::Task> for Task {
                 Some(RecurringSpec {
                     start_date,
                     pattern: RecurringPattern { every },
+                    end_date,
                 })
             }),
         }
     }
 }
 
+/// The outcome of the change of one task in a bulk mutation.
+#[derive(SimpleObject)]
+struct BulkTaskResult {
+    task_id: Uuid,
+    /// The task after the change, or null if it failed or the task was deleted.
+    task: Option<Task>,
+    /// Why the change failed, or null if it succeeded.
+    error: Option<String>,
+    /// The code of the error, as in the `code` extension of GraphQL errors.
+    error_code: Option<&'static str>,
+}
+
+impl From<app::task::BulkResult<Option<app::task::Task>>> for BulkTaskResult {
+    fn from((task_id, result): app::task::BulkResult<Option<app::task::Task>>) -> Self {
+        let (task, error, error_code) = match result {
+            Ok(task) => (task.map(Task::from), None, None),
+            Err(err) => (
+                None,
+                Some(err.to_string()),
+                Some(ApiError::from(&err).code.as_str()),
+            ),
+        };
+        Self {
+            task_id,
+            task,
+            error,
+            error_code,
+        }
+    }
+}
+
+impl From<app::task::BulkResult<app::task::Task>> for BulkTaskResult {
+    fn from((task_id, result): app::task::BulkResult<app::task::Task>) -> Self {
+        Self::from((task_id, result.map(Some)))
+    }
+}
+
+#[derive(SimpleObject)]
+struct TaskChangedEvent {
+    kind: TaskChangeKind,
+    task_id: Uuid,
+    workspace_id: Uuid,
+    /// The task after the change, or null for a deleted task.
+    task: Option<Task>,
+}
+
+impl From<app::task_event::TaskEvent> for TaskChangedEvent {
+    fn from(value: app::task_event::TaskEvent) -> Self {
+        Self {
+            kind: value.kind,
+            task_id: value.task_id,
+            workspace_id: value.workspace_id,
+            task: value.task.map(Task::from),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct Workspace {
+    id: Uuid,
+    name: String,
+    /// Whether this is the workspace every user has for their own tasks.
+    is_personal: bool,
+    created_at: DateTime<Utc>,
+    /// The role of the current user in the workspace.
+    role: Role,
+    members: Vec<WorkspaceMember>,
+}
+
+impl From<app::workspace::Workspace> for Workspace {
+    fn from(value: app::workspace::Workspace) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            is_personal: value.is_personal,
+            created_at: value.created_at,
+            role: value.role,
+            members: value.members.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct WorkspaceMember {
+    user_id: Uuid,
+    username: String,
+    role: Role,
+}
+
+impl From<app::workspace::WorkspaceMember> for WorkspaceMember {
+    fn from(value: app::workspace::WorkspaceMember) -> Self {
+        Self {
+            user_id: value.user_id,
+            username: value.username,
+            role: value.role,
+        }
+    }
+}
+
+#[derive(InputObject)]
+struct AddWorkspaceMemberInput {
+    workspace_id: Uuid,
+    username: String,
+    role: Role,
+}
+
+#[derive(InputObject)]
+struct UpdateWorkspaceMemberInput {
+    workspace_id: Uuid,
+    user_id: Uuid,
+    role: Role,
+}
+
+#[derive(SimpleObject)]
+struct Session {
+    id: Uuid,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+    last_refreshed_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    /// Whether this is the session making the request.
+    is_current: bool,
+}
+
+impl Session {
+    fn new(value: app::session::Session, current_session_id: Option<Uuid>) -> Self {
+        Self {
+            id: value.id,
+            user_agent: value.user_agent,
+            created_at: value.created_at,
+            last_refreshed_at: value.last_refreshed_at,
+            expires_at: value.expires_at,
+            is_current: Some(value.id) == current_session_id,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct ApiToken {
+    id: Uuid,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<app::api_token::ApiToken> for ApiToken {
+    fn from(value: app::api_token::ApiToken) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            scopes: value.scopes,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct SecurityEvent {
+    id: Uuid,
+    kind: EventKind,
+    /// E.g. how the user logged in, or the name of the API token.
+    detail: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<app::security_event::SecurityEvent> for SecurityEvent {
+    fn from(value: app::security_event::SecurityEvent) -> Self {
+        Self {
+            id: value.id,
+            kind: value.kind,
+            detail: value.detail,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct TwoFactorStatus {
+    enabled: bool,
+    recovery_codes_left: u64,
+}
+
+impl From<app::two_factor::TwoFactorStatus> for TwoFactorStatus {
+    fn from(value: app::two_factor::TwoFactorStatus) -> Self {
+        Self {
+            enabled: value.enabled,
+            recovery_codes_left: value.recovery_codes_left,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct TotpEnrollment {
+    /// The secret in base32, for authenticator apps that cannot scan the URI.
+    secret: String,
+    /// The `otpauth://` URI to be shown as a QR code.
+    provisioning_uri: String,
+}
+
+impl From<app::two_factor::TotpEnrollment> for TotpEnrollment {
+    fn from(value: app::two_factor::TotpEnrollment) -> Self {
+        Self {
+            secret: value.secret,
+            provisioning_uri: value.provisioning_uri,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct CreatedApiToken {
+    api_token: ApiToken,
+    /// The secret to be sent as `Authorization: Bearer`.
+    token: String,
+}
+
+#[derive(InputObject)]
+struct CreateApiTokenInput {
+    name: String,
+    scopes: Vec<Scope>,
+}
+
 #[derive(InputObject)]
 struct CreateTaskInput {
+    /// Defaults to the workspace of the parent, or to the personal workspace of the current user.
+    workspace_id: Option<Uuid>,
+    /// The `taskId` of the task to create the task as a subtask of, which must be in the same
+    /// workspace.
+    parent_id: Option<Uuid>,
+    /// Whether to complete the task once all its subtasks are completed.
+    #[graphql(default)]
+    complete_with_subtasks: bool,
     scheduled_on: Option<Epoch>,
     recurring_spec: Option<RecurringSpec>,
     title: String,
@@ -257,6 +1251,9 @@ struct CreateTaskInput {
 impl From<CreateTaskInput> for app::task::CreateTaskInput {
     fn from(value: CreateTaskInput) -> Self {
         app::task::CreateTaskInput {
+            workspace_id: value.workspace_id,
+            parent_id: value.parent_id,
+            complete_with_subtasks: value.complete_with_subtasks,
             scheduled_on: value.scheduled_on.map(From::from),
             recurring_spec: value.recurring_spec.map(From::from),
             title: value.title,
@@ -267,7 +1264,12 @@ impl From<CreateTaskInput> for app::task::CreateTaskInput {
 
 #[derive(InputObject)]
 struct UpdateTaskInput {
+    /// The `taskId` of the task to update.
     id: Uuid,
+    /// The `taskId` of the task to make the task a subtask of, or null to make it a top-level
+    /// task.
+    parent_id: MaybeUndefined<Uuid>,
+    complete_with_subtasks: MaybeUndefined<bool>,
     scheduled_on: MaybeUndefined<Epoch>,
     complete_date: MaybeUndefined<NaiveDate>,
     title: MaybeUndefined<String>,
@@ -280,6 +1282,9 @@ impl TryFrom<UpdateTaskInput> for app::task::UpdateTaskInput {
     fn try_from(value: UpdateTaskInput) -> Result<Self, Self::Error> {
         Ok(app::task::UpdateTaskInput {
             id: value.id,
+            parent_id: into_maybe(value.parent_id),
+            complete_with_subtasks: into_maybe_nonnull(value.complete_with_subtasks)
+                .ok_or_else(|| Error::required_field_is_null("completeWithSubtasks".to_owned()))?,
             scheduled_on: into_maybe(value.scheduled_on.map_value(From::from)),
             complete_date: into_maybe(value.complete_date),
             title: into_maybe_nonnull(value.title)
@@ -321,12 +1326,33 @@ impl Error {
     fn required_field_is_null(field: String) -> Self {
         Error::BadRequest(BadRequestReason::RequiredFieldIsNull { field })
     }
+
+    fn invalid_node_id(id: ID) -> Self {
+        Error::BadRequest(BadRequestReason::InvalidNodeId(id))
+    }
+}
+
+impl ErrorExtensions for Error {
+    fn extend(&self) -> async_graphql::Error {
+        let error = match self {
+            Error::BadRequest(reason) => {
+                let error = ApiError::new(ErrorCode::InvalidInput, reason.to_string());
+                match reason {
+                    BadRequestReason::RequiredFieldIsNull { field } => error.with_field(field),
+                    _ => error,
+                }
+            }
+            Error::Internal(err) => ApiError::internal(err),
+        };
+        error.extend()
+    }
 }
 
 #[derive(Debug)]
 enum BadRequestReason {
     InvalidDateRange(DateRange),
     RequiredFieldIsNull { field: String },
+    InvalidNodeId(ID),
 }
 
 impl Display for BadRequestReason {
@@ -338,32 +1364,149 @@ impl Display for BadRequestReason {
             Self::RequiredFieldIsNull { field } => {
                 write!(f, "field `{field}` is a required field, but set to null")
             }
+            Self::InvalidNodeId(id) => write!(f, "`{}` is not the id of a node", id.as_str()),
         }
     }
 }
 
 async fn graphiql() -> impl IntoResponse {
-    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+    Html(
+        GraphiQLSource::build()
+            .endpoint("/graphql")
+            .subscription_endpoint("/graphql/ws")
+            .finish(),
+    )
 }
 
+/// Executes the GraphQL request. A request whose token is not accepted is still executed, but
+/// without a user, so that the fields that need one fail with the reason in the error code. The
+/// reason is also reported in the `WWW-Authenticate` header.
 async fn graphql_handler(
-    State(AppState { db_conn, schema }): State<AppState>,
-    claim: Option<Claims>,
+    State(AppState {
+        db_conn,
+        schema,
+        error_reporting,
+    }): State<AppState>,
+    principal: Result<Principal, AuthError>,
+    client: ClientInfo,
+    request_id: RequestId,
     request: GraphQLRequest,
-) -> GraphQLResponse {
-    let mut request = request.into_inner();
-    if let Some(claim) = claim {
-        if let Ok(Some(user)) = claim.get_user(&db_conn).await {
+) -> Response {
+    let mut request = request.into_inner().data(client);
+    let token_error = match find_user(principal, &db_conn).await {
+        Ok(Ok(user)) => {
             request = request.data(user);
+            None
         }
+        Ok(Err(token_error)) => {
+            request = request.data(token_error);
+            Some(token_error)
+        }
+        Err(err) => return err.into_response(),
+    };
+
+    let mut response = schema.execute(request).await;
+    error_reporting.report_graphql(&mut response, Some(&request_id));
+    let mut response = GraphQLResponse::from(response).into_response();
+    if let Some(token_error) = token_error {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, token_error.www_authenticate());
+    }
+    response
+}
+
+/// Serves subscriptions, and other operations too, over the graphql-ws protocol. The credential
+/// is taken from the `connection_init` message, see [`ConnectionInitPayload`].
+async fn graphql_ws_handler(
+    State(AppState {
+        db_conn, schema, ..
+    }): State<AppState>,
+    headers: HeaderMap,
+    client: ClientInfo,
+    request_id: RequestId,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            GraphQLWebSocket::new(stream, schema, protocol)
+                .on_connection_init(move |payload| {
+                    connection_data(payload, headers, client, request_id, db_conn)
+                })
+                .serve()
+        })
+}
+
+/// The payload of the `connection_init` message. Browsers cannot set headers on WebSocket
+/// requests, so what would be in the headers of an HTTP request is sent here instead.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionInitPayload {
+    /// The value of the `Authorization` header, i.e. `Bearer <token>`.
+    authorization: Option<String>,
+    /// The value of the CSRF token header, for a session in cookies. The cookies themselves are
+    /// sent with the WebSocket request.
+    csrf_token: Option<String>,
+}
+
+/// Authenticates a WebSocket connection. As with HTTP requests, a token that is not accepted
+/// leaves the connection without a user, so that the fields that need one fail with the reason.
+async fn connection_data(
+    payload: serde_json::Value,
+    mut headers: HeaderMap,
+    client: ClientInfo,
+    request_id: RequestId,
+    db_conn: DatabaseConnection,
+) -> async_graphql::Result<Data> {
+    let payload = match payload {
+        serde_json::Value::Null => ConnectionInitPayload::default(),
+        payload => serde_json::from_value::<ConnectionInitPayload>(payload)?,
+    };
+    if let Some(authorization) = payload.authorization {
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization)?,
+        );
+    }
+    if let Some(csrf_token) = payload.csrf_token {
+        headers.insert(CSRF_TOKEN_HEADER, HeaderValue::from_str(&csrf_token)?);
+    }
+
+    let mut data = Data::default();
+    data.insert(client);
+    data.insert(request_id);
+    match find_user(authenticate(&headers, &db_conn).await, &db_conn)
+        .await
+        .map_err(|err| ApiError::from(&err).extend())?
+    {
+        Ok(user) => data.insert(user),
+        Err(token_error) => data.insert(token_error),
+    }
+    Ok(data)
+}
+
+/// Finds the user that the credential belongs to. Returns why the token is not accepted, if so.
+async fn find_user(
+    principal: Result<Principal, AuthError>,
+    db_conn: &DatabaseConnection,
+) -> Result<Result<User, TokenError>, AuthError> {
+    match principal {
+        Ok(principal) => Ok(principal
+            .get_user(db_conn)
+            .await?
+            .ok_or(TokenError::Invalid)),
+        Err(AuthError::Token(err)) => Ok(Err(err)),
+        Err(err) => Err(err),
     }
-    schema.execute(request).await.into()
 }
 
 #[derive(Clone)]
 struct AppState {
     db_conn: DatabaseConnection,
     schema: AppSchema,
+    error_reporting: ErrorReporting,
 }
 
 #[derive(Debug)]
@@ -371,50 +1514,133 @@ struct User {
     id: Uuid,
     #[allow(dead_code)]
     username: String,
+    access: Access,
+}
+
+/// What the credential of the request grants access to.
+#[derive(Debug)]
+enum Access {
+    /// A login session, which has full access.
+    Session { session_id: Uuid },
+    /// A personal API token, which only has access to its scopes.
+    ApiToken { scopes: Vec<Scope> },
 }
 
-impl Claims {
+impl User {
+    fn session_id(&self) -> Option<Uuid> {
+        match self.access {
+            Access::Session { session_id } => Some(session_id),
+            Access::ApiToken { .. } => None,
+        }
+    }
+
+    fn has_scope(&self, scope: Scope) -> bool {
+        match &self.access {
+            Access::Session { .. } => true,
+            Access::ApiToken { scopes } => scopes.contains(&scope),
+        }
+    }
+}
+
+impl Principal {
     async fn get_user(&self, db_conn: &DatabaseConnection) -> anyhow::Result<Option<User>> {
-        let username = &self.sub;
-        info!(username);
+        let access = match self {
+            Principal::Session { claims, .. } => Access::Session {
+                session_id: claims.sid,
+            },
+            Principal::ApiToken(api_token) => Access::ApiToken {
+                scopes: Scope::parse_all(&api_token.scopes),
+            },
+        };
 
-        Ok(entities::users::Entity::find()
-            .filter(entities::users::Column::Username.eq(username))
+        Ok(entities::users::Entity::find_by_id(self.user_id())
             .one(db_conn)
             .await?
             .map(|user| User {
                 id: user.id,
                 username: user.username,
+                access,
             }))
     }
 }
 
+/// Requires the request to be authenticated with access to the scope.
+struct ScopeGuard(Scope);
+
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        if !ctx.user()?.has_scope(self.0) {
+            return Err(ForbiddenError.extend());
+        }
+        Ok(())
+    }
+}
+
+/// Requires the request to be authenticated with a login session rather than an API token, e.g. so
+/// that API tokens cannot be used to mint more of them.
+struct LoginSessionGuard;
+
+impl Guard for LoginSessionGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        if ctx.user()?.session_id().is_none() {
+            return Err(ForbiddenError.extend());
+        }
+        Ok(())
+    }
+}
+
 #[extend::ext]
 impl Context<'_> {
     fn user(&self) -> async_graphql::Result<&User> {
-        self.data::<User>().map_err(|_| UnauthorizedError.extend())
+        self.data::<User>().map_err(|_| {
+            self.data_opt::<TokenError>()
+                .copied()
+                .unwrap_or(TokenError::Missing)
+                .extend()
+        })
     }
 
     fn db_conn(&self) -> &DatabaseConnection {
         self.data_unchecked::<DatabaseConnection>()
     }
+
+    fn client(&self) -> &ClientInfo {
+        self.data_unchecked::<ClientInfo>()
+    }
+
+    fn task_events(&self) -> &TaskEvents {
+        self.data_unchecked::<TaskEvents>()
+    }
+
+    fn task_loader(&self) -> &DataLoader<TaskLoader> {
+        self.data_unchecked::<DataLoader<TaskLoader>>()
+    }
+
+    fn totp_key(&self) -> async_graphql::Result<&TotpEncryptionKey> {
+        require_key(self.data_unchecked::<Option<TotpEncryptionKey>>().as_ref())
+            .map_err(|err| ApiError::internal(&err).extend())
+    }
+}
+
+impl ErrorExtensions for TokenError {
+    fn extend(&self) -> async_graphql::Error {
+        ApiError::from(self).extend()
+    }
 }
 
 #[derive(Debug)]
-struct UnauthorizedError;
+struct ForbiddenError;
 
-impl Display for UnauthorizedError {
+impl Display for ForbiddenError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "User is not authorized")
+        write!(f, "The credential does not grant access to this field")
     }
 }
 
-impl std::error::Error for UnauthorizedError {}
+impl std::error::Error for ForbiddenError {}
 
-impl ErrorExtensions for UnauthorizedError {
+impl ErrorExtensions for ForbiddenError {
     fn extend(&self) -> async_graphql::Error {
-        async_graphql::Error::new(self.to_string()).extend_with(|_, e| {
-            e.set("code", "UNAUTHORIZED");
-        })
+        ApiError::new(ErrorCode::Forbidden, self.to_string()).extend()
     }
 }
@@ -1,4 +1,4 @@
-use axum::{Extension, Router};
+use axum::{middleware, Extension, Router};
 use sea_orm::DatabaseConnection;
 
 use tower_http::{
@@ -8,22 +8,68 @@ use tower_http::{
 
 mod app;
 mod auth;
+mod config;
 pub(crate) mod db;
 pub mod entities;
+mod error;
 mod graphql;
+mod mail;
+mod rate_limit;
 mod utils;
 // mod batch_job;
 
 // pub async fn build_app(pg_pool: PgPool) -> Router {
-pub async fn build_app(pg_conn: DatabaseConnection) -> Router {
+pub async fn build_app(pg_conn: DatabaseConnection, config: Config) -> Router {
     let serve_dir = ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
 
+    let error_reporting = error::ErrorReporting {
+        expose_internal_errors: config.expose_internal_errors,
+    };
+    let rate_limiter = rate_limit::RateLimiter::new(config.rate_limit, pg_conn.clone());
+    let task_events = app::task_event::TaskEvents::listen(pg_conn.clone())
+        .await
+        .expect("Failed to listen to task changes");
+
     Router::new()
-        .nest("/graphql", graphql::routes(pg_conn.clone()))
-        .nest("/auth", auth::routes())
+        .nest(
+            "/graphql",
+            graphql::routes(
+                pg_conn.clone(),
+                config.totp_encryption_key.clone(),
+                task_events,
+                error_reporting,
+                config.graphql,
+            )
+            .layer(middleware::from_fn_with_state(
+                rate_limiter.clone(),
+                rate_limit::limit_graphql_by_user,
+            )),
+        )
+        .nest(
+            "/auth",
+            auth::routes(config.oidc, config.smtp, config.jwt_allow_ephemeral).layer(
+                middleware::from_fn_with_state(rate_limiter.clone(), rate_limit::limit_auth_by_ip),
+            ),
+        )
         .fallback_service(serve_dir)
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn_with_state(
+            error_reporting,
+            error::report_errors,
+        ))
+        .layer(Extension(rate_limiter))
+        .layer(Extension(config.totp_encryption_key))
+        .layer(Extension(config.session_cookies))
         .layer(Extension(pg_conn))
 }
 
 pub use crate::app::task::schedule_all_recurring_tasks_until;
+pub use crate::auth::{
+    create_invite_code, delete_expired_email_tokens, delete_expired_login_challenges,
+    delete_expired_oidc_auth_requests, hash_password, reset_two_factor, set_user_email,
+    set_user_password, CookieConfig, OidcConfig, TotpEncryptionKey,
+};
+pub use crate::config::Config;
+pub use crate::graphql::GraphqlConfig;
+pub use crate::mail::SmtpConfig;
+pub use crate::rate_limit::{delete_expired_rate_limit_counters, Budget, RateLimitConfig};
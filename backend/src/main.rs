@@ -1,8 +1,15 @@
+use std::{io::BufRead, net::SocketAddr};
+
 use anyhow::Context;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
 use futures::FutureExt;
-use planner_backend::schedule_all_recurring_tasks_until;
-use sea_orm::{Database, DatabaseConnection};
+use planner_backend::{
+    create_invite_code, delete_expired_email_tokens, delete_expired_login_challenges,
+    delete_expired_oidc_auth_requests, delete_expired_rate_limit_counters, reset_two_factor,
+    schedule_all_recurring_tasks_until, set_user_email, set_user_password, Config, RateLimitConfig,
+};
+use sea_orm::{Database, DatabaseConnection, DbErr};
 use tokio_cron_scheduler::{Job, JobScheduler};
 
 use tracing::{error, info};
@@ -26,18 +33,47 @@ async fn connect_db() -> DatabaseConnection {
         .expect("Cannot connect to Postgres")
 }
 
-async fn setup_cron(db: DatabaseConnection) -> anyhow::Result<JobScheduler> {
+/// Deletes the rows that have expired, which are otherwise only ignored.
+async fn delete_expired(db: &DatabaseConnection, rate_limit: &RateLimitConfig) {
+    log_deleted(
+        "rate limit counters",
+        delete_expired_rate_limit_counters(rate_limit, db).await,
+    );
+    log_deleted(
+        "OIDC auth requests",
+        delete_expired_oidc_auth_requests(db).await,
+    );
+    log_deleted(
+        "login challenges",
+        delete_expired_login_challenges(db).await,
+    );
+    log_deleted("email tokens", delete_expired_email_tokens(db).await);
+}
+
+fn log_deleted(what: &str, result: Result<u64, DbErr>) {
+    match result {
+        Ok(deleted) => info!(deleted, "Deleted expired {what}"),
+        Err(err) => error!("Error when deleting expired {what}: {err:?}"),
+    }
+}
+
+async fn setup_cron(
+    db: DatabaseConnection,
+    rate_limit: RateLimitConfig,
+) -> anyhow::Result<JobScheduler> {
     let sched = JobScheduler::new().await?;
     let cron_pattern = std::env::var("SCHEDULE_JOBS_CRON")
         .expect("The Cron pattern `SCHEDULE_JOBS_CRON` of the periodic schedule job must be set");
     info!(cron_pattern);
     let job = Job::new_async(cron_pattern, move |_, _| {
         let db = db.clone();
+        let rate_limit = rate_limit.clone();
         async move {
             let result = schedule_all_recurring_tasks_until(&db, None).await;
             if let Err(err) = result {
                 error!("Error when scheduling recurring tasks: {err:?}");
             }
+            delete_expired(&db, &rate_limit).await;
         }
         .boxed()
     })?;
@@ -56,13 +92,81 @@ async fn setup_cron(db: DatabaseConnection) -> anyhow::Result<JobScheduler> {
     Ok(sched)
 }
 
+#[derive(Parser)]
+#[command(about = "The planner backend server and admin tools")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the server. This is the default when no command is given.
+    Serve,
+    /// Set the login password of a user. The password is read from the first line of stdin.
+    SetPassword { username: String },
+    /// Set the email address that login links and password resets are sent to. The address is
+    /// removed if none is given.
+    SetEmail {
+        username: String,
+        email: Option<String>,
+    },
+    /// Mint a single-use invite code for `/auth/register` and print it.
+    CreateInviteCode,
+    /// Turn off two-factor authentication for a user who lost their authenticator and recovery
+    /// codes.
+    ResetTwoFactor { username: String },
+}
+
+async fn set_password(username: &str, db: &DatabaseConnection) -> anyhow::Result<()> {
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password from stdin")?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    anyhow::ensure!(!password.is_empty(), "The password must not be empty");
+
+    set_user_password(username, password, db).await
+}
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
     init_tracing();
+    let cli = Cli::parse();
 
     let db = connect_db().await;
-    let app = planner_backend::build_app(db.clone()).await;
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(db).await,
+        Command::SetPassword { username } => set_password(&username, &db)
+            .await
+            .context("Failed to set password")
+            .unwrap(),
+        Command::SetEmail { username, email } => set_user_email(&username, email.as_deref(), &db)
+            .await
+            .context("Failed to set email address")
+            .unwrap(),
+        Command::CreateInviteCode => {
+            let code = create_invite_code(&db)
+                .await
+                .context("Failed to create invite code")
+                .unwrap();
+            println!("{code}");
+        }
+        Command::ResetTwoFactor { username } => reset_two_factor(&username, &db)
+            .await
+            .context("Failed to reset two-factor authentication")
+            .unwrap(),
+    }
+}
+
+async fn serve(db: DatabaseConnection) {
+    let config = Config::from_env()
+        .context("Invalid server configuration")
+        .unwrap();
+    let rate_limit = config.rate_limit.clone();
+    let app = planner_backend::build_app(db.clone(), config).await;
     let listener = tokio::net::TcpListener::bind(
         &std::env::var("BIND_ADDR")
             .expect("Server bind address $BIND_ADDR env variable is not set"),
@@ -70,7 +174,7 @@ async fn main() {
     .await
     .unwrap();
 
-    let scheduler = setup_cron(db)
+    let scheduler = setup_cron(db, rate_limit)
         .await
         .context("Failed to set up cron job")
         .unwrap();
@@ -80,7 +184,10 @@ async fn main() {
         .context("Failed to start scheduler")
         .unwrap();
     info!("Scheduler started");
-    axum::serve(listener, app.into_make_service())
-        .await
-        .expect("Cannot create server")
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .expect("Cannot create server")
 }
@@ -0,0 +1,397 @@
+//! Rate limiting with fixed-window counters kept in Postgres, so that the limits hold across all
+//! instances of the server.
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{Extensions, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+};
+use tracing::{error, warn};
+
+use crate::{
+    auth::Principal,
+    entities,
+    error::{ApiError, ErrorCode},
+    utils::parse_env,
+};
+
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// The budget of requests to `/auth` per client IP.
+    pub auth: Budget,
+    /// The budget of requests to `/graphql` per user, or per client IP for unauthenticated
+    /// requests.
+    pub graphql: Budget,
+    /// The number of failed logins after which the username is locked out for the client IP.
+    ///
+    /// Failures are counted per username and client IP, so that guessing a password cannot lock the
+    /// owner out from their own IP. The flip side is that an attacker with many IPs gets this many
+    /// guesses on each of them, which only the `auth` budget per IP slows down.
+    pub max_login_failures: u32,
+    /// How long a lockout lasts. Failed logins are also counted in windows of this length.
+    pub lockout: TimeDelta,
+    /// The header with the client IP set by the reverse proxy, e.g. `Fly-Client-IP`. The peer
+    /// address of the connection is used if not set. For a list like `X-Forwarded-For`, the last
+    /// entry is used, since it is the one appended by the proxy in front of the server.
+    pub client_ip_header: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    pub requests: u32,
+    pub window: TimeDelta,
+}
+
+impl Budget {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            window: TimeDelta::minutes(1),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            auth: Budget::per_minute(30),
+            graphql: Budget::per_minute(600),
+            max_login_failures: 5,
+            lockout: TimeDelta::minutes(15),
+            client_ip_header: None,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads the config from `$RATE_LIMIT_AUTH_PER_MINUTE`, `$RATE_LIMIT_GRAPHQL_PER_MINUTE`,
+    /// `$LOGIN_MAX_FAILURES`, `$LOGIN_LOCKOUT_MINUTES` and `$CLIENT_IP_HEADER`, using the defaults
+    /// for the ones that are not set.
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        if let Some(requests) = parse_env("RATE_LIMIT_AUTH_PER_MINUTE")? {
+            config.auth = Budget::per_minute(requests);
+        }
+        if let Some(requests) = parse_env("RATE_LIMIT_GRAPHQL_PER_MINUTE")? {
+            config.graphql = Budget::per_minute(requests);
+        }
+        if let Some(max_login_failures) = parse_env("LOGIN_MAX_FAILURES")? {
+            config.max_login_failures = max_login_failures;
+        }
+        if let Some(minutes) = parse_env("LOGIN_LOCKOUT_MINUTES")? {
+            config.lockout = TimeDelta::minutes(minutes);
+        }
+        config.client_ip_header = std::env::var("CLIENT_IP_HEADER").ok();
+
+        Ok(config)
+    }
+
+    /// The longest window that requests or failed logins are counted in.
+    fn longest_window(&self) -> TimeDelta {
+        self.auth.window.max(self.graphql.window).max(self.lockout)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum RateLimitError {
+    #[error("rate limit exceeded, retry after {retry_after}")]
+    Limited { retry_after: TimeDelta },
+    #[error(transparent)]
+    Db(#[from] DbErr),
+}
+
+/// The error telling the client when it may retry, which is responded with a `429` status and a
+/// `Retry-After` header.
+pub(crate) fn too_many_requests(retry_after: TimeDelta) -> ApiError {
+    ApiError::new(ErrorCode::TooManyRequests, "Too many requests")
+        .with_retry_after(retry_after_secs(retry_after))
+}
+
+fn retry_after_secs(retry_after: TimeDelta) -> i64 {
+    // Round up, so that retrying right after the given time always succeeds.
+    ((retry_after.num_milliseconds() + 999) / 1000).max(1)
+}
+
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    db_conn: DatabaseConnection,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig, db_conn: DatabaseConnection) -> Self {
+        Self { config, db_conn }
+    }
+
+    /// Counts a request against the budget of the key, and fails if the budget is used up.
+    async fn hit(&self, key: &str, budget: Budget) -> Result<i32, RateLimitError> {
+        let now = Utc::now();
+        let window_start = window_start(now, budget.window);
+        // A counter from a previous window is reset rather than incremented.
+        let counter = entities::rate_limit_counters::Entity::insert(
+            entities::rate_limit_counters::ActiveModel {
+                key: Set(key.to_owned()),
+                window_start: Set(window_start.into()),
+                count: Set(1),
+            },
+        )
+        .on_conflict(
+            OnConflict::column(entities::rate_limit_counters::Column::Key)
+                .value(
+                    entities::rate_limit_counters::Column::Count,
+                    Expr::cust(
+                        "CASE WHEN rate_limit_counters.window_start = excluded.window_start \
+                         THEN rate_limit_counters.count + 1 ELSE 1 END",
+                    ),
+                )
+                .update_column(entities::rate_limit_counters::Column::WindowStart)
+                .to_owned(),
+        )
+        .exec_with_returning(&self.db_conn)
+        .await?;
+
+        if counter.count as u32 > budget.requests {
+            return Err(RateLimitError::Limited {
+                retry_after: window_start + budget.window - now,
+            });
+        }
+        Ok(counter.count)
+    }
+
+    async fn limit(&self, key: &str, budget: Budget, request: Request, next: Next) -> Response {
+        match self.hit(key, budget).await {
+            Ok(_) => next.run(request).await,
+            Err(RateLimitError::Limited { retry_after }) => {
+                warn!(key, "Rate limit exceeded");
+                too_many_requests(retry_after).into_response()
+            }
+            // Failing open keeps the service up when only the counters are broken.
+            Err(RateLimitError::Db(err)) => {
+                error!("Failed to update the rate limit counter: {err:?}");
+                next.run(request).await
+            }
+        }
+    }
+
+    /// Fails if the username is locked out for the client IP because of too many failed logins.
+    pub(crate) async fn check_login_lockout(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+    ) -> Result<(), RateLimitError> {
+        let now = Utc::now();
+        let lockout = entities::login_lockouts::Entity::find_by_id((
+            username.to_owned(),
+            client_ip_or_unknown(client_ip).to_owned(),
+        ))
+        .filter(entities::login_lockouts::Column::LockedUntil.gt(now))
+        .one(&self.db_conn)
+        .await?;
+        match lockout {
+            Some(lockout) => Err(RateLimitError::Limited {
+                retry_after: DateTime::<Utc>::from(lockout.locked_until) - now,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a failed login, locking out the username for the client IP once it has failed too
+    /// many times from there.
+    pub(crate) async fn record_login_failure(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+    ) -> Result<(), DbErr> {
+        let client_ip = client_ip_or_unknown(client_ip);
+        let key = login_failures_key(username, client_ip);
+        let budget = Budget {
+            requests: self.config.max_login_failures,
+            window: self.config.lockout,
+        };
+        match self.hit(&key, budget).await {
+            Ok(count) if count as u32 == self.config.max_login_failures => {}
+            Ok(_) => return Ok(()),
+            Err(RateLimitError::Limited { .. }) => {}
+            Err(RateLimitError::Db(err)) => return Err(err),
+        }
+
+        warn!(username, client_ip, "Too many failed logins, locking out");
+        entities::login_lockouts::Entity::insert(entities::login_lockouts::ActiveModel {
+            username: Set(username.to_owned()),
+            client_ip: Set(client_ip.to_owned()),
+            locked_until: Set((Utc::now() + self.config.lockout).into()),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                entities::login_lockouts::Column::Username,
+                entities::login_lockouts::Column::ClientIp,
+            ])
+            .update_column(entities::login_lockouts::Column::LockedUntil)
+            .to_owned(),
+        )
+        .exec(&self.db_conn)
+        .await?;
+        // Start counting afresh once the lockout is over.
+        entities::rate_limit_counters::Entity::delete_by_id(key)
+            .exec(&self.db_conn)
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn clear_login_failures(
+        &self,
+        username: &str,
+        client_ip: Option<&str>,
+    ) -> Result<(), DbErr> {
+        let key = login_failures_key(username, client_ip_or_unknown(client_ip));
+        entities::rate_limit_counters::Entity::delete_by_id(key)
+            .exec(&self.db_conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Lifts the lockouts of the username for all client IPs.
+    pub(crate) async fn lift_login_lockouts(&self, username: &str) -> Result<(), DbErr> {
+        entities::login_lockouts::Entity::delete_many()
+            .filter(entities::login_lockouts::Column::Username.eq(username))
+            .exec(&self.db_conn)
+            .await?;
+        Ok(())
+    }
+
+    /// The IP of the client, taken from the configured header if any, or from the connection.
+    ///
+    /// The header may hold a comma-separated list like `X-Forwarded-For`, whose first entries are
+    /// whatever the client sent, so the last one, appended by the proxy, is taken. A single-value
+    /// header like `Fly-Client-IP` is used as it is.
+    pub(crate) fn client_ip(&self, headers: &HeaderMap, extensions: &Extensions) -> Option<String> {
+        if let Some(header) = &self.config.client_ip_header {
+            if let Some(ip) = headers
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+            {
+                return Some(ip.trim().to_owned());
+            }
+        }
+        extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+    }
+}
+
+/// Deletes the counters of windows that are over, which are otherwise only reset when their key is
+/// hit again. Returns the number of deleted counters.
+pub async fn delete_expired_rate_limit_counters(
+    config: &RateLimitConfig,
+    db_conn: &DatabaseConnection,
+) -> Result<u64, DbErr> {
+    let result = entities::rate_limit_counters::Entity::delete_many()
+        .filter(
+            entities::rate_limit_counters::Column::WindowStart
+                .lt(Utc::now() - config.longest_window()),
+        )
+        .exec(db_conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Clients whose IP cannot be determined share the same counters and lockouts.
+fn client_ip_or_unknown(client_ip: Option<&str>) -> &str {
+    client_ip.unwrap_or("unknown")
+}
+
+fn login_failures_key(username: &str, client_ip: &str) -> String {
+    // An IP never contains a `/`, so the key cannot be made up by another username and IP.
+    format!("login-failures:{client_ip}/{username}")
+}
+
+/// Returns the start of the window of length `window` that `now` is in. Windows are aligned to the
+/// Unix epoch, so all instances agree on them.
+fn window_start(now: DateTime<Utc>, window: TimeDelta) -> DateTime<Utc> {
+    let window_ms = window.num_milliseconds().max(1);
+    let start_ms = now.timestamp_millis() - now.timestamp_millis().rem_euclid(window_ms);
+    DateTime::from_timestamp_millis(start_ms).unwrap_or(now)
+}
+
+/// Limits the requests to `/auth` by client IP.
+pub(crate) async fn limit_auth_by_ip(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = format!(
+        "auth-ip:{}",
+        client_ip_or_unknown(
+            limiter
+                .client_ip(request.headers(), request.extensions())
+                .as_deref()
+        )
+    );
+    limiter
+        .limit(&key, limiter.config.auth, request, next)
+        .await
+}
+
+/// Limits the requests to `/graphql` by user, falling back to the client IP for unauthenticated
+/// requests.
+pub(crate) async fn limit_graphql_by_user(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let key = match Principal::from_request_parts(&mut parts, &()).await {
+        Ok(principal) => format!("graphql-user:{}", principal.user_id()),
+        Err(_) => format!(
+            "graphql-ip:{}",
+            client_ip_or_unknown(
+                limiter
+                    .client_ip(&parts.headers, &parts.extensions)
+                    .as_deref()
+            )
+        ),
+    };
+    let request = Request::from_parts(parts, body);
+    limiter
+        .limit(&key, limiter.config.graphql, request, next)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use googletest::prelude::*;
+
+    use super::*;
+
+    #[googletest::test]
+    fn window_start_is_aligned_to_window() {
+        let now = Utc.with_ymd_and_hms(2024, 10, 18, 12, 34, 56).unwrap();
+
+        expect_eq!(
+            window_start(now, TimeDelta::minutes(1)),
+            Utc.with_ymd_and_hms(2024, 10, 18, 12, 34, 0).unwrap()
+        );
+        expect_eq!(
+            window_start(now, TimeDelta::minutes(15)),
+            Utc.with_ymd_and_hms(2024, 10, 18, 12, 30, 0).unwrap()
+        );
+    }
+
+    #[googletest::test]
+    fn retry_after_is_rounded_up_to_seconds() {
+        expect_eq!(retry_after_secs(TimeDelta::milliseconds(1500)), 2);
+        expect_eq!(retry_after_secs(TimeDelta::seconds(3)), 3);
+        expect_eq!(retry_after_secs(TimeDelta::zero()), 1);
+    }
+}
@@ -1,6 +1,31 @@
+use anyhow::Context;
+
 #[extend::ext(name = OptionExt)]
 pub impl<T> Option<T> {
     fn try_map<U, E, F: FnOnce(T) -> Result<U, E>>(self, f: F) -> Result<Option<U>, E> {
         self.map(f).transpose()
     }
 }
+
+/// Reads a boolean environment variable, treating an empty one as unset.
+pub(crate) fn parse_bool_env(name: &str) -> anyhow::Result<Option<bool>> {
+    match std::env::var(name) {
+        Ok(value) if !value.is_empty() => {
+            Ok(Some(value.parse().with_context(|| {
+                format!("${name} must be `true` or `false`")
+            })?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Reads and parses an environment variable.
+pub(crate) fn parse_env<T: std::str::FromStr>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    std::env::var(name)
+        .ok()
+        .map(|value| value.parse().with_context(|| format!("${name} is invalid")))
+        .transpose()
+}
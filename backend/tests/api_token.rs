@@ -0,0 +1,151 @@
+mod common;
+mod matchers;
+
+use common::{login_session, Result, TestServer, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const TASKS_QUERY: &str = "query { tasks { totalCount } }";
+const CREATE_TASK_MUTATION: &str = r#"mutation { createTask(input: { title: "Task" }) { id } }"#;
+const CREATE_API_TOKEN_MUTATION: &str = "
+    mutation($name: String!, $scopes: [Scope!]!) {
+        createApiToken(input: { name: $name, scopes: $scopes }) {
+            apiToken { id }
+            token
+        }
+    }";
+
+/// Creates an API token and returns its id and secret.
+async fn create_api_token(session: &UserSession, scopes: &[&str]) -> Result<(String, String)> {
+    let response = session
+        .graphql(
+            CREATE_API_TOKEN_MUTATION,
+            serde_json::json!({ "name": "cron", "scopes": scopes }),
+        )
+        .await?;
+    let created = &response["data"]["createApiToken"];
+    Ok((
+        created["apiToken"]["id"].as_str().unwrap().to_owned(),
+        created["token"].as_str().unwrap().to_owned(),
+    ))
+}
+
+async fn graphql_with_token(
+    server: &TestServer,
+    token: &str,
+    query: &str,
+) -> Result<serde_json::Value> {
+    Ok(server
+        .post("/graphql")
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "query": query }))
+        .send()
+        .await?
+        .json()
+        .await?)
+}
+
+fn error_code(response: &serde_json::Value) -> &serde_json::Value {
+    &response["errors"][0]["extensions"]["code"]
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn api_token_is_limited_to_its_scopes() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let (_, token) = create_api_token(&session, &["TASKS_READ"]).await?;
+
+    let response = graphql_with_token(session.server(), &token, TASKS_QUERY).await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(response["data"]["tasks"]["totalCount"], eq(0));
+
+    let response = graphql_with_token(session.server(), &token, CREATE_TASK_MUTATION).await?;
+    expect_that!(error_code(&response), json_string(eq("FORBIDDEN")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn api_token_with_write_scope_can_create_tasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let (_, token) = create_api_token(&session, &["TASKS_WRITE"]).await?;
+
+    let response = graphql_with_token(session.server(), &token, CREATE_TASK_MUTATION).await?;
+    expect_that!(
+        response["data"]["createTask"]["id"],
+        json_string(anything())
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn api_token_cannot_manage_credentials() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let (_, token) = create_api_token(&session, &["TASKS_READ", "TASKS_WRITE"]).await?;
+
+    let response = graphql_with_token(
+        session.server(),
+        &token,
+        r#"mutation { createApiToken(input: { name: "more", scopes: [TASKS_READ] }) { token } }"#,
+    )
+    .await?;
+    expect_that!(error_code(&response), json_string(eq("FORBIDDEN")));
+
+    let response =
+        graphql_with_token(session.server(), &token, "query { sessions { id } }").await?;
+    expect_that!(error_code(&response), json_string(eq("FORBIDDEN")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn api_tokens_are_listed_and_deleted_tokens_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let (id, token) = create_api_token(&session, &["TASKS_WRITE", "TASKS_READ"]).await?;
+    graphql_with_token(session.server(), &token, TASKS_QUERY).await?;
+
+    let response = session
+        .graphql(
+            "query { apiTokens { id name scopes lastUsedAt } }",
+            serde_json::json!({}),
+        )
+        .await?;
+    let api_token = &response["data"]["apiTokens"][0];
+    expect_that!(api_token["id"], json_string(eq(&id)));
+    expect_that!(api_token["name"], json_string(eq("cron")));
+    expect_that!(
+        api_token["scopes"],
+        eq(&serde_json::json!(["TASKS_READ", "TASKS_WRITE"]))
+    );
+    expect_that!(api_token["lastUsedAt"], json_string(anything()));
+
+    let response = session
+        .graphql(
+            "mutation($id: UUID!) { deleteApiToken(id: $id) }",
+            serde_json::json!({ "id": id }),
+        )
+        .await?;
+    expect_that!(response["data"]["deleteApiToken"], json_string(eq(&id)));
+
+    let response = graphql_with_token(session.server(), &token, TASKS_QUERY).await?;
+    expect_that!(error_code(&response), json_string(eq("TOKEN_INVALID")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn unknown_api_token_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = graphql_with_token(session.server(), "pat_unknown", TASKS_QUERY).await?;
+    expect_that!(error_code(&response), json_string(eq("TOKEN_INVALID")));
+    Ok(())
+}
@@ -0,0 +1,375 @@
+mod common;
+mod matchers;
+
+use common::{
+    insert_test_user, login_session, Result, TestServer, UserSession, TEST_PASSWORD, TEST_USERNAME,
+    TEST_USER_UUID,
+};
+use googletest::prelude::*;
+use planner_backend::{create_invite_code, entities};
+use reqwest::StatusCode;
+use sea_orm::{ActiveModelTrait, Set};
+use testlib::PgDocker;
+
+use crate::matchers::json_string;
+
+async fn login(server: &TestServer, username: &str, password: &str) -> Result<StatusCode> {
+    Ok(server
+        .post("/auth/login")
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .send()
+        .await?
+        .status())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_with_correct_password_succeeds() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        login(&server, TEST_USERNAME, TEST_PASSWORD).await?,
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_with_wrong_password_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        login(&server, TEST_USERNAME, "wrong-password").await?,
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_with_unknown_user_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        login(&server, "nobody", TEST_PASSWORD).await?,
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_for_user_without_password_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    entities::users::ActiveModel {
+        id: Set(TEST_USER_UUID),
+        username: Set(TEST_USERNAME.to_owned()),
+        password_hash: Set(None),
+        ..Default::default()
+    }
+    .insert(pg_docker.db_conn())
+    .await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        login(&server, TEST_USERNAME, "").await?,
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+async fn register(
+    server: &TestServer,
+    username: &str,
+    password: &str,
+    invite_code: &str,
+) -> Result<StatusCode> {
+    Ok(server
+        .post("/auth/register")
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password,
+            "invite_code": invite_code,
+        }))
+        .send()
+        .await?
+        .status())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn register_with_invite_code_creates_user_that_can_login() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let invite_code = create_invite_code(pg_docker.db_conn()).await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        register(&server, TEST_USERNAME, TEST_PASSWORD, &invite_code).await?,
+        eq(StatusCode::OK)
+    );
+    expect_that!(
+        login(&server, TEST_USERNAME, TEST_PASSWORD).await?,
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn register_with_used_invite_code_is_forbidden() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let invite_code = create_invite_code(pg_docker.db_conn()).await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+    register(&server, TEST_USERNAME, TEST_PASSWORD, &invite_code).await?;
+
+    expect_that!(
+        register(&server, "meteor2", TEST_PASSWORD, &invite_code).await?,
+        eq(StatusCode::FORBIDDEN)
+    );
+    expect_that!(
+        login(&server, "meteor2", TEST_PASSWORD).await?,
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn register_with_unknown_invite_code_is_forbidden() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        register(&server, TEST_USERNAME, TEST_PASSWORD, "not-a-code").await?,
+        eq(StatusCode::FORBIDDEN)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn register_with_existing_username_is_conflict_and_keeps_invite_code() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let invite_code = create_invite_code(pg_docker.db_conn()).await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    expect_that!(
+        register(&server, TEST_USERNAME, "another-password", &invite_code).await?,
+        eq(StatusCode::CONFLICT)
+    );
+    expect_that!(
+        register(&server, "meteor2", TEST_PASSWORD, &invite_code).await?,
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+async fn refresh(server: &TestServer, refresh_token: &str) -> Result<reqwest::Response> {
+    Ok(server
+        .post("/auth/refresh")
+        .json(&serde_json::json!({ "refresh_token": refresh_token }))
+        .send()
+        .await?)
+}
+
+const SESSIONS_QUERY: &str = "query { sessions { id isCurrent } }";
+
+#[googletest::test]
+#[tokio::test]
+async fn refresh_rotates_the_refresh_token() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = refresh(session.server(), session.refresh_token()).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["token"].as_str(), some(anything()));
+    expect_that!(
+        body["refresh_token"].as_str(),
+        some(not(eq(session.refresh_token())))
+    );
+
+    expect_that!(
+        refresh(session.server(), session.refresh_token())
+            .await?
+            .status(),
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn logout_revokes_access_and_refresh_tokens() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    expect_that!(
+        session
+            .graphql(SESSIONS_QUERY, serde_json::json!({}))
+            .await?["data"]["sessions"]
+            .as_array()
+            .map(Vec::len),
+        some(eq(1))
+    );
+
+    let response = session
+        .server()
+        .post("/auth/logout")
+        .json(&serde_json::json!({ "refresh_token": session.refresh_token() }))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::NO_CONTENT));
+
+    expect_that!(
+        session
+            .graphql(SESSIONS_QUERY, serde_json::json!({}))
+            .await?["errors"][0]["extensions"]["code"],
+        json_string(eq("TOKEN_INVALID"))
+    );
+    expect_that!(
+        refresh(session.server(), session.refresh_token())
+            .await?
+            .status(),
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn revoke_session_logs_out_the_other_session() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let other_session =
+        UserSession::login_as(session.server().clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+
+    let sessions = other_session
+        .graphql(SESSIONS_QUERY, serde_json::json!({}))
+        .await?;
+    let sessions = sessions["data"]["sessions"].as_array().unwrap();
+    expect_that!(sessions.len(), eq(2));
+    let session_id = sessions
+        .iter()
+        .find(|s| s["isCurrent"] == false)
+        .map(|s| s["id"].clone())
+        .unwrap();
+
+    let response = other_session
+        .graphql(
+            "mutation($id: UUID!) { revokeSession(id: $id) }",
+            serde_json::json!({ "id": session_id }),
+        )
+        .await?;
+    expect_that!(response["data"]["revokeSession"], eq(&session_id));
+
+    expect_that!(
+        session
+            .graphql(SESSIONS_QUERY, serde_json::json!({}))
+            .await?["errors"][0]["extensions"]["code"],
+        json_string(eq("TOKEN_INVALID"))
+    );
+    expect_that!(
+        other_session
+            .graphql(SESSIONS_QUERY, serde_json::json!({}))
+            .await?["data"]["sessions"]
+            .as_array()
+            .map(Vec::len),
+        some(eq(1))
+    );
+    Ok(())
+}
+
+async fn graphql_with_header(
+    server: &TestServer,
+    authorization: Option<&str>,
+) -> Result<(Option<String>, serde_json::Value)> {
+    let mut request = server.post("/graphql");
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let response = request
+        .json(&serde_json::json!({ "query": SESSIONS_QUERY }))
+        .send()
+        .await?;
+    let www_authenticate = response
+        .headers()
+        .get("WWW-Authenticate")
+        .map(|value| value.to_str().unwrap().to_owned());
+    Ok((www_authenticate, response.json().await?))
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn graphql_without_token_reports_missing_token() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    let (www_authenticate, body) = graphql_with_header(&server, None).await?;
+    expect_that!(www_authenticate, some(eq(r#"Bearer realm="planner""#)));
+    expect_that!(
+        body["errors"][0]["extensions"]["code"],
+        json_string(eq("UNAUTHORIZED"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn graphql_with_malformed_token_reports_malformed_token() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    let (www_authenticate, body) = graphql_with_header(&server, Some("Bearer not-a-jwt")).await?;
+    expect_that!(
+        www_authenticate,
+        some(contains_substring(r#"error="invalid_token""#))
+    );
+    expect_that!(
+        body["errors"][0]["extensions"]["code"],
+        json_string(eq("TOKEN_MALFORMED"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn graphql_with_valid_token_has_no_authenticate_header() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .post("/graphql")
+        .json(&serde_json::json!({ "query": SESSIONS_QUERY }))
+        .send()
+        .await?;
+    expect_that!(response.headers().get("WWW-Authenticate"), none());
+    Ok(())
+}
@@ -0,0 +1,193 @@
+mod common;
+mod matchers;
+
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const MISSING_TASK_UUID: Uuid = test_uuid(99);
+
+const BULK_UPDATE_MUTATION: &str = "
+    mutation($inputs: [UpdateTaskInput!]!, $allOrNothing: Boolean! = false) {
+        bulkUpdateTasks(inputs: $inputs, allOrNothing: $allOrNothing) {
+            taskId task { title } error
+        }
+    }";
+const TASK_QUERY: &str = "
+    query($id: UUID!) {
+        task(id: $id) { title isCompleted scheduledOn { type date } }
+    }";
+
+async fn task(session: &UserSession, id: &str) -> Result<serde_json::Value> {
+    let response = session
+        .graphql(TASK_QUERY, serde_json::json!({ "id": id }))
+        .await?;
+    Ok(response["data"]["task"].clone())
+}
+
+fn date_epoch(date: &str) -> serde_json::Value {
+    serde_json::json!({ "type": "DATE", "date": date })
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn bulk_updates_report_the_result_of_each_task() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let task_id = create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+
+    let response = session
+        .graphql(
+            BULK_UPDATE_MUTATION,
+            serde_json::json!({ "inputs": [
+                { "id": task_id, "title": "Renamed" },
+                { "id": MISSING_TASK_UUID, "title": "Missing" },
+            ] }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let results = &response["data"]["bulkUpdateTasks"];
+    expect_that!(results[0]["task"]["title"], json_string(eq("Renamed")));
+    expect_that!(results[0]["error"], json_null());
+    expect_that!(
+        results[1]["taskId"],
+        json_string(eq(&MISSING_TASK_UUID.to_string()))
+    );
+    expect_that!(results[1]["task"], json_null());
+    expect_that!(results[1]["error"], json_string(ends_with("is not found")));
+    expect_that!(
+        task(&session, &task_id).await?["title"],
+        json_string(eq("Renamed"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn all_or_nothing_bulk_updates_are_rolled_back_on_failure() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let task_id = create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+
+    let response = session
+        .graphql(
+            BULK_UPDATE_MUTATION,
+            serde_json::json!({
+                "inputs": [
+                    { "id": task_id, "title": "Renamed" },
+                    { "id": MISSING_TASK_UUID, "title": "Missing" },
+                ],
+                "allOrNothing": true,
+            }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["message"],
+        json_string(ends_with("is not found"))
+    );
+    expect_that!(
+        task(&session, &task_id).await?["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_can_be_completed_and_deleted_in_bulk() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let first_id = create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+    let second_id = create_task(&session, serde_json::json!({ "title": "Laundry" })).await?;
+
+    let response = session
+        .graphql(
+            "mutation($ids: [UUID!]!) {
+                bulkCompleteTasks(ids: $ids, completeDate: \"2024-03-04\") { task { isCompleted } }
+            }",
+            serde_json::json!({ "ids": [first_id, second_id] }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(
+        response["data"]["bulkCompleteTasks"][1]["task"]["isCompleted"],
+        eq(&true)
+    );
+
+    let response = session
+        .graphql(
+            "mutation($ids: [UUID!]!) {
+                bulkDeleteTasks(ids: $ids) { taskId task { title } error }
+            }",
+            serde_json::json!({ "ids": [first_id, second_id] }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(response["data"]["bulkDeleteTasks"][0]["error"], json_null());
+    let response = session
+        .graphql("query { tasks { totalCount } }", serde_json::json!({}))
+        .await?;
+    expect_that!(response["data"]["tasks"]["totalCount"], eq(0));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn incomplete_tasks_are_moved_between_epochs() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let from = date_epoch("2024-03-04");
+    let to = date_epoch("2024-03-05");
+    let incomplete_id = create_task(
+        &session,
+        serde_json::json!({ "title": "Groceries", "scheduledOn": from.clone() }),
+    )
+    .await?;
+    let completed_id = create_task(
+        &session,
+        serde_json::json!({ "title": "Laundry", "scheduledOn": from.clone() }),
+    )
+    .await?;
+    let other_id = create_task(
+        &session,
+        serde_json::json!({ "title": "Dishes", "scheduledOn": date_epoch("2024-03-06") }),
+    )
+    .await?;
+    session
+        .graphql(
+            "mutation($id: UUID!) {
+                updateTask(input: { id: $id, completeDate: \"2024-03-04\" }) { id }
+            }",
+            serde_json::json!({ "id": completed_id }),
+        )
+        .await?;
+
+    let response = session
+        .graphql(
+            "mutation($from: InputEpoch!, $to: InputEpoch!) {
+                moveTasks(from: $from, to: $to) { taskId error }
+            }",
+            serde_json::json!({ "from": from, "to": to }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let moved = &response["data"]["moveTasks"];
+    expect_that!(moved.as_array().map(Vec::len), some(eq(1)));
+    expect_that!(moved[0]["taskId"], json_string(eq(&incomplete_id)));
+    expect_that!(
+        task(&session, &incomplete_id).await?["scheduledOn"],
+        eq(&to)
+    );
+    expect_that!(
+        task(&session, &completed_id).await?["scheduledOn"],
+        eq(&from)
+    );
+    expect_that!(
+        task(&session, &other_id).await?["scheduledOn"],
+        eq(&date_epoch("2024-03-06"))
+    );
+    Ok(())
+}
@@ -1,12 +1,77 @@
+#![allow(dead_code)]
+
 use std::net::SocketAddr;
 
-use planner_backend::build_app;
+use anyhow::Context;
+use planner_backend::{build_app, entities, hash_password, Config};
 use reqwest::RequestBuilder;
-use sea_orm::DatabaseConnection;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use testlib::{test_uuid, PgDocker};
 use tokio::net::TcpListener;
+use uuid::Uuid;
 
 pub type Result<T> = anyhow::Result<T>;
 
+pub const TEST_USERNAME: &str = "meteor";
+pub const TEST_PASSWORD: &str = "meteor-password";
+pub const TEST_USER_UUID: Uuid = test_uuid(1);
+
+const CREATE_TASK_MUTATION: &str = "
+    mutation($input: CreateTaskInput!) {
+        createTask(input: $input) { taskId }
+    }";
+
+pub async fn insert_test_user(
+    id: Uuid,
+    username: &str,
+    password: &str,
+    db_conn: &DatabaseConnection,
+) -> Result<()> {
+    entities::users::ActiveModel {
+        id: Set(id),
+        username: Set(username.to_owned()),
+        password_hash: Set(Some(hash_password(password)?)),
+        ..Default::default()
+    }
+    .insert(db_conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts the test user and logs in as them on a new server.
+pub async fn login_session(pg_docker: &PgDocker) -> Result<UserSession> {
+    login_session_with_config(pg_docker, Config::default()).await
+}
+
+/// Inserts the test user and logs in as them on a new server with the config.
+pub async fn login_session_with_config(
+    pg_docker: &PgDocker,
+    config: Config,
+) -> Result<UserSession> {
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let server = TestServer::spawn_with_config(pg_docker.db_conn().clone(), config).await;
+    UserSession::login_as(server, TEST_USERNAME, TEST_PASSWORD).await
+}
+
+/// Creates a task from the `CreateTaskInput` and returns its `taskId`.
+pub async fn create_task(session: &UserSession, input: serde_json::Value) -> Result<String> {
+    let response = session
+        .graphql(CREATE_TASK_MUTATION, serde_json::json!({ "input": input }))
+        .await?;
+    Ok(response["data"]["createTask"]["taskId"]
+        .as_str()
+        .with_context(|| format!("failed to create the task: {response}"))?
+        .to_owned())
+}
+
+#[derive(Clone)]
 pub struct TestServer {
     addr: SocketAddr,
     client: reqwest::Client,
@@ -14,17 +79,29 @@ pub struct TestServer {
 
 impl TestServer {
     pub async fn spawn(db_conn: DatabaseConnection) -> Self {
-        let router = build_app(db_conn).await;
+        Self::spawn_with_config(db_conn, Config::default()).await
+    }
+
+    pub async fn spawn_with_config(db_conn: DatabaseConnection, config: Config) -> Self {
+        // The tests sign their tokens with a random JWT secret.
+        let config = Config {
+            jwt_allow_ephemeral: true,
+            ..config
+        };
+        let router = build_app(db_conn, config).await;
         let listener = TcpListener::bind("127.0.0.1:0")
             .await
             .expect("Cannot bind to 127.0.0.1:0 (dynamic port)");
         let addr = listener.local_addr().unwrap();
         tokio::spawn(async {
-            axum::serve(listener, router.into_make_service())
-                // .expect("Cannot create Axum server from listener")
-                // .serve(router.into_make_service())
-                .await
-                .expect("Server failed");
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            // .expect("Cannot create Axum server from listener")
+            // .serve(router.into_make_service())
+            .await
+            .expect("Server failed");
         });
 
         Self {
@@ -57,35 +134,65 @@ impl TestServer {
 pub struct UserSession {
     server: TestServer,
     login_token: String,
+    refresh_token: String,
 }
 
 impl UserSession {
-    pub async fn login_as(server: TestServer, username: &str) -> Result<Self> {
+    pub async fn login_as(server: TestServer, username: &str, password: &str) -> Result<Self> {
         #[derive(serde::Serialize)]
         struct LoginRequest<'a> {
             username: &'a str,
+            password: &'a str,
         }
 
         #[derive(serde::Deserialize)]
         pub struct LoginResponse {
             pub token: String,
+            pub refresh_token: String,
         }
 
         let response: LoginResponse = server
             .post("/auth/login")
-            .json(&LoginRequest { username })
+            .json(&LoginRequest { username, password })
             .send()
             .await?
+            .error_for_status()?
             .json()
             .await?;
 
-        let login_token = response.token;
         Ok(Self {
             server,
-            login_token,
+            login_token: response.token,
+            refresh_token: response.refresh_token,
         })
     }
 
+    pub fn login_token(&self) -> &str {
+        &self.login_token
+    }
+
+    pub fn refresh_token(&self) -> &str {
+        &self.refresh_token
+    }
+
+    /// Sends a GraphQL request as this user and returns the response JSON.
+    pub async fn graphql(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        Ok(self
+            .post("/graphql")
+            .json(&serde_json::json!({
+                "query": query,
+                "variables": variables,
+            }))
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+
     pub fn get(&self, path: &str) -> RequestBuilder {
         self.server
             .get(path)
@@ -0,0 +1,174 @@
+mod common;
+mod matchers;
+
+use common::{insert_test_user, Result, TestServer, TEST_PASSWORD, TEST_USERNAME, TEST_USER_UUID};
+use googletest::prelude::*;
+use planner_backend::{Config, CookieConfig};
+use reqwest::{header, StatusCode};
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const CREATE_TASK_MUTATION: &str =
+    "mutation { createTask(input: { title: \"Groceries\" }) { id } }";
+
+/// A browser-like client that keeps the cookies set by the server.
+struct CookieClient {
+    server: TestServer,
+    client: reqwest::Client,
+}
+
+impl CookieClient {
+    async fn login(pg_docker: &PgDocker) -> Result<(Self, reqwest::Response)> {
+        insert_test_user(
+            TEST_USER_UUID,
+            TEST_USERNAME,
+            TEST_PASSWORD,
+            pg_docker.db_conn(),
+        )
+        .await?;
+        let server = TestServer::spawn_with_config(
+            pg_docker.db_conn().clone(),
+            Config {
+                // The test server is plain HTTP.
+                session_cookies: Some(CookieConfig { secure: false }),
+                ..Default::default()
+            },
+        )
+        .await;
+        let client = Self {
+            server,
+            client: reqwest::Client::builder().cookie_store(true).build()?,
+        };
+
+        let response = client
+            .post("/auth/login")
+            .json(&serde_json::json!({
+                "username": TEST_USERNAME,
+                "password": TEST_PASSWORD,
+            }))
+            .send()
+            .await?;
+        Ok((client, response))
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.post(self.server.url_of(path))
+    }
+
+    async fn graphql(&self, query: &str, csrf_token: Option<&str>) -> Result<reqwest::Response> {
+        let mut request = self
+            .post("/graphql")
+            .json(&serde_json::json!({ "query": query }));
+        if let Some(csrf_token) = csrf_token {
+            request = request.header("X-CSRF-Token", csrf_token);
+        }
+        Ok(request.send().await?)
+    }
+}
+
+fn csrf_token(body: &serde_json::Value) -> String {
+    body["csrf_token"].as_str().unwrap().to_owned()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_sets_http_only_cookies_instead_of_returning_tokens() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (_, response) = CookieClient::login(&pg_docker).await?;
+
+    let cookies: Vec<_> = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_owned())
+        .collect();
+    expect_that!(
+        cookies,
+        contains_each![
+            all![
+                starts_with("planner_access_token="),
+                contains_substring("HttpOnly"),
+                contains_substring("SameSite=Strict")
+            ],
+            all![
+                starts_with("planner_refresh_token="),
+                contains_substring("HttpOnly"),
+                contains_substring("Path=/auth")
+            ],
+            all![
+                starts_with("planner_csrf_token="),
+                not(contains_substring("HttpOnly"))
+            ],
+        ]
+    );
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["token"], json_null());
+    expect_that!(body["refresh_token"], json_null());
+    expect_that!(body["csrf_token"], json_string(anything()));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn cookie_requests_need_the_csrf_token() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (client, response) = CookieClient::login(&pg_docker).await?;
+    let csrf_token = csrf_token(&response.json().await?);
+
+    let response = client.graphql(CREATE_TASK_MUTATION, None).await?;
+    expect_that!(response.status(), eq(StatusCode::FORBIDDEN));
+    let response = client
+        .graphql(CREATE_TASK_MUTATION, Some("forged-token"))
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::FORBIDDEN));
+
+    let response: serde_json::Value = client
+        .graphql(CREATE_TASK_MUTATION, Some(&csrf_token))
+        .await?
+        .json()
+        .await?;
+    expect_that!(
+        response["data"]["createTask"]["id"],
+        json_string(anything())
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn refresh_and_logout_use_the_refresh_token_cookie() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (client, _) = CookieClient::login(&pg_docker).await?;
+
+    let response = client
+        .post("/auth/refresh")
+        .json(&serde_json::json!({}))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    let csrf_token = csrf_token(&response.json().await?);
+    let response: serde_json::Value = client
+        .graphql("{ tasks { totalCount } }", Some(&csrf_token))
+        .await?
+        .json()
+        .await?;
+    expect_that!(response["errors"], json_null());
+
+    let response = client
+        .post("/auth/logout")
+        .json(&serde_json::json!({}))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::NO_CONTENT));
+    let response: serde_json::Value = client
+        .graphql("{ tasks { totalCount } }", Some(&csrf_token))
+        .await?
+        .json()
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["code"],
+        json_string(eq("UNAUTHORIZED"))
+    );
+    Ok(())
+}
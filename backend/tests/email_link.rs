@@ -0,0 +1,197 @@
+mod common;
+mod matchers;
+mod mock_smtp;
+
+use common::{insert_test_user, Result, TestServer, UserSession};
+use googletest::prelude::*;
+use mock_smtp::{MockSmtpServer, APP_URL};
+use planner_backend::{delete_expired_email_tokens, set_user_email, Config};
+use reqwest::StatusCode;
+use sea_orm::ConnectionTrait;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::json_string;
+
+const TEST_USERNAME: &str = "meteor";
+const TEST_PASSWORD: &str = "meteor-password";
+const TEST_EMAIL: &str = "meteor@example.com";
+const TEST_USER_UUID: Uuid = test_uuid(1);
+
+async fn spawn_server(pg_docker: &PgDocker, mock_smtp: &MockSmtpServer) -> Result<TestServer> {
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    set_user_email(TEST_USERNAME, Some(TEST_EMAIL), pg_docker.db_conn()).await?;
+    Ok(TestServer::spawn_with_config(
+        pg_docker.db_conn().clone(),
+        Config {
+            smtp: Some(mock_smtp.config()),
+            ..Default::default()
+        },
+    )
+    .await)
+}
+
+async fn request_link(server: &TestServer, path: &str, email: &str) -> Result<StatusCode> {
+    Ok(server
+        .post(path)
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await?
+        .status())
+}
+
+async fn login_with_link(server: &TestServer, token: &str) -> Result<reqwest::Response> {
+    Ok(server
+        .post("/auth/magic-link/login")
+        .json(&serde_json::json!({ "token": token }))
+        .send()
+        .await?)
+}
+
+async fn reset_password(server: &TestServer, token: &str, password: &str) -> Result<StatusCode> {
+    Ok(server
+        .post("/auth/password-reset/confirm")
+        .json(&serde_json::json!({ "token": token, "password": password }))
+        .send()
+        .await?
+        .status())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn magic_link_logs_in_once() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_smtp = MockSmtpServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_smtp).await?;
+
+    let status = request_link(&server, "/auth/magic-link", "Meteor@Example.com").await?;
+    expect_that!(status, eq(StatusCode::ACCEPTED));
+    let email = mock_smtp.next_email().await;
+    expect_that!(email.recipients, elements_are![eq(TEST_EMAIL)]);
+    expect_that!(
+        email.body(),
+        contains_substring(format!("{APP_URL}/login/magic-link?token="))
+    );
+
+    let response = login_with_link(&server, &email.link_token()).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["token"], json_string(anything()));
+
+    let response = login_with_link(&server, &email.link_token()).await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn only_the_latest_link_works() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_smtp = MockSmtpServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_smtp).await?;
+
+    request_link(&server, "/auth/magic-link", TEST_EMAIL).await?;
+    let first = mock_smtp.next_email().await;
+    request_link(&server, "/auth/magic-link", TEST_EMAIL).await?;
+    let second = mock_smtp.next_email().await;
+
+    let response = login_with_link(&server, &first.link_token()).await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    let response = login_with_link(&server, &second.link_token()).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn expired_links_are_deleted() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_smtp = MockSmtpServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_smtp).await?;
+
+    request_link(&server, "/auth/magic-link", TEST_EMAIL).await?;
+    mock_smtp.next_email().await;
+    pg_docker
+        .db_conn()
+        .execute_unprepared("UPDATE email_tokens SET expires_at = now() - interval '1 minute'")
+        .await?;
+    request_link(&server, "/auth/password-reset", TEST_EMAIL).await?;
+    let email = mock_smtp.next_email().await;
+
+    expect_that!(
+        delete_expired_email_tokens(pg_docker.db_conn()).await?,
+        eq(1)
+    );
+    let status = reset_password(&server, &email.link_token(), "new-password").await?;
+    expect_that!(status, eq(StatusCode::NO_CONTENT));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn unknown_addresses_get_the_same_response() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_smtp = MockSmtpServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_smtp).await?;
+
+    let status = request_link(&server, "/auth/password-reset", "comet@example.com").await?;
+    expect_that!(status, eq(StatusCode::ACCEPTED));
+    let status = request_link(&server, "/auth/password-reset", "not an address").await?;
+    expect_that!(status, eq(StatusCode::BAD_REQUEST));
+
+    // Nothing was sent for the unknown address, so the next email is the one for the known one.
+    request_link(&server, "/auth/password-reset", TEST_EMAIL).await?;
+    expect_that!(
+        mock_smtp.next_email().await.recipients,
+        elements_are![eq(TEST_EMAIL)]
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn password_reset_replaces_the_password_and_ends_sessions() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_smtp = MockSmtpServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_smtp).await?;
+    let session = UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+
+    request_link(&server, "/auth/password-reset", TEST_EMAIL).await?;
+    let email = mock_smtp.next_email().await;
+    expect_that!(
+        email.body(),
+        contains_substring(format!("{APP_URL}/reset-password?token="))
+    );
+    let status = reset_password(&server, &email.link_token(), "short").await?;
+    expect_that!(status, eq(StatusCode::BAD_REQUEST));
+    let status = reset_password(&server, &email.link_token(), "new-password").await?;
+    expect_that!(status, eq(StatusCode::NO_CONTENT));
+
+    let status = reset_password(&server, &email.link_token(), "another-password").await?;
+    expect_that!(status, eq(StatusCode::UNAUTHORIZED));
+    expect_that!(
+        session
+            .graphql("{ tasks { totalCount } }", serde_json::json!({}))
+            .await?["errors"][0]["extensions"]["code"],
+        json_string(eq("TOKEN_INVALID"))
+    );
+    expect_that!(
+        UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD)
+            .await
+            .map(drop),
+        err(anything())
+    );
+    expect_that!(
+        UserSession::login_as(server, TEST_USERNAME, "new-password")
+            .await
+            .map(drop),
+        ok(anything())
+    );
+    Ok(())
+}
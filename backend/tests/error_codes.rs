@@ -0,0 +1,97 @@
+mod common;
+mod matchers;
+
+use common::{login_session, Result, TEST_USERNAME};
+use googletest::prelude::*;
+use reqwest::StatusCode;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const MISSING_TASK_UUID: Uuid = test_uuid(99);
+
+#[googletest::test]
+#[tokio::test]
+async fn missing_resources_are_reported_with_their_type() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql(
+            "query($id: UUID!) { task(id: $id) { title } }",
+            serde_json::json!({ "id": MISSING_TASK_UUID }),
+        )
+        .await?;
+    let extensions = &response["errors"][0]["extensions"];
+    expect_that!(extensions["code"], json_string(eq("NOT_FOUND")));
+    expect_that!(extensions["resource"], json_string(eq("Task")));
+    expect_that!(extensions["requestId"], json_string(not(eq(""))));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn invalid_inputs_are_reported_with_the_field() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql(
+            "mutation { createTask(input: { title: \"  \" }) { taskId } }",
+            serde_json::json!({}),
+        )
+        .await?;
+    let extensions = &response["errors"][0]["extensions"];
+    expect_that!(extensions["code"], json_string(eq("INVALID_INPUT")));
+    expect_that!(extensions["field"], eq(&serde_json::json!(["title"])));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn queries_not_matching_the_schema_are_bad_requests() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql("query { noSuchField }", serde_json::json!({}))
+        .await?;
+    expect_that!(response["data"], json_null());
+    expect_that!(
+        response["errors"][0]["extensions"]["code"],
+        json_string(eq("BAD_REQUEST"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn http_errors_are_json_with_the_request_id() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .server()
+        .post("/auth/login")
+        .header("x-request-id", "test-request")
+        .json(&serde_json::json!({
+            "username": TEST_USERNAME,
+            "password": "wrong-password",
+        }))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    expect_that!(
+        response
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok()),
+        some(eq("test-request"))
+    );
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["code"], json_string(eq("INVALID_CREDENTIALS")));
+    expect_that!(body["message"], json_string(eq("Invalid credentials")));
+    expect_that!(body["request_id"], json_string(eq("test-request")));
+    Ok(())
+}
@@ -0,0 +1,178 @@
+mod common;
+mod matchers;
+
+use common::{login_session, login_session_with_config, Result, UserSession};
+use googletest::prelude::*;
+use planner_backend::{Config, GraphqlConfig};
+use reqwest::StatusCode;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+/// The introspection query of GraphiQL and the frontend codegen, as built by `graphql-js`.
+const INTROSPECTION_QUERY: &str = "
+    query IntrospectionQuery {
+        __schema {
+            queryType { name }
+            mutationType { name }
+            subscriptionType { name }
+            types { ...FullType }
+            directives { name description locations args { ...InputValue } }
+        }
+    }
+    fragment FullType on __Type {
+        kind name description
+        fields(includeDeprecated: true) {
+            name description
+            args { ...InputValue }
+            type { ...TypeRef }
+            isDeprecated deprecationReason
+        }
+        inputFields { ...InputValue }
+        interfaces { ...TypeRef }
+        enumValues(includeDeprecated: true) { name description isDeprecated deprecationReason }
+        possibleTypes { ...TypeRef }
+    }
+    fragment InputValue on __InputValue {
+        name description type { ...TypeRef } defaultValue
+    }
+    fragment TypeRef on __Type {
+        kind name
+        ofType { kind name ofType { kind name ofType { kind name ofType { kind name
+            ofType { kind name ofType { kind name ofType { kind name ofType { kind name } } } }
+        } } } }
+    }";
+
+async fn expect_limit_exceeded(session: &UserSession, query: &str, message: &str) -> Result<()> {
+    let response = session.graphql(query, serde_json::json!({})).await?;
+    expect_that!(response["data"], json_null());
+    expect_that!(response["errors"][0]["message"], json_string(eq(message)));
+    expect_that!(
+        response["errors"][0]["extensions"]["code"],
+        json_string(eq("QUERY_LIMIT_EXCEEDED"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn introspection_is_within_the_default_limits() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session_with_config(
+        &pg_docker,
+        Config {
+            graphql: GraphqlConfig {
+                introspection: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let response = session
+        .graphql(INTROSPECTION_QUERY, serde_json::json!({}))
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(
+        response["data"]["__schema"]["queryType"]["name"],
+        json_string(eq("QueryRoot"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn queries_over_the_limits_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session_with_config(
+        &pg_docker,
+        Config {
+            graphql: GraphqlConfig {
+                max_depth: 3,
+                max_complexity: 4,
+                max_aliases: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    expect_limit_exceeded(
+        &session,
+        "query { tasks { edges { node { title } } } }",
+        "Query is nested 4 deep, more than the limit of 3",
+    )
+    .await?;
+    expect_limit_exceeded(
+        &session,
+        "query { tasks { totalCount pageInfo { hasNextPage hasPreviousPage } } }",
+        "Query has a complexity of 5, more than the limit of 4",
+    )
+    .await?;
+    expect_limit_exceeded(
+        &session,
+        "query { a: tasks { totalCount } b: tasks { totalCount } }",
+        "Query has 2 aliases, more than the limit of 1",
+    )
+    .await?;
+
+    let response = session
+        .graphql("query { tasks { totalCount } }", serde_json::json!({}))
+        .await?;
+    expect_that!(response["errors"], json_null());
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn request_bodies_over_the_limit_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session_with_config(
+        &pg_docker,
+        Config {
+            graphql: GraphqlConfig {
+                max_body_bytes: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let response = session
+        .post("/graphql")
+        .json(&serde_json::json!({
+            "query": format!("query {{ tasks {{ totalCount }} }} # {}", "x".repeat(100)),
+        }))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::PAYLOAD_TOO_LARGE));
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["code"], json_string(eq("PAYLOAD_TOO_LARGE")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn introspection_and_graphiql_are_off_by_default() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql(
+            "query { __schema { queryType { name } } }",
+            serde_json::json!({}),
+        )
+        .await?;
+    expect_that!(response["data"], json_null());
+    expect_that!(
+        response["errors"][0]["message"],
+        json_string(eq("Introspection is disabled"))
+    );
+
+    let response = session.server().get("/graphql").send().await?;
+    expect_that!(response.status(), eq(StatusCode::METHOD_NOT_ALLOWED));
+    Ok(())
+}
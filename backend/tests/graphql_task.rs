@@ -1,43 +1,28 @@
 mod common;
 mod matchers;
 
-use common::{Result, TestServer, UserSession};
-use googletest::prelude::*;
-
-use planner_backend::entities;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use common::{insert_test_user, Result, TestServer, UserSession};
 use testlib::{test_uuid, PgDocker};
 use uuid::Uuid;
 
-use crate::matchers::{json_null, json_string, json_u64, uuid_str};
-
 const TEST_USERNAME: &str = "meteor";
+const TEST_PASSWORD: &str = "meteor-password";
 const TEST_USER_UUID: Uuid = test_uuid(1);
 
-async fn insert_test_user(id: Uuid, username: String, db_conn: &DatabaseConnection) -> Result<()> {
-    entities::users::ActiveModel {
-        id: Set(id),
-        username: Set(username),
-    }
-    .insert(db_conn)
-    .await?;
-
-    Ok(())
-}
-
 #[googletest::test]
 #[tokio::test]
 async fn user_can_login() -> Result<()> {
     let pg_docker = PgDocker::new().await;
     insert_test_user(
         TEST_USER_UUID,
-        TEST_USERNAME.to_owned(),
+        TEST_USERNAME,
+        TEST_PASSWORD,
         pg_docker.db_conn(),
     )
     .await
     .expect("cannot insert test user");
     let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
-    let _ = UserSession::login_as(server, TEST_USERNAME).await?;
+    let _ = UserSession::login_as(server, TEST_USERNAME, TEST_PASSWORD).await?;
 
     Ok(())
 }
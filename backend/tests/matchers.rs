@@ -1,4 +1,6 @@
-use std::{collections::HashMap};
+#![allow(dead_code)]
+
+use std::collections::HashMap;
 
 use googletest::{
     description::Description,
@@ -79,7 +81,8 @@ impl<'a> Matcher<&'a serde_json::Value> for JsonObjMatcher {
                 .collect::<Description>()
                 .bullet_list()
                 .indent()
-        ).into()
+        )
+        .into()
     }
 }
 
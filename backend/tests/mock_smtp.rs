@@ -0,0 +1,153 @@
+//! A minimal SMTP server that accepts every email and keeps it for the test to read, like MailHog
+//! does for local development.
+
+use std::{net::SocketAddr, time::Duration};
+
+use planner_backend::SmtpConfig;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+};
+
+pub const APP_URL: &str = "http://localhost:5173";
+const FROM: &str = "Planner <planner@example.com>";
+
+pub struct MockSmtpServer {
+    addr: SocketAddr,
+    emails: Mutex<mpsc::UnboundedReceiver<ReceivedEmail>>,
+}
+
+#[derive(Debug)]
+pub struct ReceivedEmail {
+    pub recipients: Vec<String>,
+    /// The headers and the body as sent by the client.
+    pub data: String,
+}
+
+impl MockSmtpServer {
+    pub async fn spawn() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Cannot bind to 127.0.0.1:0 (dynamic port)");
+        let addr = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.expect("Mock SMTP server failed");
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    handle_connection(stream, sender)
+                        .await
+                        .expect("Mock SMTP connection failed");
+                });
+            }
+        });
+
+        Self {
+            addr,
+            emails: Mutex::new(receiver),
+        }
+    }
+
+    /// The config for the backend to send through this server.
+    pub fn config(&self) -> SmtpConfig {
+        SmtpConfig {
+            url: format!("smtp://{}", self.addr),
+            from: FROM.to_owned(),
+            app_url: APP_URL.to_owned(),
+        }
+    }
+
+    /// Waits for the next email, which the backend sends in the background.
+    pub async fn next_email(&self) -> ReceivedEmail {
+        tokio::time::timeout(Duration::from_secs(5), self.emails.lock().await.recv())
+            .await
+            .expect("No email was sent")
+            .unwrap()
+    }
+}
+
+impl ReceivedEmail {
+    /// The body with the quoted-printable encoding that long lines are sent in undone.
+    pub fn body(&self) -> String {
+        let (headers, body) = self.data.split_once("\r\n\r\n").unwrap_or(("", &self.data));
+        if !headers
+            .to_ascii_lowercase()
+            .contains("content-transfer-encoding: quoted-printable")
+        {
+            return body.to_owned();
+        }
+
+        let body = body.replace("=\r\n", "");
+        let mut decoded = Vec::new();
+        let mut bytes = body.bytes();
+        while let Some(byte) = bytes.next() {
+            if byte == b'=' {
+                let hex = [bytes.next().unwrap(), bytes.next().unwrap()];
+                decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).unwrap(), 16).unwrap());
+            } else {
+                decoded.push(byte);
+            }
+        }
+        String::from_utf8(decoded).unwrap()
+    }
+
+    /// The token of the link in the body.
+    pub fn link_token(&self) -> String {
+        let body = self.body();
+        let (_, rest) = body.split_once("?token=").expect("No link in the email");
+        rest.chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .collect()
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    sender: mpsc::UnboundedSender<ReceivedEmail>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut recipients = Vec::new();
+
+    writer.write_all(b"220 mock ESMTP\r\n").await?;
+    while let Some(line) = lines.next_line().await? {
+        let command = line.to_ascii_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+            b"250 mock\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            recipients.clear();
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            let recipient = &line["RCPT TO:".len()..];
+            recipients.push(recipient.trim_matches(['<', '>', ' ']).to_owned());
+            b"250 OK\r\n"
+        } else if command == "DATA" {
+            writer.write_all(b"354 Go ahead\r\n").await?;
+            let mut data = Vec::new();
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                // Undo the dot-stuffing of lines that start with a dot.
+                data.push(line.strip_prefix('.').unwrap_or(&line).to_owned());
+            }
+            let _ = sender.send(ReceivedEmail {
+                recipients: std::mem::take(&mut recipients),
+                data: data.join("\r\n"),
+            });
+            b"250 OK\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            break;
+        } else if command == "RSET" || command == "NOOP" {
+            b"250 OK\r\n"
+        } else {
+            b"502 Not implemented\r\n"
+        };
+        writer.write_all(reply).await?;
+    }
+
+    Ok(())
+}
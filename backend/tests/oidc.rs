@@ -0,0 +1,229 @@
+mod common;
+mod mock_oidc;
+
+use common::{insert_test_user, Result, TestServer};
+use googletest::prelude::*;
+use mock_oidc::MockOidcServer;
+use planner_backend::{delete_expired_oidc_auth_requests, entities, Config};
+use reqwest::StatusCode;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter};
+use testlib::{test_uuid, PgDocker};
+
+async fn spawn_server(pg_docker: &PgDocker, mock_oidc: &MockOidcServer) -> TestServer {
+    TestServer::spawn_with_config(
+        pg_docker.db_conn().clone(),
+        Config {
+            oidc: Some(mock_oidc.config()),
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Starts a login and follows it through the provider, returning the `code` and `state` that the
+/// provider redirects back to the frontend with.
+async fn authorize(server: &TestServer) -> Result<(String, String)> {
+    let response: serde_json::Value = server
+        .get("/auth/oidc/authorize")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let authorization_url = response["authorization_url"].as_str().unwrap();
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let response = client.get(authorization_url).send().await?;
+    let location = reqwest::Url::parse(
+        response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .unwrap()
+            .to_str()?,
+    )?;
+    let param = |name: &str| {
+        location
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+
+    Ok((param("code"), param("state")))
+}
+
+async fn callback(server: &TestServer, code: &str, state: &str) -> Result<reqwest::Response> {
+    Ok(server
+        .post("/auth/oidc/callback")
+        .json(&serde_json::json!({ "code": code, "state": state }))
+        .send()
+        .await?)
+}
+
+async fn login(server: &TestServer) -> Result<serde_json::Value> {
+    let (code, state) = authorize(server).await?;
+    Ok(callback(server, &code, &state)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn first_login_creates_user_that_can_use_the_api() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_oidc = MockOidcServer::spawn().await;
+    mock_oidc.sign_in_as("subject-1", "nova");
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    let tokens = login(&server).await?;
+
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::OidcSubject.eq("subject-1"))
+        .one(pg_docker.db_conn())
+        .await?;
+    expect_that!(
+        user,
+        some(pat!(entities::users::Model {
+            username: eq("nova"),
+            password_hash: none(),
+        }))
+    );
+    let response: serde_json::Value = server
+        .post("/graphql")
+        .bearer_auth(tokens["token"].as_str().unwrap())
+        .json(&serde_json::json!({ "query": "query { tasks { totalCount } }" }))
+        .send()
+        .await?
+        .json()
+        .await?;
+    expect_that!(response["data"]["tasks"]["totalCount"], eq(0));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn later_logins_use_the_linked_user() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_oidc = MockOidcServer::spawn().await;
+    mock_oidc.sign_in_as("subject-1", "nova");
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    login(&server).await?;
+    // The username at the provider may change, but the subject identifies the user.
+    mock_oidc.sign_in_as("subject-1", "renamed");
+    login(&server).await?;
+
+    let users = entities::users::Entity::find()
+        .all(pg_docker.db_conn())
+        .await?;
+    expect_that!(
+        users,
+        elements_are![pat!(entities::users::Model {
+            username: eq("nova"),
+        })]
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_does_not_link_to_existing_user_with_same_name() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    insert_test_user(
+        test_uuid(1),
+        "meteor",
+        "meteor-password",
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let mock_oidc = MockOidcServer::spawn().await;
+    mock_oidc.sign_in_as("subject-1", "meteor");
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    login(&server).await?;
+
+    let user = entities::users::Entity::find()
+        .filter(entities::users::Column::OidcSubject.eq("subject-1"))
+        .one(pg_docker.db_conn())
+        .await?;
+    expect_that!(
+        user,
+        some(pat!(entities::users::Model {
+            id: not(eq(&test_uuid(1))),
+            username: starts_with("meteor-"),
+        }))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn state_can_only_be_used_once() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_oidc = MockOidcServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    let (code, state) = authorize(&server).await?;
+    expect_that!(
+        callback(&server, &code, &state).await?.status(),
+        eq(StatusCode::OK)
+    );
+    expect_that!(
+        callback(&server, &code, &state).await?.status(),
+        eq(StatusCode::UNAUTHORIZED)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn expired_auth_requests_are_deleted() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_oidc = MockOidcServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    authorize(&server).await?;
+    pg_docker
+        .db_conn()
+        .execute_unprepared("UPDATE oidc_auth_requests SET created_at = now() - interval '1 hour'")
+        .await?;
+    let (code, state) = authorize(&server).await?;
+
+    expect_that!(
+        delete_expired_oidc_auth_requests(pg_docker.db_conn()).await?,
+        eq(1)
+    );
+    expect_that!(
+        callback(&server, &code, &state).await?.status(),
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn callback_with_unknown_state_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let mock_oidc = MockOidcServer::spawn().await;
+    let server = spawn_server(&pg_docker, &mock_oidc).await;
+
+    let (code, _) = authorize(&server).await?;
+    let response = callback(&server, &code, "forged-state").await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn oidc_routes_are_absent_when_not_configured() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+
+    let response = server.get("/auth/oidc/authorize").send().await?;
+    expect_that!(response.status(), eq(StatusCode::NOT_FOUND));
+    Ok(())
+}
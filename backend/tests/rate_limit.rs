@@ -0,0 +1,295 @@
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::{insert_test_user, Result, TestServer, UserSession};
+use googletest::prelude::*;
+use planner_backend::{
+    delete_expired_rate_limit_counters, entities, Budget, Config, RateLimitConfig,
+};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use sea_orm::{EntityTrait, Set};
+use testlib::{test_uuid, PgDocker};
+
+const TEST_PASSWORD: &str = "meteor-password";
+
+/// A budget with a window long enough that tests never cross into the next window.
+fn budget(requests: u32) -> Budget {
+    Budget {
+        requests,
+        window: TimeDelta::days(1),
+    }
+}
+
+async fn spawn_server(pg_docker: &PgDocker, rate_limit: RateLimitConfig) -> Result<TestServer> {
+    for (id, username) in [(1, "meteor"), (2, "nova")] {
+        insert_test_user(test_uuid(id), username, TEST_PASSWORD, pg_docker.db_conn()).await?;
+    }
+    Ok(TestServer::spawn_with_config(
+        pg_docker.db_conn().clone(),
+        Config {
+            rate_limit,
+            ..Default::default()
+        },
+    )
+    .await)
+}
+
+async fn login(server: &TestServer, username: &str, password: &str) -> Result<reqwest::Response> {
+    Ok(server
+        .post("/auth/login")
+        .json(&serde_json::json!({
+            "username": username,
+            "password": password,
+        }))
+        .send()
+        .await?)
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<i64> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn auth_requests_over_budget_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            auth: budget(3),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    for _ in 0..3 {
+        expect_that!(
+            login(&server, "meteor", TEST_PASSWORD).await?.status(),
+            eq(StatusCode::OK)
+        );
+    }
+    let response = login(&server, "meteor", TEST_PASSWORD).await?;
+    expect_that!(response.status(), eq(StatusCode::TOO_MANY_REQUESTS));
+    expect_that!(
+        retry_after(&response),
+        some(all![gt(0), le(TimeDelta::days(1).num_seconds())])
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn auth_requests_are_limited_by_the_ip_appended_by_the_proxy() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            auth: budget(1),
+            client_ip_header: Some("X-Forwarded-For".to_owned()),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let login_forwarded_for = |forwarded_for: &'static str| {
+        server
+            .post("/auth/login")
+            .header("X-Forwarded-For", forwarded_for)
+            .json(&serde_json::json!({
+                "username": "meteor",
+                "password": TEST_PASSWORD,
+            }))
+            .send()
+    };
+
+    expect_that!(
+        login_forwarded_for("10.0.0.1, 192.0.2.1").await?.status(),
+        eq(StatusCode::OK)
+    );
+    // A client cannot get a fresh budget by making up the first entries.
+    expect_that!(
+        login_forwarded_for("10.0.0.2, 192.0.2.1").await?.status(),
+        eq(StatusCode::TOO_MANY_REQUESTS)
+    );
+    expect_that!(
+        login_forwarded_for("192.0.2.2").await?.status(),
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn repeated_failed_logins_lock_out_the_username() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            max_login_failures: 2,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    for _ in 0..2 {
+        expect_that!(
+            login(&server, "meteor", "wrong-password").await?.status(),
+            eq(StatusCode::UNAUTHORIZED)
+        );
+    }
+    let response = login(&server, "meteor", TEST_PASSWORD).await?;
+    expect_that!(response.status(), eq(StatusCode::TOO_MANY_REQUESTS));
+    expect_that!(
+        retry_after(&response),
+        some(all![gt(0), le(TimeDelta::minutes(15).num_seconds())])
+    );
+
+    expect_that!(
+        login(&server, "nova", TEST_PASSWORD).await?.status(),
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn failed_logins_only_lock_out_the_ip_they_come_from() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            max_login_failures: 2,
+            client_ip_header: Some("X-Forwarded-For".to_owned()),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let login_from = |ip: &'static str, password: &'static str| {
+        server
+            .post("/auth/login")
+            .header("X-Forwarded-For", ip)
+            .json(&serde_json::json!({
+                "username": "meteor",
+                "password": password,
+            }))
+            .send()
+    };
+
+    for _ in 0..2 {
+        expect_that!(
+            login_from("192.0.2.1", "wrong-password").await?.status(),
+            eq(StatusCode::UNAUTHORIZED)
+        );
+    }
+    expect_that!(
+        login_from("192.0.2.1", TEST_PASSWORD).await?.status(),
+        eq(StatusCode::TOO_MANY_REQUESTS)
+    );
+    expect_that!(
+        login_from("192.0.2.2", TEST_PASSWORD).await?.status(),
+        eq(StatusCode::OK)
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn successful_login_resets_failed_logins() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            max_login_failures: 2,
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    for _ in 0..2 {
+        expect_that!(
+            login(&server, "meteor", "wrong-password").await?.status(),
+            eq(StatusCode::UNAUTHORIZED)
+        );
+        expect_that!(
+            login(&server, "meteor", TEST_PASSWORD).await?.status(),
+            eq(StatusCode::OK)
+        );
+    }
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn graphql_requests_are_limited_per_user() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(
+        &pg_docker,
+        RateLimitConfig {
+            graphql: budget(2),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let meteor = UserSession::login_as(server.clone(), "meteor", TEST_PASSWORD).await?;
+    let nova = UserSession::login_as(server, "nova", TEST_PASSWORD).await?;
+
+    let query = |session: &UserSession| {
+        session
+            .post("/graphql")
+            .json(&serde_json::json!({ "query": "query { tasks { totalCount } }" }))
+            .send()
+    };
+    for _ in 0..2 {
+        expect_that!(query(&meteor).await?.status(), eq(StatusCode::OK));
+    }
+    let response = query(&meteor).await?;
+    expect_that!(response.status(), eq(StatusCode::TOO_MANY_REQUESTS));
+    expect_that!(retry_after(&response), some(gt(0)));
+
+    expect_that!(query(&nova).await?.status(), eq(StatusCode::OK));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn counters_of_windows_that_are_over_are_deleted() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let config = RateLimitConfig {
+        auth: budget(1),
+        ..Default::default()
+    };
+    let now = Utc::now();
+    for (key, window_start) in [
+        ("auth-ip:192.0.2.1", now - TimeDelta::days(2)),
+        ("auth-ip:192.0.2.2", now - TimeDelta::hours(1)),
+    ] {
+        entities::rate_limit_counters::Entity::insert(entities::rate_limit_counters::ActiveModel {
+            key: Set(key.to_owned()),
+            window_start: Set(window_start.into()),
+            count: Set(1),
+        })
+        .exec(pg_docker.db_conn())
+        .await?;
+    }
+
+    expect_that!(
+        delete_expired_rate_limit_counters(&config, pg_docker.db_conn()).await?,
+        eq(1)
+    );
+    let counters = entities::rate_limit_counters::Entity::find()
+        .all(pg_docker.db_conn())
+        .await?;
+    expect_that!(
+        counters,
+        elements_are![field!(
+            entities::rate_limit_counters::Model.key,
+            eq("auth-ip:192.0.2.2")
+        )]
+    );
+    Ok(())
+}
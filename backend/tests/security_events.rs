@@ -0,0 +1,160 @@
+mod common;
+mod matchers;
+
+use common::{insert_test_user, Result, TestServer, UserSession};
+use googletest::prelude::*;
+use reqwest::StatusCode;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const TEST_USERNAME: &str = "meteor";
+const TEST_PASSWORD: &str = "meteor-password";
+const TEST_USER_UUID: Uuid = test_uuid(1);
+
+const SECURITY_EVENTS_QUERY: &str = "
+    query($first: Int, $after: String) {
+        securityEvents(first: $first, after: $after) {
+            edges { node { kind detail ipAddress userAgent } }
+            pageInfo { hasNextPage endCursor }
+        }
+    }";
+
+async fn spawn_server(pg_docker: &PgDocker) -> Result<TestServer> {
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    Ok(TestServer::spawn(pg_docker.db_conn().clone()).await)
+}
+
+async fn security_events(
+    session: &UserSession,
+    first: i32,
+    after: Option<&str>,
+) -> Result<serde_json::Value> {
+    let response = session
+        .graphql(
+            SECURITY_EVENTS_QUERY,
+            serde_json::json!({ "first": first, "after": after }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    Ok(response["data"]["securityEvents"].clone())
+}
+
+fn kinds(events: &serde_json::Value) -> Vec<String> {
+    events["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"]["kind"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn logins_are_recorded_with_the_client() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+
+    let response = server
+        .post("/auth/login")
+        .header("User-Agent", "guesser/1.0")
+        .json(&serde_json::json!({
+            "username": TEST_USERNAME,
+            "password": "wrong-password",
+        }))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    let session = UserSession::login_as(server, TEST_USERNAME, TEST_PASSWORD).await?;
+
+    let events = security_events(&session, 10, None).await?;
+    expect_that!(
+        kinds(&events),
+        elements_are![eq("LOGIN_SUCCEEDED"), eq("LOGIN_FAILED")]
+    );
+    let failed = &events["edges"][1]["node"];
+    expect_that!(failed["detail"], json_string(eq("password")));
+    expect_that!(failed["ipAddress"], json_string(eq("127.0.0.1")));
+    expect_that!(failed["userAgent"], json_string(eq("guesser/1.0")));
+    expect_that!(
+        events["edges"][0]["node"]["detail"],
+        json_string(eq("password"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn token_and_session_changes_are_recorded() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let session = UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+    let other_session = UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+
+    let response = session
+        .graphql(
+            r#"mutation { createApiToken(input: { name: "cron", scopes: [TASKS_READ] }) {
+                apiToken { id }
+            } }"#,
+            serde_json::json!({}),
+        )
+        .await?;
+    let api_token_id = response["data"]["createApiToken"]["apiToken"]["id"].clone();
+    session
+        .graphql(
+            "mutation($id: UUID!) { deleteApiToken(id: $id) }",
+            serde_json::json!({ "id": api_token_id }),
+        )
+        .await?;
+    let response = other_session
+        .post("/auth/logout")
+        .json(&serde_json::json!({ "refresh_token": other_session.refresh_token() }))
+        .send()
+        .await?;
+    expect_that!(response.status(), eq(StatusCode::NO_CONTENT));
+
+    let events = security_events(&session, 10, None).await?;
+    expect_that!(
+        kinds(&events),
+        elements_are![
+            eq("SESSION_REVOKED"),
+            eq("API_TOKEN_DELETED"),
+            eq("API_TOKEN_CREATED"),
+            eq("LOGIN_SUCCEEDED"),
+            eq("LOGIN_SUCCEEDED"),
+        ]
+    );
+    expect_that!(
+        events["edges"][1]["node"]["detail"],
+        json_string(eq("cron"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn security_events_are_paginated() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    for _ in 0..2 {
+        UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+    }
+    let session = UserSession::login_as(server, TEST_USERNAME, TEST_PASSWORD).await?;
+
+    let first_page = security_events(&session, 2, None).await?;
+    expect_that!(kinds(&first_page), len(eq(2)));
+    expect_that!(first_page["pageInfo"]["hasNextPage"], eq(&true));
+
+    let second_page =
+        security_events(&session, 2, first_page["pageInfo"]["endCursor"].as_str()).await?;
+    expect_that!(kinds(&second_page), elements_are![eq("LOGIN_SUCCEEDED")]);
+    expect_that!(second_page["pageInfo"]["hasNextPage"], eq(&false));
+    Ok(())
+}
@@ -0,0 +1,243 @@
+mod common;
+mod matchers;
+
+use chrono::{Datelike, Utc};
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const TASKS_QUERY: &str = "
+    query($filter: TaskFilter, $orderBy: TaskOrder, $first: Int, $after: String,
+          $last: Int, $before: String) {
+        tasks(filter: $filter, orderBy: $orderBy, first: $first, after: $after, last: $last,
+              before: $before) {
+            edges { node { title cost } }
+            pageInfo { hasPreviousPage hasNextPage startCursor endCursor }
+            totalCount
+        }
+    }";
+
+async fn tasks(session: &UserSession, variables: serde_json::Value) -> Result<serde_json::Value> {
+    let response = session.graphql(TASKS_QUERY, variables).await?;
+    expect_that!(response["errors"], json_null());
+    Ok(response["data"]["tasks"].clone())
+}
+
+/// The titles of the tasks that match the filter, in the order of their titles.
+async fn titles(session: &UserSession, filter: serde_json::Value) -> Result<Vec<String>> {
+    let tasks = tasks(
+        session,
+        serde_json::json!({ "filter": filter, "orderBy": { "key": "TITLE" } }),
+    )
+    .await?;
+    Ok(nodes(&tasks)
+        .iter()
+        .map(|node| node["title"].as_str().unwrap().to_owned())
+        .collect())
+}
+
+fn nodes(tasks: &serde_json::Value) -> Vec<serde_json::Value> {
+    tasks["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"].clone())
+        .collect()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_filtered_by_completion_and_cost() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let done_id = create_task(&session, serde_json::json!({ "title": "done", "cost": 1 })).await?;
+    create_task(&session, serde_json::json!({ "title": "cheap", "cost": 1 })).await?;
+    create_task(
+        &session,
+        serde_json::json!({ "title": "pricey", "cost": 8 }),
+    )
+    .await?;
+    create_task(&session, serde_json::json!({ "title": "free" })).await?;
+    session
+        .graphql(
+            "mutation($id: UUID!) {
+                updateTask(input: { id: $id, completeDate: \"2024-03-05\" }) { taskId }
+            }",
+            serde_json::json!({ "id": done_id }),
+        )
+        .await?;
+
+    expect_that!(
+        titles(&session, serde_json::json!({ "completed": true })).await?,
+        elements_are![eq("done")]
+    );
+    expect_that!(
+        titles(&session, serde_json::json!({ "completed": false })).await?,
+        elements_are![eq("cheap"), eq("free"), eq("pricey")]
+    );
+    expect_that!(
+        titles(
+            &session,
+            serde_json::json!({ "completeDate": { "start": "2024-03-04", "end": "2024-03-06" } }),
+        )
+        .await?,
+        elements_are![eq("done")]
+    );
+    expect_that!(
+        titles(
+            &session,
+            serde_json::json!({ "completeDate": { "start": "2024-03-06", "end": "2024-03-07" } }),
+        )
+        .await?,
+        empty()
+    );
+    expect_that!(
+        titles(&session, serde_json::json!({ "cost": { "min": 2 } })).await?,
+        elements_are![eq("pricey")]
+    );
+    expect_that!(
+        titles(&session, serde_json::json!({ "cost": { "max": 1 } })).await?,
+        elements_are![eq("cheap"), eq("done")]
+    );
+    expect_that!(
+        titles(&session, serde_json::json!({ "cost": {} })).await?,
+        elements_are![eq("cheap"), eq("done"), eq("pricey")]
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_filtered_by_recurrence() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let today = Utc::now().date_naive();
+    let monday = today - chrono::TimeDelta::days(today.weekday().num_days_from_monday().into());
+    create_task(&session, serde_json::json!({ "title": "groceries" })).await?;
+    session
+        .graphql(
+            "mutation($startDate: NaiveDate!) {
+                createTask(input: {
+                    title: \"laundry\",
+                    recurringSpec: { startDate: $startDate, pattern: { every: 1 } },
+                }) { taskId }
+            }",
+            serde_json::json!({ "startDate": monday }),
+        )
+        .await?;
+
+    expect_that!(
+        titles(&session, serde_json::json!({ "recurrence": "RECURRING" })).await?,
+        elements_are![eq("laundry")]
+    );
+    expect_that!(
+        titles(&session, serde_json::json!({ "recurrence": "ONE_OFF" })).await?,
+        elements_are![eq("groceries")]
+    );
+    let generated = titles(&session, serde_json::json!({ "recurrence": "GENERATED" })).await?;
+    expect_that!(generated, not(empty()));
+    expect_that!(generated, each(eq("laundry")));
+    expect_that!(
+        titles(&session, serde_json::json!({ "unscheduledOnly": true })).await?,
+        elements_are![eq("groceries"), eq("laundry")]
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn sorted_tasks_are_paginated_with_nulls_last() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    for (title, cost) in [
+        ("a", Some(3)),
+        ("b", None),
+        ("c", Some(1)),
+        ("d", Some(3)),
+        ("e", Some(2)),
+    ] {
+        create_task(
+            &session,
+            serde_json::json!({ "title": title, "cost": cost }),
+        )
+        .await?;
+    }
+
+    for (descending, expected) in [
+        (false, [Some(1), Some(2), Some(3), Some(3), None]),
+        (true, [Some(3), Some(3), Some(2), Some(1), None]),
+    ] {
+        let order_by = serde_json::json!({ "key": "COST", "descending": descending });
+
+        let mut forwards = Vec::new();
+        let mut after = serde_json::Value::Null;
+        loop {
+            let page = tasks(
+                &session,
+                serde_json::json!({ "orderBy": order_by, "first": 2, "after": after }),
+            )
+            .await?;
+            forwards.extend(nodes(&page));
+            if page["pageInfo"]["hasNextPage"] != true {
+                break;
+            }
+            after = page["pageInfo"]["endCursor"].clone();
+        }
+
+        let mut backwards = Vec::new();
+        let mut before = serde_json::Value::Null;
+        loop {
+            let page = tasks(
+                &session,
+                serde_json::json!({ "orderBy": order_by, "last": 2, "before": before }),
+            )
+            .await?;
+            backwards.splice(0..0, nodes(&page));
+            if page["pageInfo"]["hasPreviousPage"] != true {
+                break;
+            }
+            before = page["pageInfo"]["startCursor"].clone();
+        }
+
+        let costs = |nodes: &[serde_json::Value]| {
+            nodes
+                .iter()
+                .map(|node| node["cost"].as_i64().map(|cost| cost as i32))
+                .collect::<Vec<_>>()
+        };
+        expect_that!(costs(&forwards), eq(&expected));
+        expect_that!(forwards, eq(&backwards));
+    }
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn cursors_of_another_order_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    create_task(&session, serde_json::json!({ "title": "a" })).await?;
+    create_task(&session, serde_json::json!({ "title": "b" })).await?;
+    let page = tasks(
+        &session,
+        serde_json::json!({ "orderBy": { "key": "TITLE" }, "first": 1 }),
+    )
+    .await?;
+
+    let response = session
+        .graphql(
+            TASKS_QUERY,
+            serde_json::json!({
+                "orderBy": { "key": "CREATED_AT" },
+                "after": page["pageInfo"]["endCursor"],
+            }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["code"],
+        json_string(eq("INVALID_INPUT"))
+    );
+    Ok(())
+}
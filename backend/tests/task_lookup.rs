@@ -0,0 +1,148 @@
+mod common;
+mod matchers;
+
+use common::{create_task, insert_test_user, login_session, Result, UserSession, TEST_PASSWORD};
+use googletest::prelude::*;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const OTHER_USERNAME: &str = "comet";
+const OTHER_USER_UUID: Uuid = test_uuid(2);
+
+const TASK_QUERY: &str = "query($id: UUID!) { task(id: $id) { id title } }";
+const NODE_QUERY: &str = "
+    query($id: ID!) {
+        node(id: $id) { __typename id ... on Task { taskId title } }
+    }";
+const NODES_QUERY: &str = "
+    query($ids: [ID!]!) {
+        nodes(ids: $ids) { id ... on Task { title } }
+    }";
+
+/// Logs in as the test user and as another user on the same server.
+async fn login_sessions(pg_docker: &PgDocker) -> Result<(UserSession, UserSession)> {
+    let session = login_session(pg_docker).await?;
+    insert_test_user(
+        OTHER_USER_UUID,
+        OTHER_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let other_session =
+        UserSession::login_as(session.server().clone(), OTHER_USERNAME, TEST_PASSWORD).await?;
+    Ok((session, other_session))
+}
+
+/// Creates a task and returns its global id and its id.
+async fn create_task_ids(session: &UserSession, title: &str) -> Result<(String, String)> {
+    let task_id = create_task(session, serde_json::json!({ "title": title })).await?;
+    let response = session
+        .graphql(TASK_QUERY, serde_json::json!({ "id": task_id }))
+        .await?;
+    Ok((
+        response["data"]["task"]["id"].as_str().unwrap().to_owned(),
+        task_id,
+    ))
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_can_be_looked_up_by_id() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (session, _) = login_sessions(&pg_docker).await?;
+    let (global_id, task_id) = create_task_ids(&session, "Groceries").await?;
+
+    let response = session
+        .graphql(TASK_QUERY, serde_json::json!({ "id": task_id }))
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(response["data"]["task"]["id"], json_string(eq(&global_id)));
+    expect_that!(
+        response["data"]["task"]["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_can_be_looked_up_as_nodes() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (session, _) = login_sessions(&pg_docker).await?;
+    let (first_id, task_id) = create_task_ids(&session, "Groceries").await?;
+    let (second_id, _) = create_task_ids(&session, "Laundry").await?;
+
+    let response = session
+        .graphql(NODE_QUERY, serde_json::json!({ "id": first_id }))
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let node = &response["data"]["node"];
+    expect_that!(node["__typename"], json_string(eq("Task")));
+    expect_that!(node["taskId"], json_string(eq(&task_id)));
+    expect_that!(node["title"], json_string(eq("Groceries")));
+
+    let response = session
+        .graphql(
+            NODES_QUERY,
+            serde_json::json!({ "ids": [second_id, first_id] }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(
+        response["data"]["nodes"][0]["title"],
+        json_string(eq("Laundry"))
+    );
+    expect_that!(
+        response["data"]["nodes"][1]["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_of_other_users_are_not_found() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (session, other_session) = login_sessions(&pg_docker).await?;
+    let (global_id, task_id) = create_task_ids(&session, "Groceries").await?;
+
+    let response = other_session
+        .graphql(TASK_QUERY, serde_json::json!({ "id": task_id }))
+        .await?;
+    expect_that!(
+        response["errors"][0]["message"],
+        json_string(eq(&format!("Task with id = {task_id} is not found")))
+    );
+
+    let response = other_session
+        .graphql(NODE_QUERY, serde_json::json!({ "id": global_id }))
+        .await?;
+    expect_that!(response["data"]["node"], json_null());
+    expect_that!(
+        response["errors"][0]["message"],
+        json_string(eq(&format!("Task with id = {task_id} is not found")))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn malformed_node_ids_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (session, _) = login_sessions(&pg_docker).await?;
+    let (_, task_id) = create_task_ids(&session, "Groceries").await?;
+
+    for id in [task_id.as_str(), "VGFzazpub3QtYS11dWlk", "not base64!"] {
+        let response = session
+            .graphql(NODE_QUERY, serde_json::json!({ "id": id }))
+            .await?;
+        expect_that!(
+            response["errors"][0]["message"],
+            json_string(ends_with("is not the id of a node"))
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+mod common;
+mod matchers;
+
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+
+use crate::matchers::json_null;
+
+const TASKS_QUERY: &str = "
+    query($filter: TaskFilter, $first: Int, $after: String, $last: Int, $before: String) {
+        tasks(filter: $filter, first: $first, after: $after, last: $last, before: $before) {
+            edges { node { taskId title } }
+            pageInfo { hasPreviousPage hasNextPage startCursor endCursor }
+            totalCount
+        }
+    }";
+
+/// Creates tasks with the given titles and returns their ids in the order they are listed in.
+async fn create_tasks(
+    session: &UserSession,
+    titles: &[&str],
+    scheduled_on: serde_json::Value,
+) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for title in titles {
+        let input = serde_json::json!({ "title": title, "scheduledOn": scheduled_on });
+        ids.push(create_task(session, input).await?);
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+async fn tasks(session: &UserSession, variables: serde_json::Value) -> Result<serde_json::Value> {
+    let response = session.graphql(TASKS_QUERY, variables).await?;
+    expect_that!(response["errors"], json_null());
+    Ok(response["data"]["tasks"].clone())
+}
+
+fn ids(tasks: &serde_json::Value) -> Vec<String> {
+    tasks["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"]["taskId"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_paginated_forwards() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let task_ids = create_tasks(
+        &session,
+        &["a", "b", "c", "d", "e"],
+        serde_json::Value::Null,
+    )
+    .await?;
+
+    let first_page = tasks(&session, serde_json::json!({ "first": 2 })).await?;
+    expect_that!(ids(&first_page), eq(&task_ids[..2]));
+    expect_that!(first_page["pageInfo"]["hasNextPage"], eq(&true));
+    expect_that!(first_page["totalCount"], eq(5));
+
+    let second_page = tasks(
+        &session,
+        serde_json::json!({ "first": 2, "after": first_page["pageInfo"]["endCursor"] }),
+    )
+    .await?;
+    expect_that!(ids(&second_page), eq(&task_ids[2..4]));
+
+    let last_page = tasks(
+        &session,
+        serde_json::json!({ "first": 2, "after": second_page["pageInfo"]["endCursor"] }),
+    )
+    .await?;
+    expect_that!(ids(&last_page), eq(&task_ids[4..]));
+    expect_that!(last_page["pageInfo"]["hasNextPage"], eq(&false));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_paginated_backwards() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let task_ids = create_tasks(
+        &session,
+        &["a", "b", "c", "d", "e"],
+        serde_json::Value::Null,
+    )
+    .await?;
+
+    let last_page = tasks(&session, serde_json::json!({ "last": 3 })).await?;
+    expect_that!(ids(&last_page), eq(&task_ids[2..]));
+    expect_that!(last_page["pageInfo"]["hasPreviousPage"], eq(&true));
+
+    let previous_page = tasks(
+        &session,
+        serde_json::json!({ "last": 3, "before": last_page["pageInfo"]["startCursor"] }),
+    )
+    .await?;
+    expect_that!(ids(&previous_page), eq(&task_ids[..2]));
+    expect_that!(previous_page["pageInfo"]["hasPreviousPage"], eq(&false));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn pages_only_contain_tasks_matching_the_filter() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let date = serde_json::json!({ "type": "DATE", "date": "2024-03-04" });
+    let scheduled_ids = create_tasks(&session, &["a", "b", "c"], date.clone()).await?;
+    create_tasks(&session, &["d", "e", "f", "g"], serde_json::Value::Null).await?;
+    let filter = serde_json::json!({ "viewFilter": { "type": "SCHEDULED", "epoch": date } });
+
+    let first_page = tasks(
+        &session,
+        serde_json::json!({ "filter": filter, "first": 2 }),
+    )
+    .await?;
+    expect_that!(ids(&first_page), eq(&scheduled_ids[..2]));
+    expect_that!(first_page["pageInfo"]["hasNextPage"], eq(&true));
+    expect_that!(first_page["totalCount"], eq(3));
+
+    let second_page = tasks(
+        &session,
+        serde_json::json!({
+            "filter": filter,
+            "first": 2,
+            "after": first_page["pageInfo"]["endCursor"],
+        }),
+    )
+    .await?;
+    expect_that!(ids(&second_page), eq(&scheduled_ids[2..]));
+    expect_that!(second_page["pageInfo"]["hasNextPage"], eq(&false));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn views_compare_the_epochs_of_the_tasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let week = serde_json::json!({ "type": "WEEK", "date": "2024-03-04" });
+    let wednesday = serde_json::json!({ "type": "DATE", "date": "2024-03-06" });
+    let next_monday = serde_json::json!({ "type": "DATE", "date": "2024-03-11" });
+    let week_ids = create_tasks(&session, &["week"], week.clone()).await?;
+    let wednesday_ids = create_tasks(&session, &["wednesday"], wednesday.clone()).await?;
+    create_tasks(&session, &["next monday"], next_monday).await?;
+    let unscheduled_ids = create_tasks(&session, &["unscheduled"], serde_json::Value::Null).await?;
+
+    let scheduled_in_week = tasks(
+        &session,
+        serde_json::json!({ "filter": { "viewFilter": { "type": "SCHEDULED", "epoch": week } } }),
+    )
+    .await?;
+    expect_that!(
+        ids(&scheduled_in_week),
+        unordered_elements_are![eq(&week_ids[0]), eq(&wednesday_ids[0])]
+    );
+    expect_that!(scheduled_in_week["totalCount"], eq(2));
+
+    let planned_on_wednesday = tasks(
+        &session,
+        serde_json::json!({
+            "filter": { "viewFilter": { "type": "PLANNED", "epoch": wednesday } },
+        }),
+    )
+    .await?;
+    expect_that!(
+        ids(&planned_on_wednesday),
+        unordered_elements_are![
+            eq(&week_ids[0]),
+            eq(&wednesday_ids[0]),
+            eq(&unscheduled_ids[0])
+        ]
+    );
+    expect_that!(planned_on_wednesday["totalCount"], eq(3));
+
+    let unplanned = tasks(
+        &session,
+        serde_json::json!({ "filter": { "viewFilter": { "type": "PLANNED" } } }),
+    )
+    .await?;
+    expect_that!(ids(&unplanned), eq(&unscheduled_ids));
+    expect_that!(unplanned["totalCount"], eq(1));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn first_and_last_cannot_be_combined() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql(TASKS_QUERY, serde_json::json!({ "first": 1, "last": 1 }))
+        .await?;
+    expect_that!(response["errors"][0]["message"], not(json_null()));
+    Ok(())
+}
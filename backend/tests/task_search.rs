@@ -0,0 +1,128 @@
+mod common;
+mod matchers;
+
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const SEARCH_TASKS_QUERY: &str = "
+    query($query: String!, $filter: TaskFilter) {
+        searchTasks(query: $query, filter: $filter) {
+            task { title }
+            rank
+            snippet { text highlighted }
+        }
+    }";
+
+async fn create_tasks(session: &UserSession, titles: &[&str]) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for title in titles {
+        ids.push(create_task(session, serde_json::json!({ "title": title })).await?);
+    }
+    Ok(ids)
+}
+
+async fn search(
+    session: &UserSession,
+    query: &str,
+    filter: serde_json::Value,
+) -> Result<Vec<serde_json::Value>> {
+    let response = session
+        .graphql(
+            SEARCH_TASKS_QUERY,
+            serde_json::json!({ "query": query, "filter": filter }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    Ok(response["data"]["searchTasks"].as_array().unwrap().clone())
+}
+
+fn titles(results: &[serde_json::Value]) -> Vec<String> {
+    results
+        .iter()
+        .map(|result| result["task"]["title"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_found_by_the_prefixes_of_their_words() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    create_tasks(&session, &["Renew the passport", "Buy milk"]).await?;
+
+    let results = search(&session, "renew pass", serde_json::Value::Null).await?;
+    expect_that!(titles(&results), elements_are![eq("Renew the passport")]);
+    expect_that!(
+        results[0]["snippet"],
+        eq(&serde_json::json!([
+            { "text": "Renew", "highlighted": true },
+            { "text": " the ", "highlighted": false },
+            { "text": "passport", "highlighted": true },
+        ]))
+    );
+
+    let results = search(&session, "mil", serde_json::Value::Null).await?;
+    expect_that!(titles(&results), elements_are![eq("Buy milk")]);
+    expect_that!(
+        search(&session, "renew milk", serde_json::Value::Null).await?,
+        empty()
+    );
+    expect_that!(
+        search(&session, " & | ", serde_json::Value::Null).await?,
+        empty()
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn better_matches_come_first() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    create_tasks(
+        &session,
+        &["Renew the passport", "Passport photos for the passport"],
+    )
+    .await?;
+
+    let results = search(&session, "passport", serde_json::Value::Null).await?;
+    expect_that!(
+        titles(&results),
+        elements_are![
+            eq("Passport photos for the passport"),
+            eq("Renew the passport")
+        ]
+    );
+    expect_that!(
+        results[0]["rank"].as_f64(),
+        some(gt(results[1]["rank"].as_f64().unwrap()))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn search_results_match_the_filter() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let ids = create_tasks(&session, &["Renew the passport", "Renew the insurance"]).await?;
+    let response = session
+        .graphql(
+            "mutation($id: UUID!) {
+                updateTask(input: { id: $id, completeDate: \"2024-03-05\" }) { taskId }
+            }",
+            serde_json::json!({ "id": ids[1] }),
+        )
+        .await?;
+    expect_that!(
+        response["data"]["updateTask"]["taskId"],
+        json_string(eq(&ids[1]))
+    );
+
+    let results = search(&session, "renew", serde_json::json!({ "completed": false })).await?;
+    expect_that!(titles(&results), elements_are![eq("Renew the passport")]);
+    Ok(())
+}
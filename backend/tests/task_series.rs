@@ -0,0 +1,284 @@
+mod common;
+mod matchers;
+
+use chrono::{Datelike, Utc};
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const UPDATE_TASK_MUTATION: &str = "
+    mutation($input: UpdateTaskInput!, $scope: SeriesScope! = THIS_OCCURRENCE) {
+        updateTask(input: $input, scope: $scope) { taskId }
+    }";
+const DELETE_TASK_MUTATION: &str = "
+    mutation($id: UUID!, $scope: SeriesScope! = THIS_OCCURRENCE) {
+        deleteTask(id: $id, scope: $scope)
+    }";
+const TASKS_QUERY: &str = "
+    query {
+        tasks(orderBy: { key: SCHEDULED_DATE }) {
+            edges {
+                node {
+                    taskId title cost isCompleted scheduledOn { date }
+                    recurring { endDate }
+                    recurringTemplate { taskId }
+                }
+            }
+        }
+    }";
+
+/// Creates a task recurring every week from this week, with its occurrences for the next weeks.
+async fn create_weekly_task(session: &UserSession) -> Result<String> {
+    let today = Utc::now().date_naive();
+    let monday = today - chrono::TimeDelta::days(today.weekday().num_days_from_monday().into());
+    create_task(
+        session,
+        serde_json::json!({
+            "title": "laundry",
+            "cost": 1,
+            "recurringSpec": { "startDate": monday, "pattern": { "every": 1 } },
+        }),
+    )
+    .await
+}
+
+/// The recurring task and its occurrences, in the order they are scheduled.
+async fn series(
+    session: &UserSession,
+    template_id: &str,
+) -> Result<(serde_json::Value, Vec<serde_json::Value>)> {
+    let response = session
+        .graphql(TASKS_QUERY, serde_json::Value::Null)
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let mut template = serde_json::Value::Null;
+    let mut occurrences = Vec::new();
+    for edge in response["data"]["tasks"]["edges"].as_array().unwrap() {
+        let node = edge["node"].clone();
+        if node["taskId"] == template_id {
+            template = node;
+        } else if node["recurringTemplate"]["taskId"] == template_id {
+            occurrences.push(node);
+        }
+    }
+    Ok((template, occurrences))
+}
+
+/// The values of the field of each task.
+fn values(tasks: &[serde_json::Value], field: &str) -> Vec<serde_json::Value> {
+    tasks.iter().map(|task| task[field].clone()).collect()
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn occurrences_refer_to_their_recurring_task() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let template_id = create_weekly_task(&session).await?;
+
+    let (template, occurrences) = series(&session, &template_id).await?;
+    expect_that!(template["recurringTemplate"], json_null());
+    expect_that!(occurrences.len(), ge(2));
+    expect_that!(
+        values(&occurrences, "title"),
+        each(json_string(eq("laundry")))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn updates_apply_to_the_tasks_of_the_scope() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let template_id = create_weekly_task(&session).await?;
+    let (_, occurrences) = series(&session, &template_id).await?;
+
+    let response = session
+        .graphql(
+            UPDATE_TASK_MUTATION,
+            serde_json::json!({ "input": { "id": occurrences[0]["taskId"], "title": "wash" } }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let (template, updated) = series(&session, &template_id).await?;
+    expect_that!(template["title"], json_string(eq("laundry")));
+    expect_that!(updated[0]["title"], json_string(eq("wash")));
+    expect_that!(updated[1]["title"], json_string(eq("laundry")));
+
+    session
+        .graphql(
+            UPDATE_TASK_MUTATION,
+            serde_json::json!({
+                "input": { "id": occurrences[1]["taskId"], "title": "fold" },
+                "scope": "THIS_AND_FOLLOWING",
+            }),
+        )
+        .await?;
+    let (template, updated) = series(&session, &template_id).await?;
+    expect_that!(template["title"], json_string(eq("fold")));
+    expect_that!(updated[0]["title"], json_string(eq("wash")));
+    expect_that!(
+        values(&updated[1..], "title"),
+        each(json_string(eq("fold")))
+    );
+
+    session
+        .graphql(
+            UPDATE_TASK_MUTATION,
+            serde_json::json!({
+                "input": { "id": occurrences[1]["taskId"], "cost": 3 },
+                "scope": "WHOLE_SERIES",
+            }),
+        )
+        .await?;
+    let (template, updated) = series(&session, &template_id).await?;
+    expect_that!(template["cost"], eq(&serde_json::json!(3)));
+    expect_that!(values(&updated, "cost"), each(eq(&serde_json::json!(3))));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn series_cost_updates_keep_the_cost_of_occurrences_with_subtasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let template_id = create_weekly_task(&session).await?;
+    let (_, occurrences) = series(&session, &template_id).await?;
+    create_task(
+        &session,
+        serde_json::json!({ "title": "sort", "cost": 5, "parentId": occurrences[1]["taskId"] }),
+    )
+    .await?;
+
+    let response = session
+        .graphql(
+            UPDATE_TASK_MUTATION,
+            serde_json::json!({
+                "input": { "id": occurrences[0]["taskId"], "cost": 3 },
+                "scope": "WHOLE_SERIES",
+            }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let (template, updated) = series(&session, &template_id).await?;
+    expect_that!(template["cost"], eq(&serde_json::json!(3)));
+    expect_that!(updated[0]["cost"], eq(&serde_json::json!(3)));
+    expect_that!(updated[1]["cost"], eq(&serde_json::json!(5)));
+    expect_that!(
+        values(&updated[2..], "cost"),
+        each(eq(&serde_json::json!(3)))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn recurring_tasks_must_repeat_at_least_every_epoch() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+
+    let response = session
+        .graphql(
+            "mutation {
+                createTask(input: {
+                    title: \"laundry\",
+                    recurringSpec: { startDate: \"2024-03-04\", pattern: { every: 0 } },
+                }) { taskId }
+            }",
+            serde_json::Value::Null,
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["field"],
+        eq(&serde_json::json!(["recurringSpec", "pattern", "every"]))
+    );
+    let response = session
+        .graphql(TASKS_QUERY, serde_json::Value::Null)
+        .await?;
+    expect_that!(
+        response["data"]["tasks"]["edges"],
+        eq(&serde_json::json!([]))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn deleting_the_following_occurrences_ends_the_series() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let template_id = create_weekly_task(&session).await?;
+    let (_, occurrences) = series(&session, &template_id).await?;
+
+    let response = session
+        .graphql(
+            DELETE_TASK_MUTATION,
+            serde_json::json!({ "id": occurrences[1]["taskId"], "scope": "THIS_AND_FOLLOWING" }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    let (template, remaining) = series(&session, &template_id).await?;
+    expect_that!(template["isCompleted"], eq(&serde_json::json!(false)));
+    expect_that!(
+        template["recurring"]["endDate"],
+        eq(&occurrences[1]["scheduledOn"]["date"])
+    );
+    expect_that!(
+        values(&remaining, "taskId"),
+        elements_are![eq(&occurrences[0]["taskId"])]
+    );
+    let response = session
+        .graphql(
+            "query { tasks(filter: { completed: true }) { totalCount } }",
+            serde_json::Value::Null,
+        )
+        .await?;
+    expect_that!(
+        response["data"]["tasks"]["totalCount"],
+        eq(&serde_json::json!(0))
+    );
+
+    session
+        .graphql(
+            DELETE_TASK_MUTATION,
+            serde_json::json!({ "id": occurrences[0]["taskId"], "scope": "WHOLE_SERIES" }),
+        )
+        .await?;
+    let response = session
+        .graphql(TASKS_QUERY, serde_json::Value::Null)
+        .await?;
+    expect_that!(
+        response["data"]["tasks"]["edges"],
+        eq(&serde_json::json!([]))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn occurrences_are_kept_when_only_the_recurring_task_is_deleted() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let template_id = create_weekly_task(&session).await?;
+    let (_, occurrences) = series(&session, &template_id).await?;
+
+    session
+        .graphql(
+            DELETE_TASK_MUTATION,
+            serde_json::json!({ "id": template_id }),
+        )
+        .await?;
+    let response = session
+        .graphql(TASKS_QUERY, serde_json::Value::Null)
+        .await?;
+    let remaining = values(
+        response["data"]["tasks"]["edges"].as_array().unwrap(),
+        "node",
+    );
+    expect_that!(remaining.len(), eq(occurrences.len()));
+    expect_that!(values(&remaining, "recurringTemplate"), each(json_null()));
+    Ok(())
+}
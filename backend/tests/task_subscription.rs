@@ -0,0 +1,326 @@
+mod common;
+mod matchers;
+
+use std::time::Duration;
+
+use chrono::{Datelike, Utc};
+use common::{
+    create_task, insert_test_user, login_session, Result, TestServer, UserSession, TEST_PASSWORD,
+    TEST_USERNAME, TEST_USER_UUID,
+};
+use futures::{SinkExt, StreamExt};
+use googletest::prelude::*;
+use planner_backend::schedule_all_recurring_tasks_until;
+use sea_orm::ConnectionTrait;
+use testlib::{test_uuid, PgDocker};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const OTHER_USERNAME: &str = "comet";
+const OTHER_USER_UUID: Uuid = test_uuid(2);
+
+const TASK_CHANGED_SUBSCRIPTION: &str = "
+    subscription {
+        taskChanged { kind taskId task { title } }
+    }";
+/// A GraphQL subscription over the graphql-transport-ws protocol.
+struct Subscription {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Subscription {
+    async fn start(server: &TestServer, init_payload: serde_json::Value) -> Result<Self> {
+        let mut request =
+            format!("ws://{}/graphql/ws", server.socket_addr()).into_client_request()?;
+        request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            "graphql-transport-ws".parse().unwrap(),
+        );
+        let (mut socket, _) = tokio_tungstenite::connect_async(request).await?;
+
+        socket
+            .send(Message::Text(
+                serde_json::json!({ "type": "connection_init", "payload": init_payload })
+                    .to_string(),
+            ))
+            .await?;
+        socket
+            .send(Message::Text(
+                serde_json::json!({
+                    "id": "1",
+                    "type": "subscribe",
+                    "payload": { "query": TASK_CHANGED_SUBSCRIPTION },
+                })
+                .to_string(),
+            ))
+            .await?;
+        let mut subscription = Self { socket };
+        expect_that!(
+            subscription.next_message().await?["type"],
+            json_string(eq("connection_ack"))
+        );
+        // There is no acknowledgement of the subscription, so give the server time to start it.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        Ok(subscription)
+    }
+
+    async fn next_message(&mut self) -> Result<serde_json::Value> {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), self.socket.next())
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("the WebSocket was closed"))??;
+            if let Message::Text(text) = message {
+                return Ok(serde_json::from_str(&text)?);
+            }
+        }
+    }
+
+    /// Returns the payload of the next event of the subscription.
+    async fn next_event(&mut self) -> Result<serde_json::Value> {
+        Ok(self.next_message().await?["payload"].clone())
+    }
+}
+
+fn authorization(session: &UserSession) -> serde_json::Value {
+    serde_json::json!({ "authorization": format!("Bearer {}", session.login_token()) })
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn task_changes_are_pushed() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+
+    let task_id = create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+    session
+        .graphql(
+            "mutation($id: UUID!) { updateTask(input: { id: $id, title: \"Renamed\" }) { id } }",
+            serde_json::json!({ "id": task_id }),
+        )
+        .await?;
+    session
+        .graphql(
+            "mutation($id: UUID!) { deleteTask(id: $id) }",
+            serde_json::json!({ "id": task_id }),
+        )
+        .await?;
+
+    for (kind, title) in [("CREATED", "Groceries"), ("UPDATED", "Renamed")] {
+        let event = subscription.next_event().await?;
+        let event = &event["data"]["taskChanged"];
+        expect_that!(event["kind"], json_string(eq(kind)));
+        expect_that!(event["taskId"], json_string(eq(&task_id)));
+        expect_that!(event["task"]["title"], json_string(eq(title)));
+    }
+    let event = subscription.next_event().await?;
+    let event = &event["data"]["taskChanged"];
+    expect_that!(event["kind"], json_string(eq("DELETED")));
+    expect_that!(event["taskId"], json_string(eq(&task_id)));
+    expect_that!(event["task"], json_null());
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn task_changes_of_other_users_are_not_pushed() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    insert_test_user(
+        OTHER_USER_UUID,
+        OTHER_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let other_session =
+        UserSession::login_as(server.clone(), OTHER_USERNAME, TEST_PASSWORD).await?;
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+
+    create_task(&other_session, serde_json::json!({ "title": "Secret" })).await?;
+    create_task(&session, serde_json::json!({ "title": "Mine" })).await?;
+
+    let event = subscription.next_event().await?;
+    expect_that!(
+        event["data"]["taskChanged"]["task"]["title"],
+        json_string(eq("Mine"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn task_changes_are_pushed_while_the_user_is_a_member_of_the_workspace() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    insert_test_user(
+        OTHER_USER_UUID,
+        OTHER_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let other_session =
+        UserSession::login_as(server.clone(), OTHER_USERNAME, TEST_PASSWORD).await?;
+    let response = other_session
+        .graphql(
+            "mutation { createWorkspace(name: \"Home\") { id } }",
+            serde_json::Value::Null,
+        )
+        .await?;
+    let workspace_id = response["data"]["createWorkspace"]["id"].clone();
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+    let create_shared_task = |title: &'static str| {
+        create_task(
+            &other_session,
+            serde_json::json!({ "title": title, "workspaceId": workspace_id }),
+        )
+    };
+
+    create_shared_task("Before joining").await?;
+    other_session
+        .graphql(
+            "mutation($workspaceId: UUID!, $username: String!) {
+                addWorkspaceMember(input: {
+                    workspaceId: $workspaceId, username: $username, role: VIEWER
+                }) { userId }
+            }",
+            serde_json::json!({ "workspaceId": workspace_id, "username": TEST_USERNAME }),
+        )
+        .await?;
+    create_shared_task("While a member").await?;
+    other_session
+        .graphql(
+            "mutation($workspaceId: UUID!, $userId: UUID!) {
+                removeWorkspaceMember(workspaceId: $workspaceId, userId: $userId)
+            }",
+            serde_json::json!({ "workspaceId": workspace_id, "userId": TEST_USER_UUID }),
+        )
+        .await?;
+    create_shared_task("After leaving").await?;
+    create_task(&session, serde_json::json!({ "title": "Mine" })).await?;
+
+    for title in ["While a member", "Mine"] {
+        let event = subscription.next_event().await?;
+        expect_that!(
+            event["data"]["taskChanged"]["task"]["title"],
+            json_string(eq(title))
+        );
+    }
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn scheduled_recurring_tasks_are_pushed() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    let today = Utc::now().date_naive();
+    let monday = today - chrono::TimeDelta::days(today.weekday().num_days_from_monday().into());
+    session
+        .graphql(
+            "mutation($startDate: NaiveDate!) {
+                createTask(input: {
+                    title: \"Laundry\",
+                    recurringSpec: { startDate: $startDate, pattern: { every: 1 } },
+                }) { id }
+            }",
+            serde_json::json!({ "startDate": monday }),
+        )
+        .await?;
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+
+    schedule_all_recurring_tasks_until(
+        pg_docker.db_conn(),
+        Some(today + chrono::TimeDelta::days(28)),
+    )
+    .await?;
+
+    let event = subscription.next_event().await?;
+    let event = &event["data"]["taskChanged"];
+    expect_that!(event["kind"], json_string(eq("CREATED")));
+    expect_that!(event["task"]["title"], json_string(eq("Laundry")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn task_changes_reach_other_instances() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let other_server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+    let mut subscription = Subscription::start(&other_server, authorization(&session)).await?;
+
+    create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+
+    let event = subscription.next_event().await?;
+    expect_that!(
+        event["data"]["taskChanged"]["task"]["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn subscription_ends_when_the_listener_connection_is_lost_and_listening_resumes() -> Result<()>
+{
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+
+    pg_docker
+        .db_conn()
+        .execute_unprepared(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity
+             WHERE datname = current_database() AND query LIKE 'LISTEN %'",
+        )
+        .await?;
+
+    let event = subscription.next_event().await?;
+    expect_that!(
+        event["errors"][0]["message"],
+        json_string(contains_substring("missed"))
+    );
+    expect_that!(
+        subscription.next_message().await?["type"],
+        json_string(eq("complete"))
+    );
+
+    let mut subscription = Subscription::start(server, authorization(&session)).await?;
+    create_task(&session, serde_json::json!({ "title": "Groceries" })).await?;
+    let event = subscription.next_event().await?;
+    expect_that!(
+        event["data"]["taskChanged"]["task"]["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn subscription_without_token_is_unauthorized() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let server = session.server();
+    let mut subscription = Subscription::start(server, serde_json::json!({})).await?;
+
+    let event = subscription.next_event().await?;
+    expect_that!(
+        event["errors"][0]["extensions"]["code"],
+        json_string(eq("UNAUTHORIZED"))
+    );
+    Ok(())
+}
@@ -0,0 +1,386 @@
+mod common;
+mod matchers;
+
+use common::{create_task, login_session, Result, UserSession};
+use googletest::prelude::*;
+use testlib::PgDocker;
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const CREATE_TASK_MUTATION: &str = "
+    mutation($title: String!, $cost: Int, $parentId: UUID, $completeWithSubtasks: Boolean! = false) {
+        createTask(input: {
+            title: $title,
+            cost: $cost,
+            parentId: $parentId,
+            completeWithSubtasks: $completeWithSubtasks,
+        }) { taskId }
+    }";
+const UPDATE_TASK_MUTATION: &str = "
+    mutation($input: UpdateTaskInput!) {
+        updateTask(input: $input) { taskId }
+    }";
+const TASK_QUERY: &str = "
+    query($id: UUID!) {
+        task(id: $id) {
+            title
+            cost
+            isCompleted
+            parent { title }
+            children { title cost parent { title } }
+        }
+    }";
+
+async fn update_task(session: &UserSession, input: serde_json::Value) -> Result<serde_json::Value> {
+    session
+        .graphql(UPDATE_TASK_MUTATION, serde_json::json!({ "input": input }))
+        .await
+}
+
+async fn task(session: &UserSession, task_id: &str) -> Result<serde_json::Value> {
+    let response = session
+        .graphql(TASK_QUERY, serde_json::json!({ "id": task_id }))
+        .await?;
+    expect_that!(response["errors"], json_null());
+    Ok(response["data"]["task"].clone())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn subtasks_are_listed_with_their_parent() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let parent_id = create_task(&session, serde_json::json!({ "title": "move" })).await?;
+    for (title, cost) in [("pack", Some(2)), ("drive", None), ("unpack", Some(3))] {
+        create_task(
+            &session,
+            serde_json::json!({ "title": title, "cost": cost, "parentId": parent_id }),
+        )
+        .await?;
+    }
+
+    let parent = task(&session, &parent_id).await?;
+    expect_that!(parent["parent"], json_null());
+    expect_that!(parent["cost"], eq(&serde_json::json!(5)));
+    expect_that!(
+        parent["children"],
+        eq(&serde_json::json!([
+            { "title": "pack", "cost": 2, "parent": { "title": "move" } },
+            { "title": "drive", "cost": null, "parent": { "title": "move" } },
+            { "title": "unpack", "cost": 3, "parent": { "title": "move" } },
+        ]))
+    );
+
+    let response = session
+        .graphql(
+            "query {
+                tasks(orderBy: { key: TITLE }) {
+                    edges { node { title parent { title } children { title } } }
+                }
+            }",
+            serde_json::Value::Null,
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    expect_that!(
+        response["data"]["tasks"]["edges"],
+        eq(&serde_json::json!([
+            { "node": { "title": "drive", "parent": { "title": "move" }, "children": [] } },
+            { "node": {
+                "title": "move",
+                "parent": null,
+                "children": [{ "title": "pack" }, { "title": "drive" }, { "title": "unpack" }],
+            } },
+            { "node": { "title": "pack", "parent": { "title": "move" }, "children": [] } },
+            { "node": { "title": "unpack", "parent": { "title": "move" }, "children": [] } },
+        ]))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn costs_roll_up_to_the_ancestors() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let root_id = create_task(&session, serde_json::json!({ "title": "root" })).await?;
+    let step_id = create_task(
+        &session,
+        serde_json::json!({ "title": "step", "parentId": root_id }),
+    )
+    .await?;
+    let leaf_id = create_task(
+        &session,
+        serde_json::json!({ "title": "leaf", "cost": 4, "parentId": step_id }),
+    )
+    .await?;
+    let other_id = create_task(
+        &session,
+        serde_json::json!({ "title": "other", "cost": 1, "parentId": root_id }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &root_id).await?["cost"],
+        eq(&serde_json::json!(5))
+    );
+
+    update_task(&session, serde_json::json!({ "id": leaf_id, "cost": 6 })).await?;
+    expect_that!(
+        task(&session, &step_id).await?["cost"],
+        eq(&serde_json::json!(6))
+    );
+    expect_that!(
+        task(&session, &root_id).await?["cost"],
+        eq(&serde_json::json!(7))
+    );
+
+    update_task(
+        &session,
+        serde_json::json!({ "id": leaf_id, "parentId": other_id }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &other_id).await?["cost"],
+        eq(&serde_json::json!(6))
+    );
+    expect_that!(
+        task(&session, &root_id).await?["cost"],
+        eq(&serde_json::json!(6))
+    );
+
+    session
+        .graphql(
+            "mutation($id: UUID!) { deleteTask(id: $id) }",
+            serde_json::json!({ "id": other_id }),
+        )
+        .await?;
+    let leaf = task(&session, &leaf_id).await?;
+    expect_that!(leaf["parent"], json_null());
+    expect_that!(task(&session, &root_id).await?["cost"], json_null());
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn parent_is_completed_with_its_subtasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let parent_id = create_task(
+        &session,
+        serde_json::json!({ "title": "trip", "completeWithSubtasks": true }),
+    )
+    .await?;
+    let other_parent_id = create_task(&session, serde_json::json!({ "title": "errands" })).await?;
+    let mut subtask_ids = Vec::new();
+    for (title, parent_id) in [
+        ("book", &parent_id),
+        ("pack", &parent_id),
+        ("post", &other_parent_id),
+    ] {
+        subtask_ids.push(
+            create_task(
+                &session,
+                serde_json::json!({ "title": title, "parentId": parent_id }),
+            )
+            .await?,
+        );
+    }
+
+    for subtask_id in &subtask_ids[..2] {
+        expect_that!(
+            task(&session, &parent_id).await?["isCompleted"],
+            eq(&serde_json::json!(false))
+        );
+        update_task(
+            &session,
+            serde_json::json!({ "id": subtask_id, "completeDate": "2024-03-05" }),
+        )
+        .await?;
+    }
+    expect_that!(
+        task(&session, &parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(true))
+    );
+
+    update_task(
+        &session,
+        serde_json::json!({ "id": subtask_ids[2], "completeDate": "2024-03-05" }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &other_parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(false))
+    );
+    update_task(
+        &session,
+        serde_json::json!({ "id": other_parent_id, "completeWithSubtasks": true }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &other_parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(true))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn invalid_subtasks_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let parent_id = create_task(&session, serde_json::json!({ "title": "parent" })).await?;
+    let child_id = create_task(
+        &session,
+        serde_json::json!({ "title": "child", "cost": 1, "parentId": parent_id }),
+    )
+    .await?;
+
+    let response = update_task(
+        &session,
+        serde_json::json!({ "id": parent_id, "parentId": child_id }),
+    )
+    .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["field"],
+        eq(&serde_json::json!(["parentId"]))
+    );
+
+    let response = update_task(&session, serde_json::json!({ "id": parent_id, "cost": 3 })).await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["field"],
+        eq(&serde_json::json!(["cost"]))
+    );
+
+    let response = session
+        .graphql(
+            "mutation($parentId: UUID!) {
+                createTask(input: {
+                    title: \"daily\",
+                    parentId: $parentId,
+                    recurringSpec: { startDate: \"2024-03-04\", pattern: { every: 1 } },
+                }) { taskId }
+            }",
+            serde_json::json!({ "parentId": parent_id }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["field"],
+        eq(&serde_json::json!(["recurringSpec"]))
+    );
+
+    let response = session
+        .graphql(
+            CREATE_TASK_MUTATION,
+            serde_json::json!({ "title": "orphan", "parentId": Uuid::new_v4() }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["code"],
+        json_string(eq("NOT_FOUND"))
+    );
+    expect_that!(
+        task(&session, &parent_id).await?["cost"],
+        eq(&serde_json::json!(1))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn parent_completed_with_its_subtasks_is_reopened_with_them() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let parent_id = create_task(
+        &session,
+        serde_json::json!({ "title": "trip", "completeWithSubtasks": true }),
+    )
+    .await?;
+    let subtask_id = create_task(
+        &session,
+        serde_json::json!({ "title": "book", "parentId": parent_id }),
+    )
+    .await?;
+
+    update_task(
+        &session,
+        serde_json::json!({ "id": subtask_id, "completeDate": "2024-03-05" }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(true))
+    );
+    update_task(
+        &session,
+        serde_json::json!({ "id": subtask_id, "completeDate": null }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(false))
+    );
+
+    update_task(
+        &session,
+        serde_json::json!({ "id": subtask_id, "completeDate": "2024-03-05" }),
+    )
+    .await?;
+    create_task(
+        &session,
+        serde_json::json!({ "title": "pack", "parentId": parent_id }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(false))
+    );
+
+    // A parent completed by the user stays completed.
+    update_task(
+        &session,
+        serde_json::json!({ "id": parent_id, "completeDate": "2024-03-06" }),
+    )
+    .await?;
+    update_task(
+        &session,
+        serde_json::json!({ "id": subtask_id, "completeDate": null }),
+    )
+    .await?;
+    expect_that!(
+        task(&session, &parent_id).await?["isCompleted"],
+        eq(&serde_json::json!(true))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn subtask_costs_too_large_to_sum_are_rejected() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let session = login_session(&pg_docker).await?;
+    let parent_id = create_task(&session, serde_json::json!({ "title": "parent" })).await?;
+    create_task(
+        &session,
+        serde_json::json!({ "title": "big", "cost": i32::MAX, "parentId": parent_id }),
+    )
+    .await?;
+
+    let response = session
+        .graphql(
+            CREATE_TASK_MUTATION,
+            serde_json::json!({ "title": "one more", "cost": 1, "parentId": parent_id }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["extensions"]["field"],
+        eq(&serde_json::json!(["cost"]))
+    );
+    expect_that!(
+        task(&session, &parent_id).await?["children"]
+            .as_array()
+            .map(Vec::len),
+        some(eq(1))
+    );
+    Ok(())
+}
@@ -0,0 +1,247 @@
+mod common;
+mod matchers;
+
+use chrono::Utc;
+use common::{
+    insert_test_user, Result, TestServer, UserSession, TEST_PASSWORD, TEST_USERNAME, TEST_USER_UUID,
+};
+use data_encoding::BASE32_NOPAD;
+use googletest::prelude::*;
+use hmac::{Hmac, Mac};
+use planner_backend::{
+    delete_expired_login_challenges, reset_two_factor, Config, TotpEncryptionKey,
+};
+use reqwest::StatusCode;
+use sea_orm::ConnectionTrait;
+use sha1::Sha1;
+use testlib::PgDocker;
+
+use crate::matchers::{json_null, json_string};
+
+const ENROLL_MUTATION: &str = "mutation { enrollTotp { secret provisioningUri } }";
+const CONFIRM_MUTATION: &str = "mutation($code: String!) { confirmTotp(code: $code) }";
+const DISABLE_MUTATION: &str = "mutation($code: String!) { disableTotp(code: $code) }";
+
+/// Computes the code of the authenticator app, `steps` periods away from now.
+fn totp_code(secret: &str, steps: i64) -> String {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    let counter = (Utc::now().timestamp() / 30 + steps) as u64;
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(&secret).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[19] & 0xf) as usize;
+    let binary = u32::from_be_bytes(digest[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+    format!("{:06}", binary % 1_000_000)
+}
+
+async fn spawn_server(pg_docker: &PgDocker) -> Result<TestServer> {
+    insert_test_user(
+        TEST_USER_UUID,
+        TEST_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    Ok(TestServer::spawn_with_config(
+        pg_docker.db_conn().clone(),
+        Config {
+            totp_encryption_key: Some(TotpEncryptionKey::generate()),
+            ..Default::default()
+        },
+    )
+    .await)
+}
+
+/// The state of the test user after enabling two-factor authentication.
+struct Enabled {
+    /// The session two-factor authentication was enabled in.
+    session: UserSession,
+    secret: String,
+    /// The code that was used to confirm the enrollment.
+    confirmed_code: String,
+    recovery_codes: Vec<String>,
+}
+
+async fn enable_two_factor(server: &TestServer) -> Result<Enabled> {
+    let session = UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+    let response = session
+        .graphql(ENROLL_MUTATION, serde_json::json!({}))
+        .await?;
+    let secret = response["data"]["enrollTotp"]["secret"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let confirmed_code = totp_code(&secret, 0);
+    let response = session
+        .graphql(
+            CONFIRM_MUTATION,
+            serde_json::json!({ "code": confirmed_code }),
+        )
+        .await?;
+    let recovery_codes = serde_json::from_value(response["data"]["confirmTotp"].clone())?;
+    Ok(Enabled {
+        session,
+        secret,
+        confirmed_code,
+        recovery_codes,
+    })
+}
+
+async fn login(server: &TestServer) -> Result<serde_json::Value> {
+    Ok(server
+        .post("/auth/login")
+        .json(&serde_json::json!({
+            "username": TEST_USERNAME,
+            "password": TEST_PASSWORD,
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn login_with_code(
+    server: &TestServer,
+    challenge: &serde_json::Value,
+    code: &str,
+) -> Result<reqwest::Response> {
+    Ok(server
+        .post("/auth/login/totp")
+        .json(&serde_json::json!({
+            "totp_challenge": challenge["totp_challenge"],
+            "code": code,
+        }))
+        .send()
+        .await?)
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn enrolling_returns_a_provisioning_uri() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let session = UserSession::login_as(server.clone(), TEST_USERNAME, TEST_PASSWORD).await?;
+
+    let response = session
+        .graphql(ENROLL_MUTATION, serde_json::json!({}))
+        .await?;
+
+    expect_that!(
+        response["data"]["enrollTotp"]["provisioningUri"],
+        json_string(starts_with("otpauth://totp/Planner:meteor?secret="))
+    );
+    // Enrolling alone does not enable two-factor authentication.
+    expect_that!(login(&server).await?["token"], json_string(anything()));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn login_needs_the_code_once_enabled() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let enabled = enable_two_factor(&server).await?;
+
+    let challenge = login(&server).await?;
+    expect_that!(challenge["token"], json_null());
+    expect_that!(challenge["totp_challenge"], json_string(anything()));
+    expect_that!(enabled.recovery_codes.len(), eq(10));
+
+    // The code used for confirming cannot be replayed, so the code of the next period is needed.
+    let response = login_with_code(&server, &challenge, &enabled.confirmed_code).await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    let response = login_with_code(&server, &challenge, &totp_code(&enabled.secret, 1)).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    let body: serde_json::Value = response.json().await?;
+    expect_that!(body["token"], json_string(anything()));
+
+    // The challenge is single-use.
+    let response = login_with_code(&server, &challenge, &enabled.recovery_codes[0]).await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn expired_challenges_are_deleted() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let enabled = enable_two_factor(&server).await?;
+
+    login(&server).await?;
+    pg_docker
+        .db_conn()
+        .execute_unprepared("UPDATE login_challenges SET expires_at = now() - interval '1 minute'")
+        .await?;
+    let challenge = login(&server).await?;
+
+    expect_that!(
+        delete_expired_login_challenges(pg_docker.db_conn()).await?,
+        eq(1)
+    );
+    let response = login_with_code(&server, &challenge, &enabled.recovery_codes[0]).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn recovery_codes_can_be_used_once() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let recovery_codes = enable_two_factor(&server).await?.recovery_codes;
+
+    let response = login_with_code(&server, &login(&server).await?, &recovery_codes[0]).await?;
+    expect_that!(response.status(), eq(StatusCode::OK));
+
+    let response = login_with_code(&server, &login(&server).await?, &recovery_codes[0]).await?;
+    expect_that!(response.status(), eq(StatusCode::UNAUTHORIZED));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn disabling_needs_a_code() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    let Enabled {
+        session,
+        recovery_codes,
+        ..
+    } = enable_two_factor(&server).await?;
+
+    let response = session
+        .graphql(
+            DISABLE_MUTATION,
+            serde_json::json!({ "code": "not-a-code" }),
+        )
+        .await?;
+    expect_that!(
+        response["errors"][0]["message"],
+        json_string(contains_substring("not valid"))
+    );
+
+    let response = session
+        .graphql(
+            DISABLE_MUTATION,
+            serde_json::json!({ "code": recovery_codes[0] }),
+        )
+        .await?;
+    expect_that!(response["data"]["disableTotp"], eq(true));
+    expect_that!(login(&server).await?["token"], json_string(anything()));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn admin_can_reset_two_factor() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let server = spawn_server(&pg_docker).await?;
+    enable_two_factor(&server).await?;
+
+    reset_two_factor(TEST_USERNAME, pg_docker.db_conn()).await?;
+
+    expect_that!(login(&server).await?["token"], json_string(anything()));
+    Ok(())
+}
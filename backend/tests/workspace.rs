@@ -0,0 +1,261 @@
+mod common;
+mod matchers;
+
+use common::{create_task, insert_test_user, Result, TestServer, UserSession};
+use googletest::prelude::*;
+use testlib::{test_uuid, PgDocker};
+use uuid::Uuid;
+
+use crate::matchers::{json_null, json_string};
+
+const OWNER_USERNAME: &str = "meteor";
+const OWNER_UUID: Uuid = test_uuid(1);
+const MEMBER_USERNAME: &str = "comet";
+const MEMBER_UUID: Uuid = test_uuid(2);
+const TEST_PASSWORD: &str = "test-password";
+
+const CREATE_WORKSPACE_MUTATION: &str = "
+    mutation($name: String!) {
+        createWorkspace(name: $name) { id }
+    }";
+const ADD_MEMBER_MUTATION: &str = "
+    mutation($workspaceId: UUID!, $username: String!, $role: WorkspaceRole!) {
+        addWorkspaceMember(input: { workspaceId: $workspaceId, username: $username, role: $role }) {
+            userId
+        }
+    }";
+const REMOVE_MEMBER_MUTATION: &str = "
+    mutation($workspaceId: UUID!, $userId: UUID!) {
+        removeWorkspaceMember(workspaceId: $workspaceId, userId: $userId)
+    }";
+const CREATE_TASK_MUTATION: &str = "
+    mutation($workspaceId: UUID) {
+        createTask(input: { title: \"Groceries\", workspaceId: $workspaceId }) { taskId workspaceId }
+    }";
+const UPDATE_TASK_MUTATION: &str = "
+    mutation($id: UUID!) {
+        updateTask(input: { id: $id, title: \"Renamed\" }) { id }
+    }";
+const DELETE_TASK_MUTATION: &str = "mutation($id: UUID!) { deleteTask(id: $id) }";
+const TASKS_QUERY: &str = "query { tasks { edges { node { id title } } } }";
+
+/// Logs in the owner and another user, and creates a shared workspace owned by the former.
+async fn setup(pg_docker: &PgDocker) -> Result<(UserSession, UserSession, String)> {
+    insert_test_user(
+        OWNER_UUID,
+        OWNER_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    insert_test_user(
+        MEMBER_UUID,
+        MEMBER_USERNAME,
+        TEST_PASSWORD,
+        pg_docker.db_conn(),
+    )
+    .await?;
+    let server = TestServer::spawn(pg_docker.db_conn().clone()).await;
+    let owner = UserSession::login_as(server.clone(), OWNER_USERNAME, TEST_PASSWORD).await?;
+    let member = UserSession::login_as(server, MEMBER_USERNAME, TEST_PASSWORD).await?;
+
+    let response = owner
+        .graphql(
+            CREATE_WORKSPACE_MUTATION,
+            serde_json::json!({ "name": "Household" }),
+        )
+        .await?;
+    let workspace_id = response["data"]["createWorkspace"]["id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    Ok((owner, member, workspace_id))
+}
+
+async fn add_member(
+    owner: &UserSession,
+    workspace_id: &str,
+    username: &str,
+    role: &str,
+) -> Result<serde_json::Value> {
+    owner
+        .graphql(
+            ADD_MEMBER_MUTATION,
+            serde_json::json!({ "workspaceId": workspace_id, "username": username, "role": role }),
+        )
+        .await
+}
+
+fn error_message(response: &serde_json::Value) -> &serde_json::Value {
+    &response["errors"][0]["message"]
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_are_created_in_the_personal_workspace_by_default() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, _, _) = setup(&pg_docker).await?;
+
+    let response = owner
+        .graphql(CREATE_TASK_MUTATION, serde_json::json!({}))
+        .await?;
+    let workspaces = owner
+        .graphql(
+            "query { workspaces { id isPersonal role } }",
+            serde_json::json!({}),
+        )
+        .await?;
+
+    expect_that!(workspaces["data"]["workspaces"][0]["isPersonal"], eq(true));
+    expect_that!(
+        workspaces["data"]["workspaces"][0]["role"],
+        json_string(eq("OWNER"))
+    );
+    expect_that!(
+        response["data"]["createTask"]["workspaceId"],
+        eq(&workspaces["data"]["workspaces"][0]["id"])
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn members_see_the_tasks_of_shared_workspaces() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, member, workspace_id) = setup(&pg_docker).await?;
+    create_task(
+        &owner,
+        serde_json::json!({ "title": "Groceries", "workspaceId": workspace_id }),
+    )
+    .await?;
+
+    let response = member.graphql(TASKS_QUERY, serde_json::json!({})).await?;
+    expect_that!(
+        response["data"]["tasks"]["edges"].as_array().map(Vec::len),
+        some(eq(0))
+    );
+
+    add_member(&owner, &workspace_id, MEMBER_USERNAME, "VIEWER").await?;
+    let response = member.graphql(TASKS_QUERY, serde_json::json!({})).await?;
+    expect_that!(
+        response["data"]["tasks"]["edges"][0]["node"]["title"],
+        json_string(eq("Groceries"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn viewers_cannot_modify_tasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, member, workspace_id) = setup(&pg_docker).await?;
+    let task_id = create_task(
+        &owner,
+        serde_json::json!({ "title": "Groceries", "workspaceId": workspace_id }),
+    )
+    .await?;
+    add_member(&owner, &workspace_id, MEMBER_USERNAME, "VIEWER").await?;
+
+    let update = member
+        .graphql(UPDATE_TASK_MUTATION, serde_json::json!({ "id": task_id }))
+        .await?;
+    let delete = member
+        .graphql(DELETE_TASK_MUTATION, serde_json::json!({ "id": task_id }))
+        .await?;
+    let response = member
+        .graphql(
+            CREATE_TASK_MUTATION,
+            serde_json::json!({ "workspaceId": workspace_id }),
+        )
+        .await?;
+
+    expect_that!(
+        error_message(&update),
+        json_string(starts_with("permission denied"))
+    );
+    expect_that!(
+        error_message(&delete),
+        json_string(starts_with("permission denied"))
+    );
+    expect_that!(
+        error_message(&response),
+        json_string(starts_with("permission denied"))
+    );
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn editors_can_modify_tasks() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, member, workspace_id) = setup(&pg_docker).await?;
+    let task_id = create_task(
+        &owner,
+        serde_json::json!({ "title": "Groceries", "workspaceId": workspace_id }),
+    )
+    .await?;
+    add_member(&owner, &workspace_id, MEMBER_USERNAME, "EDITOR").await?;
+
+    let update = member
+        .graphql(UPDATE_TASK_MUTATION, serde_json::json!({ "id": task_id }))
+        .await?;
+    let delete = member
+        .graphql(DELETE_TASK_MUTATION, serde_json::json!({ "id": task_id }))
+        .await?;
+
+    expect_that!(update["errors"], json_null());
+    expect_that!(delete["data"]["deleteTask"], json_string(eq(&task_id)));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn tasks_of_other_workspaces_are_not_found() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, member, workspace_id) = setup(&pg_docker).await?;
+    let task_id = create_task(
+        &owner,
+        serde_json::json!({ "title": "Groceries", "workspaceId": workspace_id }),
+    )
+    .await?;
+
+    let update = member
+        .graphql(UPDATE_TASK_MUTATION, serde_json::json!({ "id": task_id }))
+        .await?;
+    let add = add_member(&member, &workspace_id, MEMBER_USERNAME, "OWNER").await?;
+
+    expect_that!(
+        error_message(&update),
+        json_string(ends_with("is not found"))
+    );
+    expect_that!(error_message(&add), json_string(ends_with("is not found")));
+    Ok(())
+}
+
+#[googletest::test]
+#[tokio::test]
+async fn last_owner_cannot_leave_the_workspace() -> Result<()> {
+    let pg_docker = PgDocker::new().await;
+    let (owner, member, workspace_id) = setup(&pg_docker).await?;
+    add_member(&owner, &workspace_id, MEMBER_USERNAME, "EDITOR").await?;
+
+    let response = owner
+        .graphql(
+            REMOVE_MEMBER_MUTATION,
+            serde_json::json!({ "workspaceId": workspace_id, "userId": OWNER_UUID }),
+        )
+        .await?;
+    expect_that!(
+        error_message(&response),
+        json_string(contains_substring("at least one owner"))
+    );
+
+    let response = member
+        .graphql(
+            REMOVE_MEMBER_MUTATION,
+            serde_json::json!({ "workspaceId": workspace_id, "userId": MEMBER_UUID }),
+        )
+        .await?;
+    expect_that!(response["errors"], json_null());
+    Ok(())
+}
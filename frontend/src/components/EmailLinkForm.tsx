@@ -0,0 +1,59 @@
+import { FormEvent, useState } from 'react';
+
+import { Button } from '@/components/ui/button';
+import { Input } from '@/components/ui/input';
+import { Label } from '@/components/ui/label';
+
+interface EmailLinkFormProps {
+  /** The endpoint that mails the link, e.g. `/auth/magic-link`. */
+  endpoint: string;
+  submitLabel: string;
+}
+
+/** Asks for the email address to send a login or password reset link to. */
+export default function EmailLinkForm({ endpoint, submitLabel }: EmailLinkFormProps) {
+  const [email, setEmail] = useState('');
+  const [sent, setSent] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  async function doFetch() {
+    const response = await fetch(endpoint, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ email }),
+    });
+    if (!response.ok) {
+      setError('Please enter a valid email address');
+      return;
+    }
+    setSent(true);
+  }
+
+  function onSubmit(event: FormEvent) {
+    event.preventDefault();
+    void doFetch();
+  }
+
+  if (sent) {
+    return <p>If {email} belongs to an account, a link has been sent to it.</p>;
+  }
+
+  return (
+    <form className="flex flex-col gap-4" onSubmit={onSubmit}>
+      <div className="flex flex-col gap-2">
+        <Label htmlFor="email">Email</Label>
+        <Input
+          id="email"
+          type="email"
+          autoComplete="email"
+          value={email}
+          onChange={(e) => {
+            setEmail(e.target.value);
+          }}
+        />
+      </div>
+      {error != null && <p className="text-sm text-destructive">{error}</p>}
+      <Button type="submit">{submitLabel}</Button>
+    </form>
+  );
+}
@@ -13,7 +13,7 @@ import { TypedDocumentNode as DocumentNode } from '@graphql-typed-document-node/
  * Therefore it is highly recommended to use the babel or swc plugin for production.
  */
 const documents = {
-    "\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }) {\n      id\n      title\n      cost\n      isCompleted\n      scheduledOn {\n        type\n        date\n      }\n      recurring {\n        startDate\n        pattern {\n          every\n        }\n      }\n    }\n  }\n": types.ListTasksDocument,
+    "\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }, first: 200) {\n      edges {\n        node {\n          id\n          taskId\n          title\n          cost\n          isCompleted\n          scheduledOn {\n            type\n            date\n          }\n          recurring {\n            startDate\n            pattern {\n              every\n            }\n          }\n        }\n      }\n    }\n  }\n": types.ListTasksDocument,
     "\n  mutation CreateTask($title: String!, $cost: Int, $scheduledOn: InputEpoch, $recurringSpec: InputRecurringSpec) {\n    createTask(input: {\n      title: $title\n      cost: $cost\n      scheduledOn: $scheduledOn\n      recurringSpec: $recurringSpec\n    }) {\n      id\n      title\n      cost\n      isCompleted\n    }\n  }\n": types.CreateTaskDocument,
     "\n  mutation UpdateTask($input: UpdateTaskInput!) {\n    updateTask(input: $input) {\n      id\n      title\n      cost\n      isCompleted\n      scheduledOn {\n        type\n        date\n      }\n    }\n  }\n": types.UpdateTaskDocument,
     "\n  mutation UpdateTaskCompleteDate($id: UUID!, $completeDate: NaiveDate) {\n    updateTask(input: { id: $id, completeDate: $completeDate }) {\n      id\n      title\n      cost\n      isCompleted\n      scheduledOn {\n        type\n        date\n      }\n    }\n  }\n": types.UpdateTaskCompleteDateDocument,
@@ -38,7 +38,7 @@ export function gql(source: string): unknown;
 /**
  * The gql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
-export function gql(source: "\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }) {\n      id\n      title\n      cost\n      isCompleted\n      scheduledOn {\n        type\n        date\n      }\n      recurring {\n        startDate\n        pattern {\n          every\n        }\n      }\n    }\n  }\n"): (typeof documents)["\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }) {\n      id\n      title\n      cost\n      isCompleted\n      scheduledOn {\n        type\n        date\n      }\n      recurring {\n        startDate\n        pattern {\n          every\n        }\n      }\n    }\n  }\n"];
+export function gql(source: "\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }, first: 200) {\n      edges {\n        node {\n          id\n          taskId\n          title\n          cost\n          isCompleted\n          scheduledOn {\n            type\n            date\n          }\n          recurring {\n            startDate\n            pattern {\n              every\n            }\n          }\n        }\n      }\n    }\n  }\n"): (typeof documents)["\n  query ListTasks($viewType: ViewType!, $epoch: InputEpoch) {\n    tasks(filter: {\n      viewFilter: {\n        type: $viewType,\n        epoch: $epoch,\n      }\n    }, first: 200) {\n      edges {\n        node {\n          id\n          taskId\n          title\n          cost\n          isCompleted\n          scheduledOn {\n            type\n            date\n          }\n          recurring {\n            startDate\n            pattern {\n              every\n            }\n          }\n        }\n      }\n    }\n  }\n"];
 /**
  * The gql function is used to parse GraphQL queries into a document that can be used by GraphQL clients.
  */
@@ -7,19 +7,24 @@ export const LIST_TASKS = gql(`
         type: $viewType,
         epoch: $epoch,
       }
-    }) {
-      id
-      title
-      cost
-      isCompleted
-      scheduledOn {
-        type
-        date
-      }
-      recurring {
-        startDate
-        pattern {
-          every
+    }, first: 200) {
+      edges {
+        node {
+          id
+          taskId
+          title
+          cost
+          isCompleted
+          scheduledOn {
+            type
+            date
+          }
+          recurring {
+            startDate
+            pattern {
+              every
+            }
+          }
         }
       }
     }
@@ -0,0 +1,119 @@
+export type LoginResponse = TokenResponse | CookieSessionResponse;
+
+interface TokenResponse {
+  token: string;
+  refresh_token: string;
+}
+
+// Returned instead of the tokens when the backend keeps the session in cookies.
+interface CookieSessionResponse {
+  csrf_token: string;
+}
+
+// Returned by the login instead of the tokens when the user has enabled two-factor authentication.
+export interface TotpChallenge {
+  totp_challenge: string;
+  expires_at: string;
+}
+
+const TOKEN_KEY = 'bearerToken';
+const REFRESH_TOKEN_KEY = 'refreshToken';
+const COOKIE_SESSION_KEY = 'cookieSession';
+const CSRF_TOKEN_COOKIE = 'planner_csrf_token';
+// Refresh the access token this long before it expires.
+const REFRESH_MARGIN_SECONDS = 60;
+
+export function storeTokens(response: LoginResponse) {
+  if ('csrf_token' in response) {
+    // The tokens are in HttpOnly cookies, only remember that they are.
+    localStorage.setItem(COOKIE_SESSION_KEY, 'true');
+    return;
+  }
+  localStorage.setItem(TOKEN_KEY, response.token);
+  localStorage.setItem(REFRESH_TOKEN_KEY, response.refresh_token);
+}
+
+export function clearTokens() {
+  localStorage.removeItem(TOKEN_KEY);
+  localStorage.removeItem(REFRESH_TOKEN_KEY);
+  localStorage.removeItem(COOKIE_SESSION_KEY);
+}
+
+function isCookieSession(): boolean {
+  return localStorage.getItem(COOKIE_SESSION_KEY) != null;
+}
+
+function csrfToken(): string | null {
+  const prefix = `${CSRF_TOKEN_COOKIE}=`;
+  const cookie = document.cookie.split('; ').find((cookie) => cookie.startsWith(prefix));
+  return cookie != null ? decodeURIComponent(cookie.slice(prefix.length)) : null;
+}
+
+/**
+ * The headers that authenticate a request with the access token, or with the session cookies, in
+ * which case `token` is the CSRF token.
+ */
+export function authHeaders(token: string | null): Record<string, string> {
+  if (isCookieSession()) {
+    return token ? { 'x-csrf-token': token } : {};
+  }
+  return { authorization: token ? `Bearer ${token}` : '' };
+}
+
+function tokenExpiresSoon(token: string): boolean {
+  try {
+    const encoded = token.split('.')[1].replace(/-/g, '+').replace(/_/g, '/');
+    const payload = JSON.parse(atob(encoded)) as { exp: number };
+    return payload.exp - REFRESH_MARGIN_SECONDS < Date.now() / 1000;
+  } catch {
+    return true;
+  }
+}
+
+let pendingRefresh: Promise<string | null> | null = null;
+
+/** Gets a new access token with the refresh token, or returns `null` if the session is over. */
+export async function refreshAccessToken(): Promise<string | null> {
+  const refreshToken = localStorage.getItem(REFRESH_TOKEN_KEY);
+  if (refreshToken == null && !isCookieSession()) {
+    return null;
+  }
+  // Refresh tokens are single-use, so concurrent requests must share one refresh.
+  pendingRefresh ??= refresh(refreshToken).finally(() => {
+    pendingRefresh = null;
+  });
+  return pendingRefresh;
+}
+
+async function refresh(refreshToken: string | null): Promise<string | null> {
+  const response = await fetch('/auth/refresh', {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    // In a cookie session the browser sends the refresh token cookie instead.
+    body: JSON.stringify(refreshToken != null ? { refresh_token: refreshToken } : {}),
+  });
+  if (!response.ok) {
+    clearTokens();
+    return null;
+  }
+  const responseJson = (await response.json()) as LoginResponse;
+  storeTokens(responseJson);
+  return 'csrf_token' in responseJson ? responseJson.csrf_token : responseJson.token;
+}
+
+/** Returns a valid access token, refreshing it first if it is about to expire. */
+export async function getAccessToken(): Promise<string | null> {
+  if (isCookieSession()) {
+    // The expiry of the cookie is unknown to scripts, an expired one is refreshed on
+    // `TOKEN_EXPIRED`.
+    return csrfToken();
+  }
+  const token = localStorage.getItem(TOKEN_KEY);
+  if (token != null && !tokenExpiresSoon(token)) {
+    return token;
+  }
+  if (localStorage.getItem(REFRESH_TOKEN_KEY) == null) {
+    return token;
+  }
+  return refreshAccessToken();
+}
@@ -0,0 +1,44 @@
+/** The `code` of the errors of the backend, in GraphQL error extensions and REST error bodies. */
+export type ErrorCode =
+  | 'BAD_REQUEST'
+  | 'QUERY_LIMIT_EXCEEDED'
+  | 'PAYLOAD_TOO_LARGE'
+  | 'INVALID_INPUT'
+  | 'NOT_FOUND'
+  | 'PERMISSION_DENIED'
+  | 'FORBIDDEN'
+  | 'UNAUTHORIZED'
+  | 'TOKEN_EXPIRED'
+  | 'TOKEN_MALFORMED'
+  | 'TOKEN_INVALID'
+  | 'INVALID_CREDENTIALS'
+  | 'INVALID_INVITE_CODE'
+  | 'USERNAME_TAKEN'
+  | 'INVALID_CSRF_TOKEN'
+  | 'TOO_MANY_REQUESTS'
+  | 'EVENTS_MISSED'
+  | 'INTERNAL';
+
+/** The body of an error response of the REST endpoints. */
+export interface ApiError {
+  code: ErrorCode;
+  message: string;
+  /** The path of the input that is not valid, for `INVALID_INPUT`. */
+  field?: string[];
+  /** The type of the resource, for `NOT_FOUND`. */
+  resource?: string;
+  retry_after?: number;
+  request_id?: string;
+}
+
+/** Reads the error of a failed response, falling back to its status for non-JSON bodies. */
+export async function readApiError(response: Response): Promise<ApiError> {
+  try {
+    return (await response.json()) as ApiError;
+  } catch {
+    return {
+      code: response.status >= 500 ? 'INTERNAL' : 'BAD_REQUEST',
+      message: response.statusText,
+    };
+  }
+}
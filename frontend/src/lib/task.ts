@@ -179,7 +179,7 @@ export class Task {
 
   static fromGQL(task: GQLTask): Task {
     return new Task(
-      task.id,
+      task.taskId,
       task.title,
       task.isCompleted,
       task.cost ?? undefined,
@@ -1,4 +1,10 @@
-import { ApolloClient, ApolloProvider, InMemoryCache, createHttpLink } from '@apollo/client';
+import {
+  ApolloClient,
+  ApolloProvider,
+  InMemoryCache,
+  createHttpLink,
+  fromPromise,
+} from '@apollo/client';
 import { setContext } from '@apollo/client/link/context';
 import { onError } from '@apollo/client/link/error';
 import { StrictMode } from 'react';
@@ -7,10 +13,14 @@ import { Navigate, RouterProvider, createBrowserRouter } from 'react-router-dom'
 
 import { ThemeProvider } from './components/themeProvider.tsx';
 import './index.css';
+import { authHeaders, clearTokens, getAccessToken, refreshAccessToken } from './lib/auth.ts';
 import { setInitialDateOptions } from './lib/date.ts';
 import App from './routes/App.tsx';
 import Login from './routes/Login.tsx';
+import MagicLink from './routes/MagicLink.tsx';
+import OidcCallback from './routes/OidcCallback.tsx';
 import Planning from './routes/Planning.tsx';
+import ResetPassword from './routes/ResetPassword.tsx';
 import Tasks from './routes/Tasks.tsx';
 
 setInitialDateOptions();
@@ -18,15 +28,33 @@ setInitialDateOptions();
 const httpLink = createHttpLink({
   uri: '/graphql',
 });
-const errorLink = onError(({ graphQLErrors, networkError }) => {
+
+function redirectToLogin() {
+  clearTokens();
+  if (router != null) {
+    void router.navigate('/login');
+  }
+}
+
+const errorLink = onError(({ graphQLErrors, networkError, operation, forward }) => {
   if (graphQLErrors != null) {
     for (const error of graphQLErrors) {
       const code = error.extensions?.code;
       console.log('code =', code);
-      if (code == 'UNAUTHORIZED') {
-        if (router != null) {
-          void router.navigate('/login');
-        }
+      if (code == 'TOKEN_EXPIRED') {
+        // Retry once with a fresh access token.
+        return fromPromise(refreshAccessToken()).flatMap((token) => {
+          if (token == null) {
+            redirectToLogin();
+          }
+          operation.setContext({
+            headers: authHeaders(token),
+          });
+          return forward(operation);
+        });
+      }
+      if (code == 'UNAUTHORIZED' || code == 'TOKEN_INVALID' || code == 'TOKEN_MALFORMED') {
+        redirectToLogin();
       }
     }
   }
@@ -37,13 +65,13 @@ const errorLink = onError(({ graphQLErrors, networkError }) => {
     networkError,
   );
 });
-const authLink = setContext((_, { headers }) => {
-  const token = localStorage.getItem('bearerToken');
+const authLink = setContext(async (_, { headers }) => {
+  const token = await getAccessToken();
   return {
     // eslint-disable-next-line @typescript-eslint/no-unsafe-assignment
     headers: {
       ...headers,
-      authorization: token ? `Bearer ${token}` : '',
+      ...authHeaders(token),
     },
   };
 });
@@ -89,6 +117,18 @@ const router = createBrowserRouter([
     path: '/login',
     element: <Login />,
   },
+  {
+    path: '/login/oidc',
+    element: <OidcCallback />,
+  },
+  {
+    path: '/login/magic-link',
+    element: <MagicLink />,
+  },
+  {
+    path: '/reset-password',
+    element: <ResetPassword />,
+  },
 ]);
 
 createRoot(document.getElementById('root')!).render(
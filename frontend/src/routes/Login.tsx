@@ -1,32 +1,143 @@
-import { useNavigate } from 'react-router-dom';
+import { FormEvent, useState } from 'react';
+import { Link, useLocation, useNavigate } from 'react-router-dom';
 
 import { Button } from '@/components/ui/button';
-
-interface LoginResponse {
-  token: string;
-}
+import { Input } from '@/components/ui/input';
+import { Label } from '@/components/ui/label';
+import { LoginResponse, storeTokens, TotpChallenge } from '@/lib/auth';
+import { readApiError } from '@/lib/error';
 
 export default function Login() {
   const navigate = useNavigate();
+  const location = useLocation();
+  const [username, setUsername] = useState('');
+  const [password, setPassword] = useState('');
+  // A login link of a user with two-factor authentication leads here with the challenge.
+  const [totpChallenge, setTotpChallenge] = useState<string | null>(
+    (location.state as { totpChallenge?: string } | null)?.totpChallenge ?? null,
+  );
+  const [code, setCode] = useState('');
+  const [error, setError] = useState<string | null>(null);
+
   async function doFetch() {
     const response = await fetch('/auth/login', {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
-      body: JSON.stringify({ username: 'meteor' }),
+      body: JSON.stringify({ username, password }),
+    });
+    if (!response.ok) {
+      const error = await readApiError(response);
+      setError(
+        error.code === 'TOO_MANY_REQUESTS'
+          ? 'Too many attempts, try again later'
+          : 'Invalid username or password',
+      );
+      return;
+    }
+    const responseJson = (await response.json()) as LoginResponse | TotpChallenge;
+    if ('totp_challenge' in responseJson) {
+      setError(null);
+      setTotpChallenge(responseJson.totp_challenge);
+      return;
+    }
+    storeTokens(responseJson);
+    navigate('/');
+  }
+
+  async function submitCode(challenge: string) {
+    const response = await fetch('/auth/login/totp', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ totp_challenge: challenge, code }),
     });
+    if (!response.ok) {
+      setError('Invalid code');
+      return;
+    }
     const responseJson = (await response.json()) as LoginResponse;
-    if (responseJson.token != null) {
-      localStorage.setItem('bearerToken', responseJson.token);
-      navigate('/');
+    storeTokens(responseJson);
+    navigate('/');
+  }
+
+  function onSubmit(event: FormEvent) {
+    event.preventDefault();
+    if (totpChallenge != null) {
+      void submitCode(totpChallenge);
+    } else {
+      void doFetch();
+    }
+  }
+
+  async function loginWithSso() {
+    const response = await fetch('/auth/oidc/authorize');
+    if (!response.ok) {
+      setError('Single sign-on is not available');
+      return;
     }
+    const responseJson = (await response.json()) as { authorization_url: string };
+    window.location.assign(responseJson.authorization_url);
   }
+
+  if (totpChallenge != null) {
+    return (
+      <form className="mx-auto flex max-w-sm flex-col gap-4 p-8" onSubmit={onSubmit}>
+        <div className="flex flex-col gap-2">
+          <Label htmlFor="code">Authentication code or recovery code</Label>
+          <Input
+            id="code"
+            autoComplete="one-time-code"
+            value={code}
+            onChange={(e) => {
+              setCode(e.target.value);
+            }}
+          />
+        </div>
+        {error != null && <p className="text-sm text-destructive">{error}</p>}
+        <Button type="submit">Verify</Button>
+      </form>
+    );
+  }
+
   return (
-    <Button
-      onClick={() => {
-        void doFetch();
-      }}
-    >
-      Login
-    </Button>
+    <form className="mx-auto flex max-w-sm flex-col gap-4 p-8" onSubmit={onSubmit}>
+      <div className="flex flex-col gap-2">
+        <Label htmlFor="username">Username</Label>
+        <Input
+          id="username"
+          autoComplete="username"
+          value={username}
+          onChange={(e) => {
+            setUsername(e.target.value);
+          }}
+        />
+      </div>
+      <div className="flex flex-col gap-2">
+        <Label htmlFor="password">Password</Label>
+        <Input
+          id="password"
+          type="password"
+          autoComplete="current-password"
+          value={password}
+          onChange={(e) => {
+            setPassword(e.target.value);
+          }}
+        />
+      </div>
+      {error != null && <p className="text-sm text-destructive">{error}</p>}
+      <Button type="submit">Login</Button>
+      <Button
+        type="button"
+        variant="outline"
+        onClick={() => {
+          void loginWithSso();
+        }}
+      >
+        Login with SSO
+      </Button>
+      <div className="flex justify-between text-sm">
+        <Link to="/login/magic-link">Email me a login link</Link>
+        <Link to="/reset-password">Forgot password?</Link>
+      </div>
+    </form>
   );
 }
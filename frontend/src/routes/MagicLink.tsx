@@ -0,0 +1,67 @@
+import { useEffect, useRef, useState } from 'react';
+import { Link, useNavigate, useSearchParams } from 'react-router-dom';
+
+import EmailLinkForm from '@/components/EmailLinkForm';
+import { LoginResponse, storeTokens, TotpChallenge } from '@/lib/auth';
+
+/**
+ * Logs in with the token of a login link, or asks for the email address to send one to when
+ * opened without a token.
+ */
+export default function MagicLink() {
+  const navigate = useNavigate();
+  const [searchParams] = useSearchParams();
+  const token = searchParams.get('token');
+  const [error, setError] = useState<string | null>(null);
+  // The token can only be used once, so guard against the double effect run in strict mode.
+  const started = useRef(false);
+
+  useEffect(() => {
+    if (token == null || started.current) {
+      return;
+    }
+    started.current = true;
+
+    async function doFetch(token: string) {
+      const response = await fetch('/auth/magic-link/login', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ token }),
+      });
+      if (!response.ok) {
+        setError('The link is invalid or has expired');
+        return;
+      }
+      const responseJson = (await response.json()) as LoginResponse | TotpChallenge;
+      if ('totp_challenge' in responseJson) {
+        navigate('/login', { state: { totpChallenge: responseJson.totp_challenge } });
+        return;
+      }
+      storeTokens(responseJson);
+      navigate('/');
+    }
+    void doFetch(token);
+  }, [navigate, token]);
+
+  if (token == null) {
+    return (
+      <div className="mx-auto flex max-w-sm flex-col gap-4 p-8">
+        <EmailLinkForm endpoint="/auth/magic-link" submitLabel="Email me a login link" />
+        <Link to="/login">Back to login</Link>
+      </div>
+    );
+  }
+
+  return (
+    <div className="mx-auto flex max-w-sm flex-col gap-4 p-8">
+      {error == null ? (
+        <p>Logging in...</p>
+      ) : (
+        <>
+          <p className="text-sm text-destructive">{error}</p>
+          <Link to="/login">Back to login</Link>
+        </>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,54 @@
+import { useEffect, useRef, useState } from 'react';
+import { Link, useNavigate, useSearchParams } from 'react-router-dom';
+
+import { LoginResponse, storeTokens } from '@/lib/auth';
+
+/** The page the OIDC provider redirects back to, which exchanges the code for a session. */
+export default function OidcCallback() {
+  const navigate = useNavigate();
+  const [searchParams] = useSearchParams();
+  const [error, setError] = useState<string | null>(null);
+  // The code can only be exchanged once, so guard against the double effect run in strict mode.
+  const started = useRef(false);
+
+  useEffect(() => {
+    if (started.current) {
+      return;
+    }
+    started.current = true;
+
+    async function doFetch() {
+      const code = searchParams.get('code');
+      const state = searchParams.get('state');
+      if (code == null || state == null) {
+        setError(searchParams.get('error_description') ?? 'Login was cancelled');
+        return;
+      }
+      const response = await fetch('/auth/oidc/callback', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ code, state }),
+      });
+      if (!response.ok) {
+        setError('Login failed');
+        return;
+      }
+      storeTokens((await response.json()) as LoginResponse);
+      navigate('/');
+    }
+    void doFetch();
+  }, [navigate, searchParams]);
+
+  return (
+    <div className="mx-auto flex max-w-sm flex-col gap-4 p-8">
+      {error == null ? (
+        <p>Logging in...</p>
+      ) : (
+        <>
+          <p className="text-sm text-destructive">{error}</p>
+          <Link to="/login">Back to login</Link>
+        </>
+      )}
+    </div>
+  );
+}
@@ -48,8 +48,8 @@ function PlanningInner() {
     return 'loading';
   }
 
-  const allTasks = gqlTasks!.tasks
-    .map((t) => Task.fromGQL(t))
+  const allTasks = gqlTasks!.tasks.edges
+    .map((e) => Task.fromGQL(e.node))
     .sort((t1, t2) => stringCompare(t1.id, t2.id));
 
   function getEpochOfType(epochType: EpochType): Epoch {
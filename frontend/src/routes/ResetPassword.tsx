@@ -0,0 +1,72 @@
+import { FormEvent, useState } from 'react';
+import { Link, useNavigate, useSearchParams } from 'react-router-dom';
+
+import EmailLinkForm from '@/components/EmailLinkForm';
+import { Button } from '@/components/ui/button';
+import { Input } from '@/components/ui/input';
+import { Label } from '@/components/ui/label';
+import { readApiError } from '@/lib/error';
+
+/**
+ * Sets a new password with the token of a password reset link, or asks for the email address to
+ * send one to when opened without a token.
+ */
+export default function ResetPassword() {
+  const navigate = useNavigate();
+  const [searchParams] = useSearchParams();
+  const token = searchParams.get('token');
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState<string | null>(null);
+
+  async function doFetch(token: string) {
+    const response = await fetch('/auth/password-reset/confirm', {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ token, password }),
+    });
+    if (response.status === 400) {
+      setError((await readApiError(response)).message);
+      return;
+    }
+    if (!response.ok) {
+      setError('The link is invalid or has expired');
+      return;
+    }
+    navigate('/login');
+  }
+
+  function onSubmit(event: FormEvent) {
+    event.preventDefault();
+    if (token != null) {
+      void doFetch(token);
+    }
+  }
+
+  if (token == null) {
+    return (
+      <div className="mx-auto flex max-w-sm flex-col gap-4 p-8">
+        <EmailLinkForm endpoint="/auth/password-reset" submitLabel="Email me a reset link" />
+        <Link to="/login">Back to login</Link>
+      </div>
+    );
+  }
+
+  return (
+    <form className="mx-auto flex max-w-sm flex-col gap-4 p-8" onSubmit={onSubmit}>
+      <div className="flex flex-col gap-2">
+        <Label htmlFor="password">New password</Label>
+        <Input
+          id="password"
+          type="password"
+          autoComplete="new-password"
+          value={password}
+          onChange={(e) => {
+            setPassword(e.target.value);
+          }}
+        />
+      </div>
+      {error != null && <p className="text-sm text-destructive">{error}</p>}
+      <Button type="submit">Set password</Button>
+    </form>
+  );
+}
@@ -30,7 +30,7 @@ function TasksInner() {
   if (loading) return <p>Loading...</p>;
   if (error) return <p>Error : {error.message}</p>;
 
-  const tasks = gqlTasks!.tasks.map((t) => Task.fromGQL(t));
+  const tasks = gqlTasks!.tasks.edges.map((e) => Task.fromGQL(e.node));
   const exactlyScheduledTasks = tasks.filter(
     (t) => t.scheduledOn.epochType() === epoch.epochType(),
   );